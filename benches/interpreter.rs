@@ -0,0 +1,41 @@
+//! Interpreter benchmarks on representative programs. Criterion reports the
+//! executed instructions per second as element throughput. Compare against a
+//! baseline with `cargo bench -- --save-baseline before` and later
+//! `cargo bench -- --baseline before`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use my_vm::{Machine, Program, Throughput};
+
+/// Benchmarked programs by name. They need 5 side registers.
+const PROGRAMS: [(&str, &str); 4] = [
+	("arithmetic", include_str!("programs/arithmetic.asm")),
+	("memcpy", include_str!("programs/memcpy.asm")),
+	("fibonacci", include_str!("programs/fibonacci.asm")),
+	("format", include_str!("programs/format.asm")),
+];
+
+fn interpreter(c: &mut Criterion) {
+	let mut group = c.benchmark_group("interpreter");
+	for (name, source) in PROGRAMS {
+		let program: Program = source.parse().expect("benchmark program is valid");
+		let executable = program.to_executable().expect("benchmark program compiles");
+		let machine = Machine::<5>::from_executable(executable)
+			.expect("benchmark program fits the machine")
+			.with_captured_output();
+		let instructions = Throughput::measure(&machine, 1)
+			.expect("benchmark program runs")
+			.instructions_per_iteration();
+		group.throughput(criterion::Throughput::Elements(instructions));
+		group.bench_function(name, |b| {
+			b.iter_batched(
+				|| machine.clone(),
+				|mut machine| machine.run().expect("benchmark program runs"),
+				criterion::BatchSize::SmallInput,
+			);
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, interpreter);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+# Tight arithmetic loop: sum of i * 3 + 7 for i = 100000..1, wrapping.
+setRegister 0 100000
+setRegister 1 0
+setRegister 2 3
+setRegister 3 7
+label loop
+    swap 0
+    push
+    mul 2
+    add 3
+    add 1
+    swap 1
+    pop
+    swap 0
+    decrementRegister 0
+    jumpNonzero loop
+halt
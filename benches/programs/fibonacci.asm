@@ -0,0 +1,26 @@
+# Recursive fibonacci of 20.
+jump main
+
+label fibonacci
+    setRegister 0 2
+    compare 0
+    jumpGreater fibonacci.recurse
+    set 1
+    return
+label fibonacci.recurse
+    decrement
+    push
+    call fibonacci
+    swap 1
+    pop
+    pushRegister 1
+    decrement
+    call fibonacci
+    popRegister 1
+    add 1
+    return
+
+label main
+set 20
+call fibonacci
+halt
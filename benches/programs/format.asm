@@ -0,0 +1,17 @@
+# String formatting: print the numbers 1000..1 as decimal strings separated by
+# spaces.
+include "std/convert.asm"
+
+setRegister 4 1000
+label loop
+    pushRegister 4
+    popRegister 0
+    setRegister 1 0
+    call itoa
+    set 0
+    syscall 2
+    set 32
+    syscall 4
+    decrementRegister 4
+    jumpNonzero loop
+halt
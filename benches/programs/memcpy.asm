@@ -0,0 +1,12 @@
+# Memory copy: copy 1024 bytes between two buffers 64 times.
+include "std/string.asm"
+
+setRegister 4 64
+label loop
+    setRegister 0 2048
+    setRegister 1 0
+    setRegister 2 1024
+    call memcpy
+    decrementRegister 4
+    jumpNonzero loop
+halt
@@ -0,0 +1,50 @@
+use my_vm::{Instruction, Machine, Program, MAX_ALIGNMENT};
+
+const PROGRAM: &str = r#"
+.data 100
+dataString ab
+align 8
+label number
+dataBytes 0 0 0 7
+.code
+set 1
+align 16
+label aligned
+load32 number
+syscall 1
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	// The set instruction takes 5 bytes, followed by 11 nops.
+	assert_eq!(program.symbols().address("aligned"), Some(16));
+	assert_eq!(program.iter().filter(|(_, i)| **i == Instruction::Nop).count(), 11);
+	// The static data is padded with zeros up to the number at 104.
+	assert_eq!(program.data(), b"ab\0\0\0\0\0\x07");
+	let mut machine =
+		Machine::<0>::from_executable(program.to_executable()?)?.with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), "7");
+
+	// Aligned positions need no padding.
+	let mut program = Program::new();
+	assert_eq!(program.add_align(4)?, 0);
+	program.add_instruction(Instruction::Set(1));
+	assert_eq!(program.add_align(1)?, 0);
+	assert_eq!(program.add_align(MAX_ALIGNMENT)?, 4091);
+
+	// Zero and huge alignments are rejected instead of padding without end.
+	assert!(program.add_align(0).is_err());
+	assert!(program.align_static_data(0).is_err());
+	let error = "align 2147483648".parse::<Program>().unwrap_err();
+	assert_eq!(format!("{error:#}"), "line 1: Alignment 2147483648 exceeds the maximum of 4096");
+	let error = ".data\nalign 8192".parse::<Program>().unwrap_err();
+	assert_eq!(format!("{error:#}"), "line 2: Alignment 8192 exceeds the maximum of 4096");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,69 @@
+use my_vm::{Analysis, Program};
+
+/// Nested calls with saved registers and static memory.
+const PROGRAM: &str = r#"
+.data 512
+label message
+dataString Hi
+.code
+entry main
+
+// Uses 4 bytes of stack.
+label inner
+pushRegister 0
+popRegister 0
+return
+
+// Uses 8 bytes of stack, plus the call of 4 + 4 bytes.
+label outer
+push
+pushRegister 1
+call inner
+popRegister 1
+pop
+return
+
+label main
+store32 1000
+call outer
+halt
+"#;
+
+/// Recursion cannot be bounded.
+const RECURSIVE: &str = r#"
+label countdown
+decrement
+jumpZero done
+call countdown
+label done
+return
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let analysis = program.analyze();
+	println!("{analysis}");
+	assert_eq!(
+		analysis,
+		Analysis {
+			// main calls outer (4 + 8), which calls inner (4 + 4).
+			max_stack_depth: Some(20),
+			unbounded: Vec::new(),
+			// The 32 bit store at 1000 is above the data section at 512.
+			static_memory_end: 1004,
+		}
+	);
+	assert_eq!(analysis.suggested_memory_size(), Some(1024));
+
+	let program: Program = RECURSIVE.parse()?;
+	let analysis = program.analyze();
+	assert_eq!(analysis.max_stack_depth, None);
+	assert_eq!(analysis.unbounded, ["Function countdown is recursive"]);
+	assert_eq!(analysis.suggested_memory_size(), None);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,78 @@
+use my_vm::{Instruction, Machine, Program};
+
+/// Prints its data segment and static data, then calls `b`.
+fn program_a() -> anyhow::Result<Program> {
+	let mut program = Program::new();
+	program.set_data_base(64)?;
+	let text = program.add_static_data(c"S".to_bytes_with_nul())?;
+	program.add_jump_label("a");
+	let data = program.add_data(c"A".to_bytes_with_nul());
+	program.add_label("a")?;
+	program.add_instruction(Instruction::Set(0));
+	program.add_copy_data(data)?;
+	program.add_syscall(0);
+	program.add_instruction(Instruction::Set(text));
+	program.add_syscall(0);
+	program.add_call_label("b");
+	program.add_halt();
+	program.set_memory_size(128);
+	Ok(program)
+}
+
+/// Function `b` printing its data segment and static data.
+fn program_b() -> anyhow::Result<Program> {
+	let mut program = Program::new();
+	program.set_data_base(66)?;
+	let text = program.add_static_data(c"T".to_bytes_with_nul())?;
+	program.add_label("b")?;
+	program.add_jump_label("b.start");
+	let data = program.add_data(c"B".to_bytes_with_nul());
+	program.add_label("b.start")?;
+	program.add_instruction(Instruction::Set(16));
+	program.add_copy_data(data)?;
+	program.add_syscall(0);
+	program.add_instruction(Instruction::Set(text));
+	program.add_syscall(0);
+	program.add_return();
+	Ok(program)
+}
+
+fn main() -> anyhow::Result<()> {
+	let mut program = program_a()?;
+	// The call to `b` cannot be resolved on its own.
+	assert!(program.compile().is_err());
+	let b = program_b()?;
+	let b_jump = b.iter().next().and_then(|(_, instruction)| instruction.code_address()).unwrap();
+
+	let offset = program.append(b)?;
+	assert_eq!(offset, 9);
+	let base = program.address_of(offset).unwrap();
+	let targets =
+		program.iter().map(|(_, instruction)| instruction.code_address()).collect::<Vec<_>>();
+	// The call in the first program is resolved to the appended function.
+	assert_eq!(targets[7], Some(base));
+	assert_eq!(program.symbols().address("b"), Some(base));
+	// The jump and copy of the appended program are rebased.
+	assert_eq!(targets[offset], Some(b_jump + base));
+	assert_eq!(targets[offset], program.address_of(offset + 2));
+	assert_eq!(targets[offset + 3], Some(program.address_of(offset + 1).unwrap() + 5));
+	// The data sections are concatenated.
+	assert_eq!(program.data_base(), 64);
+	assert_eq!(program.data(), b"S\0T\0");
+
+	Machine::<0>::from_executable(program.to_executable()?)?.run()?;
+
+	// Labels must be unique, and data sections adjacent.
+	let mut program = program_a()?;
+	program.add_label("b")?;
+	assert!(program.append(program_b()?).is_err());
+	let mut program = program_a()?;
+	program.add_static_data(b"gap")?;
+	assert!(program.append(program_b()?).is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
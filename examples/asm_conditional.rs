@@ -0,0 +1,39 @@
+use my_vm::{Machine, Program};
+
+const PROGRAM: &str = r#"
+// Use a default memory size, unless it was defined from the outside.
+.ifndef MEMORY_SIZE
+.define MEMORY_SIZE 1024
+.endif
+
+// Place the string at the end of the memory on big machines.
+.if MEMORY_SIZE >= 2048
+.define STRING_ADDRESS 1024
+.else
+.define STRING_ADDRESS 0
+.endif
+
+jump main
+label data
+dataString Hello world!
+
+label main
+set STRING_ADDRESS ; copyCodeMemory data ; syscall 0
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	for memory_size in [1024, 4096] {
+		let program = Program::from_str_with_defines(PROGRAM, [("MEMORY_SIZE", memory_size)])?;
+		let executable = program.compile()?;
+
+		let mut machine = Machine::<0>::new(executable, memory_size);
+		machine.run()?;
+	}
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
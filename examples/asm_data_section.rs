@@ -0,0 +1,36 @@
+use my_vm::{Machine, Program};
+
+const PROGRAM: &str = r#"
+// Static data is placed in the data section, which is loaded to memory address 512
+// before execution. Labels in the data section are constants with the memory address.
+.data 512
+label greeting
+dataString Hello world!
+align 4
+label numbers
+dataBytes 0 0 0 42
+.code
+
+// Print the string straight from memory, no copying necessary.
+set greeting
+syscall 0
+// Print the 32 bit number.
+load32 numbers
+syscall 1
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let executable = program.to_executable()?;
+
+	let mut machine = Machine::<0>::from_executable(executable)?;
+	machine.run()?;
+	println!();
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
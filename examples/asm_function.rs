@@ -1,8 +1,8 @@
 use my_vm::{Machine, Program};
 
 const PROGRAM: &str = r#"
-// Jump straight to the main function.
-jump main
+// Start execution at the main function.
+entry main
 
 // Data segment to hold our string.
 label data
@@ -34,9 +34,9 @@ halt
 
 fn main() -> anyhow::Result<()> {
 	let program: Program = PROGRAM.parse()?;
-	let executable = program.compile();
+	let executable = program.compile()?;
 
-	let mut machine = Machine::<0>::new(executable, 1024);
+	let mut machine = Machine::<0>::new(executable, 1024).with_entry_point(program.entry_point());
 	machine.run()?;
 	Ok(())
 }
@@ -1,10 +1,6 @@
 use my_vm::{Machine, Program};
 
 const PROGRAM: &str = r#"
-# Add data segment to hold our string.
-label str
-dataString Hello world!
-
 # Set the main register to 10 to point to the address we want to write the string to.
 set 10
 # Load the data segment into machine memory at the address in the main register.
@@ -14,11 +10,16 @@ syscall 0
 
 # Halt the machine.
 halt
+
+# Add data segment to hold our string. It is placed after the code, as
+# executing data is an error.
+label str
+dataString Hello world!
 "#;
 
 fn main() -> anyhow::Result<()> {
 	let program: Program = PROGRAM.parse()?;
-	let executable = program.compile();
+	let executable = program.compile()?;
 
 	let mut machine = Machine::<0>::new(executable, 1024);
 	machine.run()?;
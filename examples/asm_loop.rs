@@ -24,7 +24,7 @@ halt
 
 fn main() -> anyhow::Result<()> {
 	let program: Program = PROGRAM.parse()?;
-	let executable = program.compile();
+	let executable = program.compile()?;
 
 	let mut machine = Machine::<0>::new(executable, 1024);
 	machine.run()?;
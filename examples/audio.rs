@@ -0,0 +1,56 @@
+use my_vm::{Audio, Instruction, Machine, Program, Sound};
+
+/// Play a short melody, a pause and a square wave from memory.
+const PROGRAM: &str = r#"
+setRegister 0 150
+set 440 ; syscall 12
+set 554 ; syscall 12
+set 659 ; syscall 12
+setRegister 0 50
+set 0 ; syscall 12
+
+jump main
+label wave
+dataBytes 255 255 255 255 0 0 0 0
+label main
+set 100 ; copyCodeMemory wave
+setRegister 0 8
+syscall 13
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let audio = Audio::recording();
+	let mut machine =
+		Machine::<1>::from_executable(program.to_executable()?)?.with_audio(audio.clone());
+	machine.run()?;
+	let tone = |frequency, duration_ms| Sound::Tone { frequency, duration_ms };
+	assert_eq!(
+		audio.recorded(),
+		[
+			tone(440, 150),
+			tone(554, 150),
+			tone(659, 150),
+			tone(0, 50),
+			Sound::Samples(vec![255, 255, 255, 255, 0, 0, 0, 0]),
+		]
+	);
+	// Recording does not wait for anything.
+	audio.wait();
+
+	// Sounds need an audio device and samples must be in memory.
+	let mut machine = Machine::<1>::new(Vec::new(), 64);
+	let error = machine.execute_instruction(Instruction::Syscall(12)).unwrap_err();
+	assert_eq!(error.to_string(), "No audio device attached");
+	let mut machine = Machine::<1>::new(Vec::new(), 64).with_audio(Audio::recording());
+	machine.execute_instruction(Instruction::Set(60))?;
+	machine.execute_instruction(Instruction::SetRegister(0, 8))?;
+	assert!(machine.execute_instruction(Instruction::Syscall(13)).is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,35 @@
+use my_vm::{Machine, Program, Throughput};
+
+/// Count down from 1000, printing the result.
+const PROGRAM: &str = r#"
+setRegister 0 1000
+label loop
+decrementRegister 0
+jumpNonzero loop
+set 0
+syscall 1
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let machine = Machine::<1>::from_executable(program.to_executable()?)?.with_captured_output();
+
+	let throughput = Throughput::measure(&machine, 5)?;
+	println!("{throughput}");
+	assert_eq!(throughput.iterations(), 5);
+	// Setup, 1000 loop iterations with 2 instructions and the output.
+	assert_eq!(throughput.instructions_per_iteration(), 2004);
+	assert_eq!(throughput.instructions(), 5 * 2004);
+	assert!(throughput.instructions_per_second() > 0.0);
+
+	// The fuel of the machine limits every run.
+	let error = Throughput::measure(&machine.with_fuel(100), 5).unwrap_err();
+	assert_eq!(format!("{error}"), "Benchmark iteration 0 failed");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,80 @@
+use std::fs;
+
+use my_vm::{BlockDevice, Instruction, Machine, Program};
+
+/// Write a message to sector 2 of the block device.
+const WRITER: &str = r#"
+jump main
+label message
+dataString persistent
+label main
+set 0 ; copyCodeMemory message
+setRegister 0 0
+set 2
+syscall 7
+halt
+"#;
+
+/// Read sector 2 into memory, print it and the number of sectors.
+const READER: &str = r#"
+setRegister 0 100
+set 2
+syscall 6
+set 100
+syscall 0
+syscall 8
+syscall 1
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let path = std::env::temp_dir().join(format!("my_vm_block_device_{}.img", std::process::id()));
+	let result = run(&path);
+	fs::remove_file(&path)?;
+	result
+}
+
+fn run(path: &std::path::Path) -> anyhow::Result<()> {
+	// The data survives between machines through the backing file.
+	let device = BlockDevice::create(path, 16, 4)?;
+	assert_eq!((device.sector_size(), device.sectors()), (16, 4));
+	let program: Program = WRITER.parse()?;
+	let mut machine =
+		Machine::<1>::from_executable(program.to_executable()?)?.with_block_device(device);
+	machine.run()?;
+	assert_eq!(fs::metadata(path)?.len(), 64);
+	assert_eq!(&fs::read(path)?[32..43], b"persistent\0");
+
+	let program: Program = READER.parse()?;
+	let mut machine = Machine::<1>::from_executable(program.to_executable()?)?
+		.with_captured_output()
+		.with_block_device(BlockDevice::open(path, 16)?);
+	machine.run()?;
+	assert_eq!(machine.take_output(), "persistent\n4");
+
+	// A partial last sector is padded with zeros.
+	fs::write(path, [1, 2, 3])?;
+	let device = BlockDevice::open(path, 4)?;
+	assert_eq!(device.sectors(), 1);
+	let mut buffer = [0xFF; 4];
+	device.read_sector(0, &mut buffer)?;
+	assert_eq!(buffer, [1, 2, 3, 0]);
+
+	// Invalid accesses are runtime errors.
+	let mut machine = Machine::<1>::new(Vec::new(), 64).with_block_device(device);
+	machine.execute_instruction(Instruction::Set(1))?;
+	let error = machine.execute_instruction(Instruction::Syscall(6)).unwrap_err();
+	assert_eq!(error.to_string(), "Sector 1 is out of range, the block device has 1 sectors");
+	machine.execute_instruction(Instruction::Set(0))?;
+	machine.execute_instruction(Instruction::SetRegister(0, 62))?;
+	assert!(machine.execute_instruction(Instruction::Syscall(7)).is_err());
+	let mut machine = Machine::<1>::new(Vec::new(), 64);
+	let error = machine.execute_instruction(Instruction::Syscall(8)).unwrap_err();
+	assert_eq!(error.to_string(), "No block device attached");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,51 @@
+use my_vm::{Machine, Program};
+
+/// Print the first bytes of a nul terminated string with an explicit length
+/// and then the whole string.
+const PROGRAM: &str = r#"
+jump main
+label text
+dataString Hello, world!
+label main
+set 0 ; copyCodeMemory text
+setRegister 0 5
+syscall 20
+set 32 ; syscall 4
+set 0
+syscall 2
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let executable = program.to_executable()?;
+
+	let mut machine = Machine::<1>::from_executable(executable.clone())?.with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), "Hello Hello, world!");
+
+	// Strings are read up to the maximum length only, so a missing terminator
+	// cannot leak the rest of memory.
+	let mut machine = Machine::<1>::from_executable(executable.clone())?
+		.with_captured_output()
+		.with_max_string_length(8);
+	let error = machine.run().unwrap_err();
+	assert_eq!(format!("{:#}", error.root_cause()), "String is not terminated within 8 bytes");
+	assert_eq!(machine.take_output(), "Hello ");
+
+	// Strings with explicit length must not exceed it either.
+	let mut machine =
+		Machine::<1>::from_executable(executable)?.with_captured_output().with_max_string_length(4);
+	let error = machine.run().unwrap_err();
+	assert_eq!(
+		format!("{:#}", error.root_cause()),
+		"String of 5 bytes at 0 exceeds the maximum length of 4 bytes"
+	);
+	assert_eq!(machine.take_output(), "");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
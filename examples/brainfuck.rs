@@ -0,0 +1,55 @@
+use my_vm::{compile_brainfuck, Machine, BRAINFUCK_TAPE_SIZE};
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+/// Reverse the input line, stress testing nested loops.
+const REVERSE: &str = "
+>,[>,]     read the input into cells 1 to n
+<[.<]      print them backwards
+";
+
+/// Compile and run the source with the input, returning the output bytes.
+fn run_bytes(source: &str, input: &[u8]) -> anyhow::Result<Vec<u8>> {
+	let program = compile_brainfuck(source, BRAINFUCK_TAPE_SIZE)?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?
+		.with_fuel(10_000_000)
+		.with_captured_output()
+		.with_input(input);
+	machine.run()?;
+	Ok(machine.take_output_bytes())
+}
+
+/// Compile and run the source with the input, returning the output.
+fn run(source: &str, input: &str) -> anyhow::Result<String> {
+	Ok(String::from_utf8(run_bytes(source, input.as_bytes())?)?)
+}
+
+fn main() -> anyhow::Result<()> {
+	let output = run(HELLO_WORLD, "")?;
+	print!("{output}");
+	assert_eq!(output, "Hello World!\n");
+	assert_eq!(run(REVERSE, "stressed")?, "desserts");
+
+	// Cells wrap around, the end of the input reads as 0.
+	assert_eq!(run_bytes("-.+.,+++++[-]+++.", b"")?, b"\xff\0\x03");
+	// Bytes are written as they are, not as UTF-8 encoded characters.
+	assert_eq!(run_bytes(",.,.,.", &[0x80, 0xc3, 0xfe])?, [0x80, 0xc3, 0xfe]);
+	// Runs of commands are folded.
+	assert_eq!(compile_brainfuck("+++++>>>>", 10)?.len(), 9);
+
+	let error = compile_brainfuck("+[\n[-]", 10).unwrap_err();
+	assert_eq!(error.to_string(), "Unmatched [ at line 1, column 2");
+	let error = compile_brainfuck("+]", 10).unwrap_err();
+	assert_eq!(error.to_string(), "Unmatched ] at line 1, column 2");
+
+	// Moving outside of the tape is an error.
+	let program = compile_brainfuck("<+", 10)?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?;
+	assert!(machine.run().is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,81 @@
+use my_vm::{Instruction, Machine, Program, FRAME_POINTER};
+
+/// Build a program computing `weighted(a, b, c) = a + 2 * b + 3 * c` and the
+/// factorial recursively with the calling convention helpers.
+fn program() -> anyhow::Result<Program> {
+	let mut program = Program::new();
+	// main: print weighted(1, 2, 3) and factorial(10).
+	program.add_instruction(Instruction::SetRegister(5, 99));
+	for (register, value) in [(0, 1), (1, 2), (2, 3)] {
+		program.add_instruction(Instruction::SetRegister(register, value));
+	}
+	program.push_args(&[0, 1, 2]);
+	program.call_function("weighted");
+	program.add_syscall(1);
+	program.add_instruction(Instruction::SetRegister(0, 10));
+	program.push_args(&[0]);
+	program.call_function("factorial");
+	program.add_syscall(1);
+	// Registers from the frame pointer on are preserved.
+	program.add_instruction(Instruction::Swap(5));
+	program.add_syscall(1);
+	program.add_halt();
+
+	// weighted(a, b, c) with one local variable for the sum.
+	program.begin_function("weighted", 3, 1)?;
+	for (arg, weight) in [(0, 1), (1, 2), (2, 3)] {
+		program.load_arg(arg)?;
+		program.add_instruction(Instruction::SetRegister(0, weight));
+		program.add_instruction(Instruction::Mul(0));
+		program.add_instruction(Instruction::Swap(1));
+		program.load_local(0)?;
+		program.add_instruction(Instruction::Add(1));
+		program.store_local(0)?;
+	}
+	program.load_local(0)?;
+	program.end_function()?;
+
+	// factorial(n) = n < 2 ? 1 : n * factorial(n - 1)
+	program.begin_function("factorial", 1, 0)?;
+	program.load_arg(0)?;
+	program.add_instruction(Instruction::SetRegister(0, 2));
+	program.add_instruction(Instruction::Compare(0));
+	program.add_jump_greater_equal_label("factorial_recurse");
+	program.add_instruction(Instruction::Set(1));
+	program.add_function_return()?;
+	program.add_label("factorial_recurse")?;
+	program.add_instruction(Instruction::Decrement);
+	program.add_instruction(Instruction::Swap(0));
+	program.push_args(&[0]);
+	program.call_function("factorial");
+	program.add_instruction(Instruction::Swap(1));
+	program.load_arg(0)?;
+	program.add_instruction(Instruction::Mul(1));
+	program.end_function()?;
+	Ok(program)
+}
+
+fn main() -> anyhow::Result<()> {
+	let program = program()?;
+	assert_eq!(program.required_side_registers(), u16::from(FRAME_POINTER) + 2);
+	assert!(program.lint(None).is_empty());
+	let mut machine =
+		Machine::<6>::from_executable(program.to_executable()?)?.with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), "14362880099");
+	// The stack is balanced after all calls.
+	assert_eq!(machine.stack_pointer(), machine.memory_size());
+
+	// Misuse is reported.
+	let mut program = Program::new();
+	assert!(program.load_arg(0).is_err());
+	program.begin_function("f", 1, 0)?;
+	assert!(program.load_local(0).is_err());
+	assert!(program.begin_function("g", 0, 0).is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
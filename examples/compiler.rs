@@ -0,0 +1,70 @@
+use my_vm::{compile, Machine};
+
+const PROGRAM: &str = r#"
+// Recursive fibonacci.
+fn fib(n) {
+	if (n < 2) {
+		return n;
+	}
+	return fib(n - 1) + fib(n - 2);
+}
+
+fn print_line(label, n) {
+	print(label);
+	print_number(n);
+	println("");
+}
+
+fn main() {
+	let i = 0;
+	while (i <= 10) {
+		if (i % 5 == 0 && i != 0) {
+			print_line("fib ", fib(i));
+		} else if (!(i < 9)) {
+			let square = i * i;
+			print_line("square ", square);
+		}
+		i = i + 1;
+	}
+	print_line("negated ", -1 / 2147483648);
+	print_line("chars ", 'a' + ('b' - 'a') * 2);
+	println("done");
+}
+"#;
+
+/// Compile and run the source, returning the output.
+fn run(source: &str) -> anyhow::Result<String> {
+	let program = compile(source)?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?
+		.with_fuel(1_000_000)
+		.with_captured_output();
+	machine.run()?;
+	Ok(machine.take_output())
+}
+
+fn main() -> anyhow::Result<()> {
+	let output = run(PROGRAM)?;
+	print!("{output}");
+	assert_eq!(output, "fib 5\nsquare 81\nfib 55\nnegated 1\nchars 99\ndone\n");
+
+	// Functions are labelled, instructions carry their source lines.
+	let program = compile(PROGRAM)?;
+	assert_eq!(program.required_side_registers(), 2);
+	assert!(program.symbols().iter().any(|(name, _)| name == "fib"));
+	assert!(program.listing().contains("return fib(n - 1) + fib(n - 2);"));
+
+	// Errors name the line.
+	let error = compile("fn main() {\n\tx = 1;\n}").unwrap_err();
+	assert_eq!(error.to_string(), "line 2: Unknown variable x");
+	let error = compile("fn main() {\n\tfib(1);\n}").unwrap_err();
+	assert_eq!(error.to_string(), "line 2: Unknown function fib");
+	let error = compile("fn main() {\n\treturn 1\n}").unwrap_err();
+	assert_eq!(error.to_string(), "line 3: Expected `;`");
+	assert!(compile("fn start() {}").is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
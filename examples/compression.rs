@@ -0,0 +1,60 @@
+use my_vm::{Executable, Machine, Program};
+
+/// Prints a string from a large, well compressible data section.
+const PROGRAM: &str = r#"
+set 100
+syscall 2
+halt
+.data 100
+dataString compressed
+dataBytes 0
+"#;
+
+/// Replace the uncompressed length stored after the data base address.
+fn set_uncompressed_len(bytes: &mut [u8], data_base: u32, old: u32, new: u32) {
+	let pattern = [data_base.to_be_bytes(), old.to_be_bytes()].concat();
+	let pos = bytes
+		.windows(pattern.len())
+		.position(|window| window == pattern)
+		.expect("compressed data section header");
+	bytes[pos + 4..pos + 8].copy_from_slice(&new.to_be_bytes());
+}
+
+fn main() -> anyhow::Result<()> {
+	let mut program: Program = PROGRAM.parse()?;
+	program.add_static_data(vec![0; 10_000])?;
+	program.set_memory_size(16_384);
+	let mut executable = program.to_executable()?;
+	let data_len = executable.data.len() as u32;
+	let uncompressed = executable.to_bytes();
+	executable.compress_data = true;
+	let bytes = executable.to_bytes();
+	assert!(bytes.len() < uncompressed.len() / 10);
+
+	let decoded = Executable::from_bytes(&bytes)?;
+	assert!(decoded.compress_data);
+	assert_eq!(decoded.data, executable.data);
+	let mut machine = Machine::<0>::from_executable(decoded)?.with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), "compressed");
+
+	// A wrong uncompressed length is rejected, lengths beyond the address space
+	// before decompressing anything.
+	let mut wrong = bytes.clone();
+	set_uncompressed_len(&mut wrong, 100, data_len, data_len - 1);
+	let error = Executable::from_bytes(&wrong).unwrap_err();
+	assert_eq!(error.to_string(), "Compressed data section has the wrong length");
+	let mut huge = bytes;
+	set_uncompressed_len(&mut huge, 100, data_len, u32::MAX);
+	let error = Executable::from_bytes(&huge).unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"Data section of 4294967295 bytes at 100 exceeds the address space"
+	);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
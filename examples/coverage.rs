@@ -0,0 +1,51 @@
+use my_vm::{Coverage, Machine, Program};
+
+/// Program printing whether the given number is zero.
+fn program(number: u32) -> anyhow::Result<Program> {
+	let source = format!(
+		r#"
+set {number}
+setRegister 0 0
+compare 0
+jumpEqual is_zero
+label not_zero
+syscall 1
+halt
+label is_zero
+syscall 1
+halt
+"#
+	);
+	source.parse()
+}
+
+/// Run the program with the given number and return its coverage.
+fn run(number: u32) -> anyhow::Result<Coverage> {
+	let mut machine =
+		Machine::<1>::from_executable(program(number)?.to_executable()?)?.with_execution_counts();
+	machine.run()?;
+	println!();
+	Coverage::from_machine(&machine)
+}
+
+fn main() -> anyhow::Result<()> {
+	// Both numbers result in the same code layout.
+	let program = program(0)?;
+
+	// Only one branch is covered by a single run.
+	let mut coverage = run(5)?;
+	println!("{}", coverage.report(&program));
+	assert_eq!(coverage.uncovered_labels(&program), ["is_zero"]);
+	assert_eq!(coverage.covered_instructions(&program), (6, 8));
+
+	// Merging the coverage of a second run covers everything.
+	coverage.merge(&run(0)?);
+	println!("{}", coverage.report(&program));
+	assert!(coverage.uncovered_labels(&program).is_empty());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,59 @@
+use my_vm::{Machine, Program};
+
+/// Falls into the data segment after printing.
+const FALL_THROUGH: &str = r#"
+set 1
+syscall 1
+label message
+dataString Hi
+halt
+"#;
+
+/// Jumps into the middle of the data segment, whose bytes decode as no-ops.
+const JUMP_INTO: &str = r#"
+jump main
+label bytes
+dataBytes 0 0
+label main
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	// Executing a data segment is an error naming its address.
+	let program: Program = FALL_THROUGH.parse()?;
+	let executable = program.to_executable()?;
+	let mut machine = Machine::<0>::from_executable(executable.clone())?.with_captured_output();
+	let error = machine.run().unwrap_err();
+	assert_eq!(
+		format!("{error:#}"),
+		"Runtime error at <unknown>:5 (label message) in `dataString Hi`: Executed data as code at 7"
+	);
+	assert_eq!(machine.take_output(), "1");
+
+	// The legacy behavior skips the data segment.
+	let mut machine =
+		Machine::<0>::from_executable(executable)?.with_captured_output().with_data_execution(true);
+	machine.run()?;
+	assert_eq!(machine.take_output(), "1");
+
+	// Jumping into the payload is detected as well.
+	let program: Program = JUMP_INTO.parse()?;
+	let mut code = program.compile()?;
+	// Retarget the jump to the second data byte.
+	let target = (code.len() - 2) as u32;
+	code[1..5].copy_from_slice(&target.to_be_bytes());
+	let mut machine = Machine::<0>::new(code.clone(), 64);
+	let error = machine.run().unwrap_err();
+	assert_eq!(
+		format!("{error:#}"),
+		format!("Runtime error at code address {target}: Executed data as code at {target}")
+	);
+	let mut machine = Machine::<0>::new(code, 64).with_data_execution(true);
+	machine.run()?;
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
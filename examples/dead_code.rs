@@ -0,0 +1,60 @@
+use my_vm::{Instruction, Machine, Program};
+
+fn program() -> anyhow::Result<Program> {
+	let mut program = Program::new();
+	program.add_jump_label("main");
+	// Only the first data segment is copied, the second is never used.
+	let data = program.add_data(c"A".to_bytes_with_nul());
+	program.add_data(c"B".to_bytes_with_nul());
+	// Nothing jumps here.
+	program.add_syscall(1);
+	program.add_label("main")?;
+	program.add_instruction(Instruction::Set(0));
+	program.add_copy_data(data)?;
+	program.add_syscall(0);
+	program.add_jump_label("end");
+	// Skipped by the jump.
+	program.add_syscall(1);
+	program.add_label("end")?;
+	program.add_halt();
+	Ok(program)
+}
+
+/// The program without dead code.
+fn expected() -> anyhow::Result<Program> {
+	let mut program = Program::new();
+	program.add_jump_label("main");
+	let data = program.add_data(c"A".to_bytes_with_nul());
+	program.add_label("main")?;
+	program.add_instruction(Instruction::Set(0));
+	program.add_copy_data(data)?;
+	program.add_syscall(0);
+	program.add_jump_label("end");
+	program.add_label("end")?;
+	program.add_halt();
+	Ok(program)
+}
+
+fn main() -> anyhow::Result<()> {
+	let mut program = program()?;
+	Machine::<0>::new(program.compile()?, 64).run()?;
+	assert_eq!(program.unreachable_instructions(), [2, 3, 8]);
+
+	// The data segment reached only via the copy is kept, and the copy and the
+	// jumps are rewritten.
+	assert_eq!(program.remove_dead_code(), 3);
+	let expected = expected()?;
+	assert_eq!(program.compile()?, expected.compile()?);
+	assert_eq!(program.symbols(), expected.symbols());
+	Machine::<0>::new(program.compile()?, 64).run()?;
+
+	// Nothing is left to remove.
+	assert!(program.unreachable_instructions().is_empty());
+	assert_eq!(program.remove_dead_code(), 0);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
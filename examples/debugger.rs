@@ -0,0 +1,49 @@
+use my_vm::{Debugger, Machine, Program};
+
+const PROGRAM: &str = r#"
+entry main
+
+// Print the number in the main register.
+label function
+syscall 1
+return
+
+label main
+setRegister 0 3
+label loop
+call function
+decrementRegister 0
+jumpNonzero loop
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let machine = Machine::<1>::from_executable(program.to_executable()?)?;
+	let mut debugger = Debugger::new(machine);
+
+	// Stop inside the function and inspect the return address on the stack.
+	println!("{}", debugger.execute("break function")?);
+	println!("{}", debugger.execute("continue")?);
+	let stack = debugger.execute("stack 1")?;
+	println!("{stack}");
+	assert!(stack.contains("(loop+"));
+	let backtrace = debugger.execute("backtrace")?;
+	println!("{backtrace}");
+	assert_eq!(backtrace.lines().count(), 2);
+	println!("{}", debugger.execute("registers")?);
+	println!("{}", debugger.execute("memory 0 16")?);
+
+	// Remove the breakpoint again and run until the program halts.
+	println!("{}", debugger.execute("delete function")?);
+	println!("{}", debugger.execute("step 2")?);
+	println!("{}", debugger.execute("c")?);
+	assert!(debugger.is_halted());
+	assert!(debugger.execute("unknown").is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
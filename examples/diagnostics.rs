@@ -0,0 +1,51 @@
+use my_vm::{Diagnostic, Program};
+
+/// Program with several mistakes, which are all reported at once.
+const PROGRAM: &str = r#"
+set 1
+frobnicate 2
+setRegister 0 many
+.if UNDEFINED > 1
+halt
+.endif
+jump nowhere
+label done
+call don
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let diagnostics = Program::parse_with_diagnostics(PROGRAM).unwrap_err();
+	let lines = diagnostics.iter().map(|diagnostic| diagnostic.line).collect::<Vec<_>>();
+	assert_eq!(lines, [Some(3), Some(4), Some(5), Some(8), Some(10)]);
+	assert_eq!(
+		diagnostics[0],
+		Diagnostic {
+			file: None,
+			line: Some(3),
+			message: "Unknown command or wrong number of arguments: frobnicate".to_owned(),
+		}
+	);
+	assert_eq!(
+		diagnostics[1].to_string(),
+		"line 4: Invalid number or unknown constant: many: Invalid number: many: invalid digit \
+		 found in string"
+	);
+	assert_eq!(diagnostics[4].to_string(), "line 10: Unresolved label don, did you mean `done`?");
+
+	// Parsing normally combines them into one error.
+	let error = PROGRAM.parse::<Program>().unwrap_err();
+	let message = error.to_string();
+	assert!(message.starts_with("5 errors:\nline 3: Unknown command"), "{message}");
+	assert_eq!(message.lines().count(), 6);
+
+	// A single error is reported with its line as well.
+	let error = "halt\njump nowhere".parse::<Program>().unwrap_err();
+	assert_eq!(error.to_string(), "line 2: Unresolved label nowhere");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
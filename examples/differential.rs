@@ -0,0 +1,64 @@
+use my_vm::{
+	test_support::{
+		assert_instruction_roundtrip, assert_machines_equal, assert_program_roundtrip,
+		diff_machines,
+	},
+	Machine, Program,
+};
+
+const PROGRAM: &str = r#"
+entry main
+
+// Function: Sum the numbers from 1 to the main register into side register 0.
+label sum
+setRegister 0 0
+label sum_loop
+swap 0
+add 0
+swap 0
+decrement
+jumpNonzero sum_loop
+return
+
+label main
+set 10
+call sum
+swap 0
+syscall 1
+// Store the result in memory.
+store32 0
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+
+	// The encodings survive round-trips.
+	for (_addr, instruction) in program.iter() {
+		assert_instruction_roundtrip(instruction);
+	}
+	assert_program_roundtrip(&program);
+
+	// Running the program and its disassembly leads to the same machine state.
+	let disassembled = Program::disassemble(&program.compile()?)?;
+	let mut original =
+		Machine::<1>::new(program.compile()?, 64).with_entry_point(program.entry_point());
+	let mut roundtrip =
+		Machine::<1>::new(disassembled.compile()?, 64).with_entry_point(program.entry_point());
+	original.run()?;
+	roundtrip.run()?;
+	println!();
+	assert_machines_equal(&original, &roundtrip);
+
+	// Different programs lead to differences.
+	let fresh = Machine::<1>::new(program.compile()?, 64);
+	for difference in diff_machines(&original, &fresh) {
+		println!("{difference}");
+	}
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,60 @@
+use my_vm::{Instruction, Machine, Program};
+
+/// Copies a string embedded in the code to memory and prints it.
+const PROGRAM: &str = r#"
+entry main
+
+label greeting
+dataString Hello; world!
+label numbers
+dataString 123
+align 4
+
+label main
+copyCodeMemory greeting
+set 0
+syscall 2
+load8 200
+jumpZero end
+halt
+label end
+halt
+"#;
+
+/// Decode all instructions of the code.
+fn decode(code: &[u8]) -> anyhow::Result<Vec<Instruction>> {
+	let mut instructions = Vec::new();
+	let mut addr = 0;
+	while addr < code.len() {
+		let instruction = Instruction::parse(&code[addr..])?;
+		addr += instruction.size();
+		instructions.push(instruction);
+	}
+	Ok(instructions)
+}
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let code = program.compile()?;
+
+	// Decoding the code gives back the same instructions, including the data
+	// embedded in the code, which compile to the same bytes.
+	let disassembled = Program::disassemble(&code)?;
+	assert_eq!(disassembled.compile()?, code);
+	let instructions = decode(&code)?;
+	assert!(instructions.contains(&Instruction::Data(4, b"123\0".to_vec())));
+	assert_eq!(decode(&disassembled.compile()?)?, instructions);
+	Machine::<0>::new(disassembled.compile()?, 256)
+		.with_entry_point(program.entry_point())
+		.run()?;
+
+	// Truncated code is reported.
+	let error = Program::disassemble(&code[..10]).unwrap_err();
+	assert!(error.to_string().starts_with("Failed decoding instruction at code address"));
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
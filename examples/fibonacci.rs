@@ -50,7 +50,7 @@ halt
 
 fn main() -> anyhow::Result<()> {
 	let program: Program = PROGRAM.parse()?;
-	let executable = program.compile();
+	let executable = program.compile()?;
 
 	// Machine with 2 side registers and 1024 bytes memory.
 	let mut machine = Machine::<2>::new(executable, 1024);
@@ -0,0 +1,57 @@
+use my_vm::{format_asm, Program};
+
+const INPUT: &str = "
+.define   COUNT 3
+entry  main
+
+
+// Print the number in the main register.
+label print
+SYSCALL 1 ;   return ; # both on one line
+label main
+  setregister   0  COUNT ; # counter
+.rept 2
+nop    ; # padding
+.endr
+label loop
+call print
+decrementRegister 0
+    JumpNonZero loop
+halt
+";
+
+const EXPECTED: &str = "\
+.define COUNT 3
+entry main
+
+// Print the number in the main register.
+label print
+    syscall 1 ; return  ; # both on one line
+label main
+    setRegister 0 COUNT ; # counter
+    .rept 2
+        nop             ; # padding
+    .endr
+label loop
+    call print
+    decrementRegister 0
+    jumpNonzero loop
+    halt
+";
+
+fn main() -> anyhow::Result<()> {
+	let formatted = format_asm(INPUT);
+	print!("{formatted}");
+	assert_eq!(formatted, EXPECTED);
+	// Formatting is idempotent and does not change the program.
+	assert_eq!(format_asm(&formatted), formatted);
+	let original: Program = INPUT.parse()?;
+	let reformatted: Program = formatted.parse()?;
+	assert_eq!(original.compile()?, reformatted.compile()?);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
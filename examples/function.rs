@@ -33,7 +33,7 @@ fn function_program() -> anyhow::Result<Program> {
 
 fn main() -> anyhow::Result<()> {
 	let program = function_program()?;
-	let executable = program.compile();
+	let executable = program.compile()?;
 
 	let mut machine = Machine::<0>::new(executable, 1024);
 	machine.run()?;
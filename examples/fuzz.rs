@@ -0,0 +1,48 @@
+//! Run the fuzzing harnesses on pseudo-random inputs, as a quick smoke test
+//! without cargo-fuzz.
+
+use my_vm::fuzz;
+
+/// Number of inputs per harness.
+const ITERATIONS: usize = 2_000;
+
+/// Simple xorshift pseudo-random number generator, for reproducible inputs.
+struct Rng(u64);
+
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	/// Random bytes, biased towards small values and valid opcodes.
+	fn bytes(&mut self) -> Vec<u8> {
+		let len = self.next() % 96;
+		(0..len)
+			.map(|_| {
+				let limit = if self.next().is_multiple_of(2) { 48 } else { 256 };
+				(self.next() % limit) as u8
+			})
+			.collect()
+	}
+}
+
+fn main() -> anyhow::Result<()> {
+	let iterations =
+		std::env::args().nth(1).map_or(Ok(ITERATIONS), |iterations| iterations.parse())?;
+	let mut rng = Rng(0x5eed);
+	for _ in 0..iterations {
+		let data = rng.bytes();
+		fuzz::decode(&data);
+		fuzz::execute_bytes(&data);
+		fuzz::execute_program(&data);
+	}
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,26 @@
+use my_vm::GoldenTest;
+
+fn main() -> anyhow::Result<()> {
+	// Run the golden tests next to this example.
+	let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/golden");
+	let tests = GoldenTest::discover(dir)?;
+	assert_eq!(tests.len(), 2);
+	for test in &tests {
+		let outcome = test.run::<1>(10_000);
+		println!("{}: {}", outcome.path.display(), outcome.passed());
+		assert!(outcome.passed(), "{:?}\n{}", outcome.error, outcome.diff());
+	}
+
+	// Failing tests show what differs.
+	let mut test = tests[0].clone();
+	test.expected = "3\n2\n0\n".to_owned();
+	let outcome = test.run::<1>(10_000);
+	assert!(!outcome.passed());
+	assert_eq!(outcome.diff(), "  3\n  2\n- 0\n+ 1\n");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,17 @@
+#expect: 3
+#expect: 2
+#expect: 1
+setRegister 0 3
+set 100
+copyCodeMemory newline
+label loop
+swap 0
+syscall 1
+swap 0
+set 100
+syscall 2
+decrementRegister 0
+jumpNonzero loop
+halt
+label newline
+dataBytes 10 0
@@ -0,0 +1,7 @@
+// Print a greeting.
+set 0
+copyCodeMemory greeting
+syscall 0
+halt
+label greeting
+dataString Hello golden tests!
@@ -2,8 +2,11 @@ use my_vm::{Instruction, Machine, Program};
 
 fn hello_world_program() -> anyhow::Result<Program> {
 	let mut program = Program::new();
-	// Add data segment to hold our string.
+	// Add data segment to hold our string and jump over it, as executing data
+	// is an error.
+	program.add_jump_label("main");
 	let s = program.add_data(c"Hello world!".to_bytes_with_nul());
+	program.add_label("main")?;
 	// Set the main register to 10 to point to the address we want to write the
 	// string to.
 	program.add_instruction(Instruction::Set(10));
@@ -20,7 +23,7 @@ fn hello_world_program() -> anyhow::Result<Program> {
 
 fn main() -> anyhow::Result<()> {
 	let program = hello_world_program()?;
-	let executable = program.compile();
+	let executable = program.compile()?;
 
 	let mut machine = Machine::<0>::new(executable, 1024);
 	machine.run()?;
@@ -0,0 +1,123 @@
+use my_vm::{Instruction, Machine, Program, VmPtr};
+
+/// Prints the string in the data segment twice using a function.
+const PROGRAM: &str = r#"
+jump main
+label data
+dataString Hi
+label function
+set 0
+copyCodeMemory data
+syscall 0
+return
+label main
+call function
+call function
+halt
+"#;
+
+fn program() -> anyhow::Result<Program> {
+	PROGRAM.parse()
+}
+
+/// Decode the compiled program into instructions and their code addresses.
+fn decode(program: &Program) -> Vec<(VmPtr, Instruction)> {
+	let code = program.compile().unwrap();
+	let mut instructions = Vec::new();
+	let mut addr = 0;
+	while addr < code.len() {
+		let instruction = Instruction::parse(&code[addr..]).unwrap();
+		instructions.push((addr as VmPtr, instruction.clone()));
+		addr += instruction.size();
+	}
+	instructions
+}
+
+/// Code address of the indexed instruction.
+fn address_of(program: &Program, index: usize) -> VmPtr {
+	decode(program)[index].0
+}
+
+/// Code address operand of the indexed instruction.
+fn target(program: &Program, index: usize) -> VmPtr {
+	decode(program)[index].1.code_address().unwrap()
+}
+
+/// Code address the payload of the indexed data segment starts at.
+fn payload(program: &Program, index: usize) -> VmPtr {
+	address_of(program, index) + 5
+}
+
+/// Run the program, which prints the string once per call.
+fn run(program: &Program) -> anyhow::Result<()> {
+	Machine::<0>::new(program.compile()?, 64).run()
+}
+
+fn main() -> anyhow::Result<()> {
+	let mut program = program()?;
+	run(&program)?;
+
+	// Inserting before everything moves all targets.
+	program.insert_instruction(0, Instruction::Nop)?;
+	assert_eq!(target(&program, 1), address_of(&program, 7));
+	assert_eq!(target(&program, 4), payload(&program, 2));
+	assert_eq!(target(&program, 7), address_of(&program, 3));
+	assert_eq!(target(&program, 8), address_of(&program, 3));
+	assert_eq!(program.symbols().address("function"), Some(address_of(&program, 3)));
+	run(&program)?;
+
+	// References to the indexed instruction keep pointing to it, so the
+	// inserted instruction in front of the call target is never executed.
+	program.insert_instruction(3, Instruction::Syscall(1))?;
+	assert_eq!(target(&program, 1), address_of(&program, 8));
+	assert_eq!(target(&program, 5), payload(&program, 2));
+	assert_eq!(target(&program, 8), address_of(&program, 4));
+	run(&program)?;
+
+	// Inserting after all targets keeps them, and the code address operand of
+	// the inserted instruction is taken as it is.
+	let function = address_of(&program, 4);
+	program.insert_instruction(10, Instruction::Call(function))?;
+	assert_eq!(target(&program, 1), address_of(&program, 8));
+	assert_eq!(target(&program, 5), payload(&program, 2));
+	assert_eq!(target(&program, 10), function);
+	run(&program)?;
+
+	// Removing instructions before the targets moves them back.
+	assert_eq!(program.remove_instruction(0)?, Instruction::Nop);
+	assert_eq!(program.remove_instruction(2)?, Instruction::Syscall(1));
+	assert_eq!(target(&program, 0), address_of(&program, 6));
+	assert_eq!(target(&program, 3), payload(&program, 1));
+	assert_eq!(target(&program, 6), address_of(&program, 2));
+	assert_eq!(target(&program, 8), address_of(&program, 2));
+	run(&program)?;
+
+	// Removing a target redirects its references to the following
+	// instruction.
+	assert_eq!(program.remove_instruction(2)?, Instruction::Set(0));
+	assert_eq!(target(&program, 5), address_of(&program, 2));
+	assert_eq!(target(&program, 2), payload(&program, 1));
+	assert_eq!(program.symbols().address("function"), Some(address_of(&program, 2)));
+	run(&program)?;
+
+	// Removing after the targets keeps them.
+	let function = address_of(&program, 2);
+	assert_eq!(program.remove_instruction(7)?, Instruction::Call(function));
+	assert_eq!(target(&program, 0), address_of(&program, 5));
+	assert_eq!(target(&program, 6), address_of(&program, 2));
+	run(&program)?;
+	assert_eq!(program.compile()?, {
+		let mut expected = self::program()?;
+		expected.remove_instruction(2)?;
+		expected.compile()?
+	});
+
+	assert!(program.insert_instruction(9, Instruction::Nop).is_err());
+	assert!(program.remove_instruction(8).is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,198 @@
+use my_vm::{
+	ir::{BinaryOp, Condition, IrFunction, IrInstruction, IrModule, Terminator, VReg},
+	Machine,
+};
+
+/// Add an instruction setting a new virtual register to the constant.
+fn constant(function: &mut IrFunction, block: my_vm::ir::BlockId, value: u32) -> VReg {
+	let dst = function.vreg();
+	function.push(block, IrInstruction::Const { dst, value });
+	dst
+}
+
+/// `fib(n) = n < 2 ? n : fib(n - 1) + fib(n - 2)`
+fn fib() -> IrFunction {
+	let mut function = IrFunction::new("fib", 1);
+	let n = function.param(0);
+	let entry = function.entry();
+	let base = function.block();
+	let recurse = function.block();
+	let two = constant(&mut function, entry, 2);
+	function.terminate(
+		entry,
+		Terminator::Branch {
+			condition: Condition::Less,
+			lhs: n,
+			rhs: two,
+			then: base,
+			otherwise: recurse,
+		},
+	);
+	function.terminate(base, Terminator::Return(Some(n)));
+	let one = constant(&mut function, recurse, 1);
+	let (n1, n2, a, b, sum) =
+		(function.vreg(), function.vreg(), function.vreg(), function.vreg(), function.vreg());
+	function.push(recurse, IrInstruction::Binary { op: BinaryOp::Sub, dst: n1, lhs: n, rhs: one });
+	function.push(recurse, IrInstruction::Binary { op: BinaryOp::Sub, dst: n2, lhs: n, rhs: two });
+	function.push(
+		recurse,
+		IrInstruction::Call { dst: Some(a), function: "fib".into(), args: vec![n1] },
+	);
+	function.push(
+		recurse,
+		IrInstruction::Call { dst: Some(b), function: "fib".into(), args: vec![n2] },
+	);
+	function.push(recurse, IrInstruction::Binary { op: BinaryOp::Add, dst: sum, lhs: a, rhs: b });
+	function.terminate(recurse, Terminator::Return(Some(sum)));
+	function
+}
+
+/// Print the number followed by a space.
+fn print(function: &mut IrFunction, block: my_vm::ir::BlockId, value: VReg) {
+	function.push(block, IrInstruction::Syscall { index: 1, arg: Some(value), dst: None });
+	let space = constant(function, block, u32::from(b' '));
+	function.push(block, IrInstruction::Syscall { index: 4, arg: Some(space), dst: None });
+}
+
+/// Main function exercising loops, calls, memory and many live values.
+fn main_function() -> IrFunction {
+	let mut function = IrFunction::new("main", 0);
+	let entry = function.entry();
+	let header = function.block();
+	let body = function.block();
+	let exit = function.block();
+
+	// Sum of the squares of 1 to 10 in a loop.
+	let i = constant(&mut function, entry, 1);
+	let sum = constant(&mut function, entry, 0);
+	let one = constant(&mut function, entry, 1);
+	let limit = constant(&mut function, entry, 10);
+	function.terminate(entry, Terminator::Jump(header));
+	function.terminate(
+		header,
+		Terminator::Branch {
+			condition: Condition::LessEqual,
+			lhs: i,
+			rhs: limit,
+			then: body,
+			otherwise: exit,
+		},
+	);
+	let square = function.vreg();
+	function.push(body, IrInstruction::Binary { op: BinaryOp::Mul, dst: square, lhs: i, rhs: i });
+	function
+		.push(body, IrInstruction::Binary { op: BinaryOp::Add, dst: sum, lhs: sum, rhs: square });
+	function.push(body, IrInstruction::Binary { op: BinaryOp::Add, dst: i, lhs: i, rhs: one });
+	function.terminate(body, Terminator::Jump(header));
+	print(&mut function, exit, sum);
+
+	// Recursive calls.
+	let n = constant(&mut function, exit, 15);
+	let result = function.vreg();
+	function.push(
+		exit,
+		IrInstruction::Call { dst: Some(result), function: "fib".into(), args: vec![n] },
+	);
+	print(&mut function, exit, result);
+
+	// Division, remainder and comparison.
+	let seven = constant(&mut function, exit, 7);
+	let (quotient, remainder, less) = (function.vreg(), function.vreg(), function.vreg());
+	function.push(
+		exit,
+		IrInstruction::Binary { op: BinaryOp::Div, dst: quotient, lhs: result, rhs: seven },
+	);
+	function.push(
+		exit,
+		IrInstruction::Binary { op: BinaryOp::Rem, dst: remainder, lhs: result, rhs: seven },
+	);
+	function.push(
+		exit,
+		IrInstruction::Compare {
+			condition: Condition::Less,
+			dst: less,
+			lhs: remainder,
+			rhs: seven,
+		},
+	);
+	print(&mut function, exit, quotient);
+	print(&mut function, exit, remainder);
+	print(&mut function, exit, less);
+
+	// Memory accesses, which are big-endian.
+	let addr = constant(&mut function, exit, 3000);
+	let loaded = function.vreg();
+	function.push(exit, IrInstruction::Store32 { addr, value: result });
+	function.push(exit, IrInstruction::Load8 { dst: loaded, addr });
+	print(&mut function, exit, loaded);
+	function.push(exit, IrInstruction::Store8 { addr, value: seven });
+	function.push(exit, IrInstruction::Load32 { dst: loaded, addr });
+	print(&mut function, exit, loaded);
+
+	// More live values than side registers, summed in reverse.
+	let values = (1..=20).map(|value| constant(&mut function, exit, value)).collect::<Vec<_>>();
+	let total = constant(&mut function, exit, 0);
+	for value in values.iter().rev() {
+		function.push(
+			exit,
+			IrInstruction::Binary { op: BinaryOp::Add, dst: total, lhs: total, rhs: *value },
+		);
+	}
+	print(&mut function, exit, total);
+	function.terminate(exit, Terminator::Return(None));
+	function
+}
+
+fn main() -> anyhow::Result<()> {
+	let mut module = IrModule::new();
+	module.add_function(main_function());
+	module.add_function(fib());
+
+	let expected = "385 610 87 1 1 0 117441122 210 ";
+	// Without allocatable side registers, all values are spilled.
+	let program = module.lower(5)?;
+	assert_eq!(program.required_side_registers(), 5);
+	let mut machine =
+		Machine::<5>::from_executable(program.to_executable()?)?.with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), expected);
+	assert_eq!(machine.stack_pointer(), machine.memory_size());
+
+	let program = module.lower(8)?;
+	let mut machine =
+		Machine::<8>::from_executable(program.to_executable()?)?.with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), expected);
+
+	let program = module.lower(32)?;
+	let mut machine =
+		Machine::<32>::from_executable(program.to_executable()?)?.with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), expected);
+	assert_eq!(machine.stack_pointer(), machine.memory_size());
+
+	// Invalid modules are reported.
+	assert!(module.lower(4).is_err());
+	let mut unterminated = IrModule::new();
+	unterminated.add_function(IrFunction::new("main", 0));
+	let error = unterminated.lower(5).unwrap_err();
+	assert_eq!(error.to_string(), "Block b0 of function main has no terminator");
+	let mut unknown = IrModule::new();
+	let mut function = IrFunction::new("main", 0);
+	let entry = function.entry();
+	function.push(
+		entry,
+		IrInstruction::Call { dst: None, function: "missing".into(), args: Vec::new() },
+	);
+	function.terminate(entry, Terminator::Halt);
+	unknown.add_function(function);
+	let error = unknown.lower(5).unwrap_err();
+	assert_eq!(error.to_string(), "Function main calls unknown function missing");
+	assert!(IrModule::new().lower(5).is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,81 @@
+use std::{thread, time::Duration};
+
+use my_vm::{Instruction, Key, Keyboard, Machine, Program, VmPtr};
+
+/// Echo typed characters and print `^` for arrow keys until `q` is pressed.
+const PROGRAM: &str = r#"
+label poll
+syscall 5
+// No event is 0xFFFFFFFF, so incrementing it sets the zero flag.
+increment
+jumpZero poll
+decrement
+setRegister 0 113
+compare 0
+jumpEqual done
+// Arrow keys have codes from 0x110000 on.
+setRegister 0 1114112
+compare 0
+jumpGreaterEqual arrow
+syscall 4
+jump poll
+label arrow
+set 94
+syscall 4
+jump poll
+label done
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+
+	// Events buffered before the program runs.
+	let keyboard = Keyboard::new();
+	for key in [Key::Char('h'), Key::Char('i'), Key::Up, Key::Left, Key::Char('q')] {
+		assert!(keyboard.push(key));
+	}
+	let mut machine = Machine::<1>::from_executable(program.to_executable()?)?
+		.with_captured_output()
+		.with_keyboard(keyboard.clone());
+	machine.run()?;
+	assert_eq!(machine.take_output(), "hi^^");
+	assert!(keyboard.is_empty());
+
+	// Events pushed by another thread while the program is polling.
+	let keyboard = Keyboard::new();
+	let host = keyboard.clone();
+	let typing = thread::spawn(move || {
+		for c in "ok!q".chars() {
+			thread::sleep(Duration::from_millis(5));
+			host.push(Key::Char(c));
+		}
+	});
+	let mut machine = Machine::<1>::from_executable(program.to_executable()?)?
+		.with_captured_output()
+		.with_keyboard(keyboard);
+	machine.run()?;
+	typing.join().unwrap();
+	assert_eq!(machine.take_output(), "ok!");
+
+	// A full buffer drops new events.
+	let keyboard = Keyboard::with_capacity(2);
+	assert!(keyboard.push(Key::Char('a')));
+	assert!(keyboard.push(Key::Down));
+	assert!(!keyboard.push(Key::Char('b')));
+	assert_eq!(keyboard.len(), 2);
+	assert_eq!(keyboard.pop(), Some(VmPtr::from('a')));
+	assert_eq!(keyboard.pop(), Some(Key::Down.code()));
+	assert_eq!(keyboard.pop(), None);
+
+	// Without a keyboard, there are never events.
+	let mut machine = Machine::<1>::new(Vec::new(), 0);
+	machine.execute_instruction(Instruction::Syscall(5))?;
+	assert_eq!(machine.main_register(), VmPtr::MAX);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
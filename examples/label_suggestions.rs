@@ -0,0 +1,65 @@
+use std::fs;
+
+use my_vm::{Diagnostic, Program};
+
+const PROGRAM: &str = r#"
+label main
+call pritn
+jump mian
+call exit
+label print
+return
+label main
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let diagnostics = Program::parse_with_diagnostics(PROGRAM).unwrap_err();
+	let messages = diagnostics.iter().map(Diagnostic::to_string).collect::<Vec<_>>();
+	assert_eq!(
+		messages,
+		[
+			"line 8: Label main is defined multiple times (line 2 and line 8)",
+			"line 3: Unresolved label pritn, did you mean `print`?",
+			"line 4: Unresolved label mian, did you mean `main`?",
+			"line 5: Unresolved label exit",
+		]
+	);
+
+	// Definitions and references in included files are located in them.
+	let dir = std::env::temp_dir().join(format!("my_vm_labels_{}", std::process::id()));
+	fs::create_dir_all(&dir)?;
+	let included = dir.join("inc.asm");
+	fs::write(&included, "label main\nentry main\njump mian\nlabel main\n")?;
+	let source = format!("include \"{}\"\nentry main\nlabel main\nhalt\n", included.display());
+	let diagnostics = Program::parse_with_diagnostics(&source).unwrap_err();
+	fs::remove_dir_all(&dir)?;
+	let inc = included.display();
+	assert_eq!(
+		diagnostics.iter().map(Diagnostic::to_string).collect::<Vec<_>>(),
+		[
+			format!("{inc}:1: Label main is defined multiple times (line 3 and {inc}:1)"),
+			format!("{inc}:2: Entry point is defined multiple times (line 2 and {inc}:2)"),
+			format!("{inc}:4: Label main is defined multiple times (line 3, {inc}:1 and {inc}:4)"),
+			format!("{inc}:3: Unresolved label mian, did you mean `main`?"),
+		]
+	);
+	assert_eq!(diagnostics[3].file, Some(inc.to_string()));
+	assert_eq!(diagnostics[3].line, Some(3));
+
+	// The builder suggests labels as well.
+	let mut program = Program::new();
+	program.add_call_label("mian");
+	program.add_label("main")?;
+	assert_eq!(
+		program.compile().unwrap_err().to_string(),
+		"Undefined label mian used by instruction 0, did you mean main?"
+	);
+	assert!(program.add_label("main").is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
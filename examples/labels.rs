@@ -0,0 +1,45 @@
+use my_vm::{Instruction, Machine, Program};
+
+fn labels_program() -> anyhow::Result<Program> {
+	let mut program = Program::new();
+	// Jump to main, which is defined later.
+	program.add_jump_label("main");
+	// Add data segment to hold our string.
+	let data = program.add_data(c"Hello labels!".to_bytes_with_nul());
+	// Function to print the string.
+	program.add_label("function")?;
+	program.add_instruction(Instruction::Set(0));
+	program.add_copy_data(data)?;
+	program.add_syscall(0);
+	program.add_return();
+	// Main: call the function 3 times using a counter in side register 0.
+	program.add_label("main")?;
+	program.add_instruction(Instruction::SetRegister(0, 3));
+	program.add_label("loop")?;
+	program.add_call_label("function");
+	program.add_instruction(Instruction::DecrementRegister(0));
+	program.add_jump_nonzero_label("loop");
+	// Halt the machine.
+	program.add_halt();
+	Ok(program)
+}
+
+fn main() -> anyhow::Result<()> {
+	let program = labels_program()?;
+	let executable = program.compile()?;
+
+	let mut machine = Machine::<1>::new(executable, 1024);
+	machine.run()?;
+
+	// Referring to a label that is never added is reported when compiling.
+	let mut program = Program::new();
+	program.add_call_label("mian");
+	program.add_label("main")?;
+	assert!(program.compile().is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
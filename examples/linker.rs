@@ -0,0 +1,67 @@
+use my_vm::{Instruction, Linker, Machine, Object, VmPtr};
+
+const MAIN: &str = r#"
+// Start execution at the main function.
+entry main
+
+// Main function, calling the greeting function from the other module twice.
+label main
+call greet
+call greet
+halt
+"#;
+
+const GREET: &str = r#"
+// Only export the function, the data label stays local to this module.
+global greet
+
+// Data segment to hold our string.
+label message
+dataString Hello from another module!
+
+// Function to print the string.
+label greet
+set 0
+copyCodeMemory message
+syscall 0
+return
+"#;
+
+fn main() -> anyhow::Result<()> {
+	// Assemble the modules separately. `greet` stays unresolved in the first
+	// object.
+	let main = Object::assemble(MAIN)?;
+	let greet = Object::from_bytes(&Object::assemble(GREET)?.to_bytes())?;
+
+	let executable = Linker::new().add_object(main).add_object(greet).link()?;
+	let mut machine = Machine::<0>::from_executable(executable)?;
+	machine.run()?;
+
+	// Addresses of untrusted objects that overflow after relocation are
+	// rejected.
+	let first = Object { code: Instruction::Halt.bytes(), ..Object::default() };
+	let link = |second: Object| Linker::new().add_object(first.clone()).add_object(second).link();
+	let code = Instruction::Jump(VmPtr::MAX).bytes();
+	let relocation = Object { code: code.clone(), relocations: vec![1], ..Object::default() };
+	assert_eq!(
+		link(relocation).unwrap_err().to_string(),
+		"Relocation in object 1 exceeds the address space"
+	);
+	let symbol = Object {
+		code: code.clone(),
+		symbols: [("far".to_owned(), (VmPtr::MAX, true))].into(),
+		..Object::default()
+	};
+	assert_eq!(link(symbol).unwrap_err().to_string(), "Symbol far exceeds the address space");
+	let entry = Object { code, entry_point: Some(VmPtr::MAX), ..Object::default() };
+	assert_eq!(
+		link(entry).unwrap_err().to_string(),
+		"Entry point of object 1 exceeds the address space"
+	);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
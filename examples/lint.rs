@@ -0,0 +1,52 @@
+use my_vm::Program;
+
+const PROGRAM: &str = r#"
+call function
+call unbalanced
+halt
+set 1
+
+label unused
+nop
+
+label function
+setRegister 4 1
+set 0
+label message
+dataString oops
+
+label unbalanced
+push
+return
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let lints = program.lint(Some(2));
+	for lint in &lints {
+		println!("{lint}");
+	}
+	let messages = lints.iter().map(|lint| (lint.line.unwrap(), lint.message.as_str()));
+	assert_eq!(
+		messages.collect::<Vec<_>>(),
+		[
+			(5, "Unreachable code"),
+			(8, "Label unused is never used"),
+			(11, "Side register 4 is beyond the 2 declared registers"),
+			(12, "Execution falls through into a data segment"),
+			(14, "Label message is never used"),
+			// `function` runs through the data into `unbalanced`.
+			(18, "Function function: 1 more pushes than pops at return"),
+			(18, "Function unbalanced: 1 more pushes than pops at return"),
+		]
+	);
+
+	let program: Program = "call function\nhalt\nlabel function\npush\npop\nreturn".parse()?;
+	assert!(program.lint(Some(2)).is_empty());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -21,7 +21,7 @@ fn loop_program() -> anyhow::Result<Program> {
 
 fn main() -> anyhow::Result<()> {
 	let program = loop_program()?;
-	let executable = program.compile();
+	let executable = program.compile()?;
 
 	let mut machine = Machine::<0>::new(executable, 1024);
 	machine.run()?;
@@ -0,0 +1,119 @@
+use std::thread;
+
+use my_vm::{Instruction, Machine, Mailbox, MailboxEndpoint, Program, VmPtr};
+
+/// Send `ping` to endpoint 1 and print the reply.
+const PING: &str = r#"
+jump main
+label message
+dataString ping
+label main
+set 0 ; copyCodeMemory message
+setRegister 0 4
+setRegister 1 1
+set 0
+syscall 16
+label wait
+set 100
+setRegister 0 16
+syscall 17
+// No message is 0xFFFFFFFF.
+increment
+jumpZero wait
+set 100 ; syscall 0
+halt
+"#;
+
+/// Wait for a message, reply `pong` to its sender and print it.
+const PONG: &str = r#"
+jump main
+label message
+dataString pong
+label main
+label wait
+set 100
+setRegister 0 16
+syscall 17
+increment
+jumpZero wait
+set 0 ; copyCodeMemory message
+setRegister 0 4
+set 0
+syscall 16
+set 100 ; syscall 0
+halt
+"#;
+
+/// Create a machine running the program with the mailbox endpoint.
+fn machine(source: &str, endpoint: MailboxEndpoint) -> anyhow::Result<Machine<2>> {
+	let program: Program = source.parse()?;
+	Ok(Machine::<2>::from_executable(program.to_executable()?)?
+		.with_captured_output()
+		.with_mailbox(endpoint))
+}
+
+fn main() -> anyhow::Result<()> {
+	// Non-blocking: both machines run interleaved in one thread.
+	let mailbox = Mailbox::new(4);
+	let mut ping = machine(PING, mailbox.endpoint())?;
+	let mut pong = machine(PONG, mailbox.endpoint())?;
+	let (mut ping_running, mut pong_running) = (true, true);
+	while ping_running || pong_running {
+		if ping_running {
+			ping_running = ping.step()?;
+		}
+		if pong_running {
+			pong_running = pong.step()?;
+		}
+	}
+	assert_eq!(ping.take_output(), "pong\n");
+	assert_eq!(pong.take_output(), "ping\n");
+
+	// Blocking: each machine runs in its own thread.
+	let mailbox = Mailbox::new(1).with_blocking(true);
+	let mut ping = machine(PING, mailbox.endpoint())?;
+	let mut pong = machine(PONG, mailbox.endpoint())?;
+	thread::scope(|scope| {
+		scope.spawn(|| pong.run().unwrap());
+		ping.run().unwrap();
+	});
+	assert_eq!(ping.take_output(), "pong\n");
+	assert_eq!(pong.take_output(), "ping\n");
+
+	// The host can use endpoints as well.
+	let mailbox = Mailbox::new(1);
+	let host = mailbox.endpoint();
+	let mut machine = Machine::<2>::new(Vec::new(), 64).with_mailbox(mailbox.endpoint());
+	machine.execute_instruction(Instruction::Syscall(19))?;
+	assert_eq!(machine.main_register(), 1);
+	machine.execute_instruction(Instruction::Syscall(18))?;
+	assert_eq!(machine.main_register(), VmPtr::MAX);
+	assert!(host.send(1, b"hi")?);
+	assert!(!host.send(1, b"full")?);
+	machine.execute_instruction(Instruction::Syscall(18))?;
+	assert_eq!(machine.main_register(), 2);
+	// Messages that do not fit into the buffer stay queued.
+	machine.execute_instruction(Instruction::SetRegister(0, 1))?;
+	assert!(machine.execute_instruction(Instruction::Syscall(17)).is_err());
+	// The same holds for buffers exceeding the memory.
+	machine.execute_instruction(Instruction::Set(63))?;
+	machine.execute_instruction(Instruction::SetRegister(0, 16))?;
+	let error = machine.execute_instruction(Instruction::Syscall(17)).unwrap_err();
+	assert_eq!(format!("{error:#}"), "Message buffer at 63 exceeds the memory");
+	machine.execute_instruction(Instruction::Set(62))?;
+	machine.execute_instruction(Instruction::Syscall(17))?;
+	assert_eq!(machine.main_register(), 2);
+	assert_eq!(machine.read_memory(62, 2)?, b"hi");
+	assert_eq!(host.receive(16)?, None);
+	assert!(host.send(7, b"nobody").is_err());
+	assert!(host.send(1, &[0; Mailbox::MAX_MESSAGE_SIZE + 1]).is_err());
+	let mut machine = Machine::<2>::new(Vec::new(), 64);
+	let error = machine.execute_instruction(Instruction::Syscall(16)).unwrap_err();
+	assert_eq!(error.to_string(), "No mailbox attached");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,30 @@
+use my_vm::{Instruction, Opcode, ISA_VERSION};
+
+fn main() -> anyhow::Result<()> {
+	// Every opcode round-trips through its byte.
+	for opcode in Opcode::ALL {
+		let byte = u8::from(opcode);
+		assert_eq!(Opcode::try_from(byte)?, opcode);
+		assert_eq!(Opcode::from_versioned(byte, ISA_VERSION)?, opcode);
+	}
+	// The numbering is dense and everything behind the last opcode is invalid.
+	assert_eq!(Opcode::ALL.len(), usize::from(u8::from(Opcode::LAST)) + 1);
+	for byte in u8::from(Opcode::LAST) + 1..=u8::MAX {
+		let error = Opcode::try_from(byte).unwrap_err();
+		assert_eq!(error.to_string(), format!("Unrecognized instruction: {byte}"));
+	}
+
+	// Instructions are encoded with their opcode as first byte.
+	let instruction = Instruction::SetRegister(1, 2);
+	assert_eq!(instruction.opcode(), Opcode::SetRegister);
+	assert_eq!(instruction.bytes()[0], u8::from(Opcode::SetRegister));
+
+	assert!(Opcode::from_versioned(0, 0).is_err());
+	assert!(Opcode::from_versioned(0, ISA_VERSION + 1).is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeSet;
+
+use my_vm::{Instruction, VmPtr};
+
+/// One instruction of every kind, with operands that differ from each other.
+fn instructions() -> Vec<Instruction> {
+	vec![
+		Instruction::Nop,
+		Instruction::Halt,
+		Instruction::Load8(1),
+		Instruction::Store8(2),
+		Instruction::Load16(3),
+		Instruction::Store16(4),
+		Instruction::Load32(5),
+		Instruction::Store32(6),
+		Instruction::Set(7),
+		Instruction::Deref8(8),
+		Instruction::Deref16(9),
+		Instruction::Deref32(10),
+		Instruction::Syscall(11),
+		Instruction::CopyCodeMemory(12, 13),
+		Instruction::Data(3, vec![0, 127, 255]),
+		Instruction::Swap(14),
+		Instruction::Write8(15),
+		Instruction::Write16(16),
+		Instruction::Write32(17),
+		Instruction::ReadStackPointer,
+		Instruction::WriteStackPointer,
+		Instruction::Jump(18),
+		Instruction::Call(19),
+		Instruction::Return,
+		Instruction::Increment,
+		Instruction::Decrement,
+		Instruction::Add(20),
+		Instruction::Sub(21),
+		Instruction::Compare(22),
+		Instruction::JumpEqual(23),
+		Instruction::JumpNotEqual(24),
+		Instruction::JumpGreater(25),
+		Instruction::JumpLess(26),
+		Instruction::JumpGreaterEqual(27),
+		Instruction::JumpLessEqual(28),
+		Instruction::JumpZero(29),
+		Instruction::JumpNonzero(30),
+		Instruction::Push,
+		Instruction::Pop,
+		Instruction::PushRegister(31),
+		Instruction::PopRegister(32),
+		Instruction::Mul(33),
+		Instruction::Div(34),
+		Instruction::IncrementRegister(35),
+		Instruction::DecrementRegister(36),
+		Instruction::SetRegister(37, 38),
+		// Immediate values with the highest bit set are shown as negative
+		// numbers.
+		Instruction::Set(VmPtr::MAX),
+		Instruction::Set(-1000i32 as VmPtr),
+		Instruction::Set(i32::MIN as VmPtr),
+		Instruction::SetRegister(1, VmPtr::MAX),
+		Instruction::SetRegister(2, -42i32 as VmPtr),
+		// Code addresses are not signed.
+		Instruction::Jump(VmPtr::MAX),
+	]
+}
+
+fn main() -> anyhow::Result<()> {
+	let instructions = instructions();
+	// The opcodes are numbered without gaps.
+	let opcodes =
+		instructions.iter().map(|instruction| instruction.bytes()[0]).collect::<BTreeSet<_>>();
+	let last = *opcodes.last().unwrap();
+	assert_eq!(opcodes, (0..=last).collect(), "not all instructions are covered");
+
+	// Every instruction parses back from its text form.
+	for instruction in instructions {
+		let text = instruction.to_string();
+		assert_eq!(text.parse::<Instruction>()?, instruction, "{text}");
+	}
+	assert_eq!(Instruction::Set(VmPtr::MAX).to_string(), "set -1");
+	assert_eq!(Instruction::SetRegister(2, -42i32 as VmPtr).to_string(), "setRegister 2 -42");
+	assert_eq!(
+		" setRegister  3   -7 ".parse::<Instruction>()?,
+		Instruction::SetRegister(3, -7i32 as VmPtr)
+	);
+
+	// Mistakes are reported.
+	let error = "frobnicate 1".parse::<Instruction>().unwrap_err();
+	assert_eq!(error.to_string(), "Unknown instruction or wrong number of arguments: frobnicate 1");
+	assert!("set".parse::<Instruction>().is_err());
+	assert!("push 1".parse::<Instruction>().is_err());
+	assert!("swap 256".parse::<Instruction>().is_err());
+	assert!("jump label".parse::<Instruction>().is_err());
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
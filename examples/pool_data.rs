@@ -0,0 +1,65 @@
+use my_vm::{Machine, Program};
+
+const PROGRAM: &str = r#"
+jump main
+label hello
+dataString hello
+label world
+dataString world
+label hello2
+dataString hello
+label hello3
+dataString hello
+label main
+set 0
+copyCodeMemory hello2
+syscall 0
+copyCodeMemory world
+syscall 0
+copyCodeMemory hello3
+syscall 0
+copyCodeMemory hello
+syscall 0
+halt
+"#;
+
+/// The program without the duplicates, copying from the first segment.
+const POOLED: &str = r#"
+jump main
+label hello
+dataString hello
+label world
+dataString world
+label main
+set 0
+copyCodeMemory hello
+syscall 0
+copyCodeMemory world
+syscall 0
+copyCodeMemory hello
+syscall 0
+copyCodeMemory hello
+syscall 0
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let mut program: Program = PROGRAM.parse()?;
+	Machine::<0>::new(program.compile()?, 64).run()?;
+
+	// The two duplicates of the first segment are removed, copies and the jump
+	// over the data are fixed up.
+	assert_eq!(program.pool_data(), 2);
+	let pooled: Program = POOLED.parse()?;
+	assert_eq!(program.compile()?, pooled.compile()?);
+	Machine::<0>::new(program.compile()?, 64).run()?;
+
+	// Pooling again finds nothing.
+	assert_eq!(program.pool_data(), 0);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
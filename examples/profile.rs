@@ -0,0 +1,39 @@
+use my_vm::{Machine, Profile, Program};
+
+const PROGRAM: &str = r#"
+entry main
+
+// Do nothing, but often.
+label function
+nop
+return
+
+label main
+setRegister 0 10
+label loop
+call function
+decrementRegister 0
+jumpNonzero loop
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let mut machine =
+		Machine::<1>::from_executable(program.to_executable()?)?.with_execution_counts();
+	machine.run()?;
+
+	let profile = Profile::from_machine(&machine)?;
+	println!("{}", profile.report(5));
+	// 10 loop iterations with 5 instructions, plus setup and halt.
+	assert_eq!(profile.total(), 52);
+	// The halt after the loop also belongs to the `loop` label.
+	assert_eq!(profile.hottest_labels()[0], ("loop".to_owned(), 31));
+	assert_eq!(profile.hottest_instructions()[0].1, 10);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,56 @@
+use my_vm::{Machine, Program};
+
+const PROGRAM: &str = r#"
+.rept 3 i
+label entry_{i}
+    .rept 2 j
+    set {i}{j}
+    syscall 1
+    set 32
+    syscall 4
+    .endr
+.endr
+# Empty blocks expand to nothing, regardless of the count.
+.rept 4000000000
+.endr
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	assert_eq!(program.len(), 3 * 2 * 4 + 1);
+	// Every outer iteration has 2 inner iterations of 4 instructions of 5 or 2
+	// bytes each.
+	let symbols = program.symbols();
+	assert_eq!(symbols.address("entry_0"), Some(0));
+	assert_eq!(symbols.address("entry_1"), Some(28));
+	assert_eq!(symbols.address("entry_2"), Some(56));
+	let mut machine = Machine::<0>::new(program.compile()?, 64).with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), "0 1 10 11 20 21 ");
+
+	// The total expansion is limited, also for nested blocks.
+	let error = ".rept 4000000000\nnop\n.endr".parse::<Program>().unwrap_err();
+	assert_eq!(error.to_string(), "line 3: .rept blocks expand to more than 100000 statements");
+	let error = ".rept 1000\n.rept 1000\nnop\n.endr\n.endr".parse::<Program>().unwrap_err();
+	assert_eq!(error.to_string(), "line 4: .rept blocks expand to more than 100000 statements");
+	let program: Program = ".rept 10\n.rept 9000\nnop\n.endr\n.endr".parse()?;
+	assert_eq!(program.len(), 90_000);
+
+	// Errors in the body are reported at their line, once for all iterations,
+	// and do not stop the expansion.
+	let source = "halt\n.rept 3 i\nset {i}\nbogus 1\nswap 300\n.endr\njump nowhere";
+	let error = source.parse::<Program>().unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"3 errors:\nline 4: Unknown command or wrong number of arguments: bogus\nline 5: Operand \
+		 300 does not fit into a byte: out of range integral type conversion attempted\nline 7: \
+		 Unresolved label nowhere"
+	);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
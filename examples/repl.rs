@@ -0,0 +1,26 @@
+use my_vm::Repl;
+
+fn main() -> anyhow::Result<()> {
+	let mut repl = Repl::<2>::new(64);
+
+	// Every instruction reports what it changed.
+	for line in ["set 42", "store32 8", "setRegister 1 42", "compare 1", "push", "nop"] {
+		println!("> {line}\n{}", repl.execute(line)?);
+	}
+	assert_eq!(repl.execute("set 42")?, "no changes");
+	assert_eq!(repl.execute("setRegister 0 1")?, "r0: 0x0 -> 0x1");
+	assert_eq!(repl.machine().stack_pointer(), 60);
+	println!("{}", repl.execute(".memory 8 4")?);
+
+	// Labels are not available, and unknown instructions are errors.
+	assert!(repl.execute("jump main").is_err());
+
+	repl.execute(".reset")?;
+	assert_eq!(repl.machine().main_register(), 0);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,86 @@
+use std::{
+	io::{self, Cursor, Read, Write},
+	net::{Shutdown, TcpListener, TcpStream},
+	sync::{Arc, Mutex},
+	thread,
+};
+
+use my_vm::{Instruction, Machine, Program, Serial};
+
+/// Echo the received bytes in upper case until the end of the stream.
+const PROGRAM: &str = r#"
+setRegister 0 97
+setRegister 1 122
+label loop
+syscall 15
+// The end of the stream is 0xFFFFFFFF.
+increment
+jumpZero done
+decrement
+compare 0
+jumpLess send
+compare 1
+jumpGreater send
+setRegister 2 32
+sub 2
+label send
+syscall 14
+jump loop
+label done
+halt
+"#;
+
+/// Writer into a buffer that stays accessible after moving it into the
+/// serial device.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.lock().unwrap().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+
+	// In-memory streams.
+	let output = SharedBuffer::default();
+	let serial = Serial::new(Cursor::new(b"Hello, serial!".to_vec()), output.clone());
+	let mut machine = Machine::<3>::from_executable(program.to_executable()?)?.with_serial(serial);
+	machine.run()?;
+	assert_eq!(output.0.lock().unwrap().as_slice(), b"HELLO, SERIAL!");
+
+	// A TCP connection, e.g. to a terminal program on the host.
+	let listener = TcpListener::bind("127.0.0.1:0")?;
+	let addr = listener.local_addr()?;
+	let client = thread::spawn(move || -> io::Result<Vec<u8>> {
+		let mut stream = TcpStream::connect(addr)?;
+		stream.write_all(b"over tcp")?;
+		stream.shutdown(Shutdown::Write)?;
+		let mut received = Vec::new();
+		stream.read_to_end(&mut received)?;
+		Ok(received)
+	});
+	let (stream, _) = listener.accept()?;
+	let mut machine =
+		Machine::<3>::from_executable(program.to_executable()?)?.with_serial(Serial::tcp(stream)?);
+	machine.run()?;
+	drop(machine);
+	assert_eq!(client.join().unwrap()?, b"OVER TCP");
+
+	// The syscalls need a serial device.
+	let mut machine = Machine::<3>::new(Vec::new(), 64);
+	let error = machine.execute_instruction(Instruction::Syscall(14)).unwrap_err();
+	assert_eq!(error.to_string(), "No serial device attached");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
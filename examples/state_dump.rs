@@ -0,0 +1,54 @@
+use my_vm::{Machine, Program};
+
+/// Stores a greeting and a number, then stops in a function.
+const PROGRAM: &str = r#"
+jump main
+label text
+dataString Hi!
+label main
+set 0 ; copyCodeMemory text
+set 7 ; store32 40
+setRegister 1 42
+call function
+halt
+label function
+push
+syscall 2
+return
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let executable = program.to_executable()?;
+	let mut machine = Machine::<2>::new(executable.code, 64)
+		.with_debug_info(executable.debug_info.expect("parsed programs have debug info"))
+		.with_captured_output();
+	let syscall = program.symbols().address("function").expect("label exists") + 1;
+	machine.add_breakpoint(syscall);
+	machine.run_until_break()?;
+
+	let state = machine.dump_state();
+	println!("{state}");
+	assert_eq!(state, machine.to_string());
+	assert_eq!(
+		state,
+		"\
+ip=0x00000033 (function+1) sp=0x00000038 main=0x00000007 zero=true cmp=Equal
+r0=0x00000000 r1=0x0000002a
+Next instruction: syscall 2 at <unknown>:13 (label function)
+Stack (2 words):
+  0x00000038: 0x00000007 (text+2)
+  0x0000003c: 0x00000031 (main+35)
+Memory (64 bytes, non-zero regions):
+  0x00000000: 48 69 21 00 00 00 00 00 00 00 00 00 00 00 00 00  Hi!.............
+  *
+  0x00000020: 00 00 00 00 00 00 00 00 00 00 00 07 00 00 00 00  ................
+  0x00000030: 00 00 00 00 00 00 00 00 00 00 00 07 00 00 00 31  ...............1"
+	);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
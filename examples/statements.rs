@@ -0,0 +1,61 @@
+use my_vm::{Machine, Program};
+
+/// Counts down from 3 with several statements on each line.
+const PROGRAM: &str = r#"
+set 3 ; setRegister 0 0
+label loop; syscall 1; decrement ; jumpNonzero loop
+copyCodeMemory text; set 0; syscall 2 ;; halt
+label text; dataString a; b // c
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	Machine::<1>::new(program.compile()?, 64).run()?;
+
+	// The data string consumes the rest of its line, semicolons included.
+	let data: Program = "dataString a; b // c".parse()?;
+	let mut expected = Program::new();
+	expected.add_data(*b"a; b // c\0");
+	assert_eq!(data.compile()?, expected.compile()?);
+
+	// It is the same as one statement per line.
+	let separate: Program = r#"
+set 3
+setRegister 0 0
+label loop
+syscall 1
+decrement
+jumpNonzero loop
+copyCodeMemory text
+set 0
+syscall 2
+halt
+label text
+dataString a; b // c
+"#
+	.parse()?;
+	assert_eq!(program.compile()?, separate.compile()?);
+
+	// Comments also consume the rest of their line.
+	let commented: Program = "set 1 ; // set 2; set 3\n# halt; halt\nset 4; # set 5".parse()?;
+	let expected: Program = "set 1\nset 4".parse()?;
+	assert_eq!(commented.compile()?, expected.compile()?);
+
+	// Errors name the line of the failing statement.
+	let error = "halt\nset 1; frobnicate; halt".parse::<Program>().unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"line 2: Unknown command or wrong number of arguments: frobnicate"
+	);
+	let error = "label twice; halt; label twice".parse::<Program>().unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"line 1: Label twice is defined multiple times (line 1 and line 1)"
+	);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,125 @@
+use std::fs;
+
+use my_vm::{DebugInfo, Machine, Program};
+
+const PROGRAM: &str = r#"
+.data 256
+label hello
+dataString hello
+label world
+dataString world
+label number
+dataString 1234xyz
+label newline
+dataString
+label buffer
+dataBytes 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+.code
+
+# Included files are appended to the program, so they can be included anywhere.
+include "std/string.asm"
+include "std/convert.asm"
+include "std/string.asm"
+
+setRegister 4 42
+setRegister 0 hello ; call strlen ; call print
+setRegister 0 hello ; setRegister 1 world ; call strcmp ; call print
+setRegister 0 world ; setRegister 1 hello ; call strcmp ; call print
+setRegister 0 hello ; setRegister 1 hello ; call strcmp ; call print
+setRegister 0 buffer ; setRegister 1 world ; call strcpy ; call print
+set buffer ; syscall 0
+setRegister 0 hello ; setRegister 1 world ; setRegister 2 1 ; call memcmp ; call print
+setRegister 0 hello ; setRegister 1 hello ; setRegister 2 6 ; call memcmp ; call print
+setRegister 0 buffer ; setRegister 1 120 ; setRegister 2 3 ; call memset ; syscall 0
+setRegister 0 buffer ; setRegister 1 hello ; setRegister 2 2 ; call memcpy ; syscall 0
+setRegister 0 number ; call atoi ; call print
+setRegister 0 -1 ; setRegister 1 buffer ; call itoa ; call print
+set buffer ; syscall 0
+setRegister 0 0 ; setRegister 1 buffer ; call itoa ; call print
+set buffer ; syscall 0
+# Registers from r4 on are preserved.
+swap 4 ; call print
+halt
+
+label print
+    syscall 1
+    set newline
+    syscall 0
+    return
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	assert_eq!(program.required_side_registers(), 5);
+	assert!(program.lint(Some(5)).is_empty());
+	let mut machine =
+		Machine::<5>::from_executable(program.to_executable()?)?.with_captured_output();
+	machine.run()?;
+	let output = machine.take_output();
+	print!("{output}");
+	assert_eq!(
+		output.lines().collect::<Vec<_>>(),
+		[
+			"5",
+			"4294967295",
+			"1",
+			"0",
+			"5",
+			"world",
+			"4294967295",
+			"0",
+			"xxxld",
+			"hexld",
+			"1234",
+			"10",
+			"4294967295",
+			"1",
+			"0",
+			"42",
+		]
+	);
+
+	// Faults inside included code are reported in the included file.
+	let program: Program =
+		"include \"std/string.asm\"\nsetRegister 0 5000\ncall strlen\nhalt\n".parse()?;
+	let mut machine =
+		Machine::<4>::new(program.compile()?, 64).with_debug_info(program.debug_info("main.asm"));
+	let error = machine.run().unwrap_err();
+	assert_eq!(
+		format!("{error:#}"),
+		"Runtime error at std/string.asm:10 (label strlen.loop) in `deref8 0`: Out of memory \
+		 access occured at 5000"
+	);
+	let debug_info = program.debug_info("main.asm");
+	assert_eq!(debug_info.location(0), Some(("main.asm", 2)));
+	assert_eq!(debug_info.location(20), Some(("std/string.asm", 10)));
+	let sidecar = debug_info.to_sidecar();
+	assert_eq!(DebugInfo::from_sidecar(&sidecar)?.to_sidecar(), sidecar);
+
+	// Unknown modules are rejected.
+	let error = "include \"std/missing.asm\"".parse::<Program>().unwrap_err();
+	assert_eq!(error.to_string(), "line 1: Unknown standard library module std/missing.asm");
+
+	// Relative includes are resolved against the directory of the file.
+	let dir = std::env::temp_dir().join(format!("my_vm_stdlib_{}", std::process::id()));
+	fs::create_dir_all(dir.join("lib"))?;
+	fs::write(dir.join("main.asm"), "include \"lib/greet.asm\"\ncall greet\nhalt\n")?;
+	fs::write(
+		dir.join("lib/greet.asm"),
+		"include \"std/string.asm\"\nlabel greet\nset 7\nsyscall 1\nreturn\n",
+	)?;
+	let program = Program::from_file(dir.join("main.asm"));
+	fs::remove_dir_all(&dir)?;
+	let program = program?;
+	assert_eq!(program.included_files(), [dir.join("lib/greet.asm")]);
+	let mut machine =
+		Machine::<4>::from_executable(program.to_executable()?)?.with_captured_output();
+	machine.run()?;
+	assert_eq!(machine.take_output(), "7");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
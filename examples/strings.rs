@@ -0,0 +1,50 @@
+use my_vm::{Machine, Program};
+
+/// Print strings with explicit lengths: a slice of a string without
+/// terminator and a length-prefixed string.
+const PROGRAM: &str = r#"
+jump main
+label text
+dataString Hello, wörld!
+label prefixed
+dataBytes 0 0 0 5 103 114 252 195 159
+label main
+set 0 ; copyCodeMemory text
+setRegister 0 5
+syscall 20
+set 32 ; syscall 4
+// "wörld" is 6 bytes in UTF-8.
+set 7
+setRegister 0 6
+syscall 20
+set 32 ; syscall 4
+set 100 ; copyCodeMemory prefixed
+syscall 21
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let executable = program.to_executable()?;
+
+	// The length-prefixed string contains invalid UTF-8, which is an error.
+	let mut machine = Machine::<1>::from_executable(executable.clone())?.with_captured_output();
+	let error = machine.run().unwrap_err();
+	assert_eq!(
+		format!("{:#}", error.root_cause()),
+		"invalid utf-8 sequence of 1 bytes from index 2"
+	);
+	assert_eq!(machine.take_output(), "Hello wörld ");
+
+	// Or is replaced in lossy mode.
+	let mut machine =
+		Machine::<1>::from_executable(executable)?.with_captured_output().with_lossy_strings(true);
+	machine.run()?;
+	assert_eq!(machine.take_output(), "Hello wörld gr\u{FFFD}ß");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,68 @@
+use my_vm::{Machine, Program};
+
+/// Stores a point with a 32-bit x and a 16-bit y coordinate at memory address
+/// 100 and reads the y coordinate back.
+const PROGRAM: &str = r#"
+struct Point
+	field x 4
+	field y 2
+	field tag 1
+endstruct
+
+.define POINT 100
+.define POINT_Y 104
+
+set 7
+store32 POINT
+set 9
+store16 POINT_Y
+set Point.y
+setRegister 0 POINT
+add 0
+swap 0
+deref16 0
+syscall 1
+set Point.size
+syscall 1
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	Machine::<1>::new(program.compile()?, 128).run()?;
+
+	// Fields are constants holding their offsets, and the size is the sum of
+	// all fields.
+	let offsets: Program = r#"
+struct Pair
+	field first 4
+	field second 4
+endstruct
+set Pair.first
+set Pair.second
+set Pair.size
+"#
+	.parse()?;
+	let expected: Program = "set 0\nset 4\nset 8".parse()?;
+	assert_eq!(offsets.compile()?, expected.compile()?);
+
+	// Empty structs have size 0.
+	let empty: Program = "struct Empty\nendstruct\nset Empty.size".parse()?;
+	assert_eq!(empty.compile()?, "set 0".parse::<Program>()?.compile()?);
+
+	// Mistakes are reported with the line of the offending statement.
+	let error = "set 1\nfield x 4".parse::<Program>().unwrap_err();
+	assert_eq!(error.to_string(), "line 2: Field outside of struct");
+	let error = "halt\nstruct Open\nfield x 4".parse::<Program>().unwrap_err();
+	assert_eq!(error.to_string(), "line 2: Unterminated struct Open");
+	let error = "struct Point\nset 1\nendstruct".parse::<Program>().unwrap_err();
+	assert_eq!(error.to_string(), "line 2: Only fields are allowed in struct Point");
+	let error = "endstruct".parse::<Program>().unwrap_err();
+	assert_eq!(error.to_string(), "line 1: endstruct without struct");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,51 @@
+use my_vm::{Executable, Machine, Program};
+
+/// Calls a function reading beyond the end of memory.
+const PROGRAM: &str = r#"
+call read
+halt
+label read
+set 100
+load32 5000
+return
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let executable = program.to_executable()?;
+
+	// With debug information, errors name the source line, the label and the
+	// failing instruction.
+	let mut machine = Machine::<0>::new(executable.code.clone(), 64)
+		.with_debug_info(program.debug_info("read.asm"));
+	let error = machine.run().unwrap_err();
+	assert_eq!(
+		format!("{error:#}"),
+		"Runtime error at read.asm:6 (label read) in `load32 5000`: Out of memory access occured \
+		 at 5000"
+	);
+
+	// Symbols alone still give the label and the instruction.
+	let stripped = Executable { debug_info: None, ..executable.clone() };
+	let mut machine = Machine::<0>::from_executable(stripped)?;
+	let error = machine.run().unwrap_err();
+	assert_eq!(
+		format!("{error:#}"),
+		"Runtime error at <unknown> at code address 11 (label read) in `load32 5000`: Out of \
+		 memory access occured at 5000"
+	);
+
+	// Without, only the code address is known.
+	let mut machine = Machine::<0>::new(executable.code, 64);
+	let error = machine.run().unwrap_err();
+	assert_eq!(
+		format!("{error:#}"),
+		"Runtime error at code address 11: Out of memory access occured at 5000"
+	);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
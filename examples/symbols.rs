@@ -0,0 +1,59 @@
+use my_vm::{Instruction, Program, SymbolTable, VmPtr};
+
+/// Two labels share the address of the function, one is at the very end.
+const PROGRAM: &str = r#"
+label start
+call double
+halt
+label double
+label twice
+set 2
+mul 0
+return
+label end
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let (code, symbols) = program.compile_with_symbols()?;
+	assert_eq!(code, program.compile()?);
+	assert_eq!(symbols, program.symbols());
+
+	let call_size = Instruction::Call(0).size() as VmPtr;
+	let halt_size = Instruction::Halt.size() as VmPtr;
+	let double = call_size + halt_size;
+	assert_eq!(symbols.len(), 4);
+	assert_eq!(symbols.address("start"), Some(0));
+	assert_eq!(symbols.address("double"), Some(double));
+	assert_eq!(symbols.address("twice"), Some(double));
+	assert_eq!(symbols.address("end"), Some(code.len() as VmPtr));
+	assert_eq!(symbols.address("missing"), None);
+	assert_eq!(symbols.names_at(double).collect::<Vec<_>>(), ["double", "twice"]);
+	assert_eq!(symbols.lookup(double + 3), Some(("double", 3)));
+
+	// The sidecar file lists one symbol per line, sorted by address, and reads
+	// back to the same table.
+	let sidecar = symbols.to_sidecar();
+	assert_eq!(
+		sidecar,
+		format!(
+			"00000000 start\n{double:08x} double\n{double:08x} twice\n{:08x} end\n",
+			code.len()
+		)
+	);
+	assert_eq!(SymbolTable::from_sidecar(&sidecar)?, symbols);
+	assert_eq!(SymbolTable::from_sidecar(&format!("\n{sidecar}\n\n"))?, symbols);
+	assert!(SymbolTable::from_sidecar("")?.is_empty());
+
+	// Mistakes are reported with their line.
+	let error = SymbolTable::from_sidecar("00000000 start\nnospace").unwrap_err();
+	assert_eq!(error.to_string(), "Invalid symbol at line 2");
+	let error = SymbolTable::from_sidecar("xyz start").unwrap_err();
+	assert_eq!(error.to_string(), "Invalid symbol address at line 1");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,164 @@
+use my_vm::{Instruction, Machine, Program, TimerPeriod};
+
+/// Interrupt handler counting the ticks at memory address 0. The call pushes
+/// the address of the handler, which is popped to register it.
+const HANDLER: &str = r#"
+call install
+label handler
+push
+pushRegister 0
+setRegister 0 0
+deref32 0
+increment
+write32 0
+popRegister 0
+pop
+syscall 11
+label install
+pop
+syscall 9
+"#;
+
+/// Count to 1000 in a loop whose comparisons must survive interrupts.
+const COUNT: &str = r#"
+setRegister 0 1000
+set 0
+label loop
+increment
+compare 0
+jumpLess loop
+syscall 1
+halt
+"#;
+
+/// Mask the interrupt during a critical section, recording the ticks seen at
+/// its end at address 4, then wait for a tick after unmasking.
+const MASKED: &str = r#"
+set 1
+syscall 10
+setRegister 0 100
+set 0
+label critical
+increment
+compare 0
+jumpLess critical
+setRegister 0 0
+deref32 0
+setRegister 0 4
+write32 0
+set 0
+syscall 10
+label wait
+setRegister 0 0
+deref32 0
+increment
+decrement
+jumpZero wait
+halt
+"#;
+
+/// Wait until the handler counted 3 ticks.
+const WAIT: &str = r#"
+setRegister 1 3
+label wait
+setRegister 0 0
+deref32 0
+compare 1
+jumpLess wait
+halt
+"#;
+
+/// Interrupt handler at code address 0 like [`HANDLER`], registered from the
+/// entry point.
+const HANDLER_AT_ZERO: &str = r#"
+entry main
+label handler
+push
+pushRegister 0
+setRegister 0 0
+deref32 0
+increment
+write32 0
+popRegister 0
+pop
+syscall 11
+label main
+set 0
+syscall 9
+"#;
+
+/// Read the 32 bit value at the address.
+fn read(machine: &Machine<2>, addr: u32) -> anyhow::Result<u32> {
+	let bytes = machine.read_memory(addr, 4)?;
+	Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+fn main() -> anyhow::Result<()> {
+	// Interrupts every 10 instructions do not disturb the interrupted code.
+	let program: Program = format!("{HANDLER}{COUNT}").parse()?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?
+		.with_captured_output()
+		.with_timer(TimerPeriod::Instructions(10))?;
+	machine.run()?;
+	assert_eq!(machine.take_output(), "1000");
+	let ticks = read(&machine, 0)?;
+	assert!(ticks > 300, "only {ticks} ticks");
+	assert_eq!(machine.stack_pointer(), machine.memory_size());
+
+	// Masked interrupts are delivered once unmasked.
+	let program: Program = format!("{HANDLER}{MASKED}").parse()?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?
+		.with_timer(TimerPeriod::Instructions(10))?;
+	machine.run()?;
+	assert_eq!(read(&machine, 4)?, 0);
+	assert!(read(&machine, 0)? > 0);
+
+	// Wall-clock timers.
+	let program: Program = format!("{HANDLER}{WAIT}").parse()?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?
+		.with_fuel(1_000_000_000)
+		.with_timer(TimerPeriod::Milliseconds(1))?;
+	machine.run()?;
+	assert_eq!(read(&machine, 0)?, 3);
+
+	// Without a handler, the timer does nothing.
+	let program: Program = COUNT.parse()?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?
+		.with_captured_output()
+		.with_timer(TimerPeriod::Instructions(1))?;
+	machine.run()?;
+	assert_eq!(machine.take_output(), "1000");
+
+	// Code address 0 is a valid handler.
+	let program: Program = format!("{HANDLER_AT_ZERO}{WAIT}").parse()?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?
+		.with_fuel(100_000)
+		.with_timer(TimerPeriod::Instructions(100))?;
+	machine.run()?;
+	assert_eq!(read(&machine, 0)?, 3);
+
+	// Registering `VmPtr::MAX` removes the handler again.
+	let program: Program = format!("{HANDLER_AT_ZERO}set -1\nsyscall 9\n{COUNT}").parse()?;
+	let mut machine = Machine::<2>::from_executable(program.to_executable()?)?
+		.with_captured_output()
+		.with_timer(TimerPeriod::Instructions(20))?;
+	machine.run()?;
+	assert_eq!(machine.take_output(), "1000");
+	assert_eq!(read(&machine, 0)?, 0);
+
+	// Misuse is reported.
+	assert!(Machine::<2>::new(Vec::new(), 64).with_timer(TimerPeriod::Milliseconds(0)).is_err());
+	let mut machine = Machine::<2>::new(Vec::new(), 64);
+	let error = machine.execute_instruction(Instruction::Syscall(9)).unwrap_err();
+	assert_eq!(error.to_string(), "No timer attached");
+	let mut machine =
+		Machine::<2>::new(Vec::new(), 64).with_timer(TimerPeriod::Instructions(10))?;
+	let error = machine.execute_instruction(Instruction::Syscall(11)).unwrap_err();
+	assert_eq!(error.to_string(), "Return from interrupt outside of a handler");
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,39 @@
+use my_vm::{Machine, Program, Tracer};
+
+const PROGRAM: &str = r#"
+setRegister 0 3
+label loop
+set 42
+push
+decrementRegister 0
+jumpNonzero loop
+halt
+"#;
+
+fn main() -> anyhow::Result<()> {
+	let program: Program = PROGRAM.parse()?;
+	let mut machine = Machine::<1>::new(program.compile()?, 64);
+
+	let mut trace = Vec::new();
+	let mut tracer = Tracer::new(&mut trace);
+	tracer.run(&mut machine)?;
+	let trace = String::from_utf8(trace)?;
+	print!("{trace}");
+
+	// One line per executed instruction, pushes report the written bytes.
+	assert_eq!(trace.lines().count(), 14);
+	let push = trace.lines().find(|line| line.contains(r#""mnemonic":"push""#)).unwrap();
+	assert!(push.contains(r#""writes":[{"address":60,"bytes":"0000002a"}]"#));
+
+	// Sampling and limits keep traces small.
+	let mut machine = Machine::<1>::new(program.compile()?, 64);
+	let mut tracer = Tracer::new(std::io::sink()).with_sampling(2).with_limit(3);
+	tracer.run(&mut machine)?;
+	assert_eq!(tracer.traced(), 3);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,56 @@
+use my_vm::{Instruction, Program};
+
+fn main() -> anyhow::Result<()> {
+	// A valid program passes.
+	let mut program = Program::new();
+	let data = program.add_data(c"ok".to_bytes_with_nul());
+	let start = program.add_instruction(Instruction::Set(0));
+	program.add_copy_data(data)?;
+	program.add_syscall(0);
+	program.add_jump(start)?;
+	program.validate()?;
+
+	// Jump into the middle of the set instruction at 00000008.
+	let mut program = Program::new();
+	program.add_data(c"ok".to_bytes_with_nul());
+	program.add_instruction(Instruction::Set(0));
+	program.add_instruction(Instruction::Jump(9));
+	assert_eq!(
+		program.validate().unwrap_err().to_string(),
+		"Program has 1 problem(s):\nInstruction 2 at 0000000d targets 00000009 in the middle of \
+		 an instruction"
+	);
+
+	// Copy from the set instruction instead of the data segment, and copy
+	// beyond the end of the data segment.
+	let mut program = Program::new();
+	program.add_data(c"ok".to_bytes_with_nul());
+	program.add_instruction(Instruction::Set(0));
+	program.add_instruction(Instruction::CopyCodeMemory(8, 3));
+	program.add_instruction(Instruction::CopyCodeMemory(5, 4));
+	assert_eq!(
+		program.validate().unwrap_err().to_string(),
+		"Program has 2 problem(s):\nInstruction 2 at 0000000d copies 3 bytes from 00000008, \
+		 which is not within a data segment\nInstruction 3 at 00000016 copies 4 bytes from \
+		 00000005, which is not within a data segment"
+	);
+
+	// Targets outside of the program, dummy addresses and undefined labels.
+	let mut program = Program::new();
+	program.add_instruction(Instruction::Call(1000));
+	program.add_dummy_jump();
+	program.add_jump_label("missing");
+	program.add_halt();
+	assert_eq!(
+		program.validate().unwrap_err().to_string(),
+		"Program has 3 problem(s):\nInstruction 0 at 00000000 targets 000003e8 outside of the \
+		 program\nInstruction 1 at 00000005 still has a dummy address\nInstruction 2 refers to \
+		 undefined label missing"
+	);
+	Ok(())
+}
+
+#[test]
+fn test() {
+	main().unwrap();
+}
@@ -0,0 +1,233 @@
+//! Static analysis of the stack depth and memory requirements of programs.
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt,
+};
+
+use crate::{util::vm_ptr, Instruction, Program, SourceLine, SymbolTable, VmPtr};
+
+/// Estimated memory requirements of a program, see [`Program::analyze`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+	/// Maximum stack depth in bytes when running from the entry point,
+	/// including the return addresses of calls. `None` if it cannot be
+	/// bounded, see [`Self::unbounded`].
+	pub max_stack_depth: Option<VmPtr>,
+	/// Reasons why the stack depth cannot be bounded, e.g. recursion.
+	pub unbounded: Vec<String>,
+	/// End of the highest memory region that is statically known to be used:
+	/// the data section, loads and stores of constant addresses and code
+	/// copies to constant addresses.
+	pub static_memory_end: VmPtr,
+}
+
+impl Analysis {
+	/// Suggested minimum memory size: the static memory below the stack at its
+	/// maximum depth. `None` if the stack depth cannot be bounded.
+	pub fn suggested_memory_size(&self) -> Option<VmPtr> {
+		self.max_stack_depth.and_then(|depth| self.static_memory_end.checked_add(depth))
+	}
+}
+
+impl fmt::Display for Analysis {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.max_stack_depth {
+			Some(depth) => writeln!(f, "Maximum stack depth: {depth} bytes")?,
+			None => writeln!(f, "Maximum stack depth: unbounded")?,
+		}
+		for reason in &self.unbounded {
+			writeln!(f, "  {reason}")?;
+		}
+		writeln!(f, "Static memory end: {}", self.static_memory_end)?;
+		match self.suggested_memory_size() {
+			Some(size) => write!(f, "Suggested minimum memory size: {size} bytes"),
+			None => write!(f, "Suggested minimum memory size: unknown"),
+		}
+	}
+}
+
+impl Program {
+	/// Estimate the memory requirements: walk the call graph from the entry
+	/// point to find the maximum stack depth and collect the statically known
+	/// memory addresses. Recursion, loops that grow the stack and writes to
+	/// the stack pointer cannot be bounded. Interrupt handlers and program
+	/// arguments are not taken into account.
+	pub fn analyze(&self) -> Analysis {
+		let instructions = self.iter().collect::<Vec<_>>();
+		let index_of = instructions
+			.iter()
+			.enumerate()
+			.map(|(index, (addr, _))| (*addr, index))
+			.collect::<BTreeMap<_, _>>();
+		let symbols = self.symbols();
+		let mut stack = StackAnalysis {
+			program: self,
+			instructions: &instructions,
+			index_of: &index_of,
+			symbols: &symbols,
+			depths: BTreeMap::new(),
+			unbounded: BTreeSet::new(),
+		};
+		let max_stack_depth = match index_of.get(&self.entry_point()) {
+			Some(entry) => stack.function_depth(*entry),
+			None => Some(0),
+		};
+		let unbounded = stack.unbounded.into_iter().collect();
+
+		let mut static_memory_end = if self.data().is_empty() {
+			0
+		} else {
+			self.data_base().saturating_add(vm_ptr(self.data().len()))
+		};
+		let mut previous: Option<&Instruction> = None;
+		for (_, instruction) in &instructions {
+			let end = match (previous, instruction) {
+				(_, Instruction::Load8(addr) | Instruction::Store8(addr)) => addr.saturating_add(1),
+				(_, Instruction::Load16(addr) | Instruction::Store16(addr)) => {
+					addr.saturating_add(2)
+				}
+				(_, Instruction::Load32(addr) | Instruction::Store32(addr)) => {
+					addr.saturating_add(4)
+				}
+				// The target of code copies is known if set right before.
+				(Some(Instruction::Set(target)), Instruction::CopyCodeMemory(_, size)) => {
+					target.saturating_add(*size)
+				}
+				_ => 0,
+			};
+			static_memory_end = static_memory_end.max(end);
+			previous = Some(*instruction);
+		}
+
+		Analysis { max_stack_depth, unbounded, static_memory_end }
+	}
+}
+
+/// State of the stack depth analysis.
+struct StackAnalysis<'a> {
+	/// Analyzed program, to locate instructions in the source.
+	program: &'a Program,
+	/// Instructions of the program and their code addresses.
+	instructions: &'a [(VmPtr, &'a Instruction)],
+	/// Instruction index by code address.
+	index_of: &'a BTreeMap<VmPtr, usize>,
+	/// Labels of the program, to name functions.
+	symbols: &'a SymbolTable,
+	/// Maximum stack depth of the functions by start index: `None` while the
+	/// function is analyzed, `Some(None)` if it is unbounded.
+	depths: BTreeMap<usize, Option<Option<VmPtr>>>,
+	/// Reasons why the stack depth cannot be bounded.
+	unbounded: BTreeSet<String>,
+}
+
+impl StackAnalysis<'_> {
+	/// Name of the function starting at the given index.
+	fn name(&self, function: usize) -> String {
+		let addr = self.instructions[function].0;
+		self.symbols.name_at(addr).map_or_else(|| format!("at {addr}"), str::to_owned)
+	}
+
+	/// Describe the location of the indexed instruction, preferring its source
+	/// line.
+	fn location(&self, index: usize) -> String {
+		match self.program.source(index) {
+			Some(SourceLine { file: Some(file), line, .. }) => format!("{file}:{line}"),
+			Some(SourceLine { file: None, line, .. }) => format!("line {line}"),
+			None => format!("instruction {index}"),
+		}
+	}
+
+	/// Maximum stack depth of the function starting at the given index, from
+	/// its start to its return, including its calls.
+	fn function_depth(&mut self, function: usize) -> Option<VmPtr> {
+		match self.depths.get(&function) {
+			Some(Some(depth)) => return *depth,
+			Some(None) => {
+				self.unbounded.insert(format!("Function {} is recursive", self.name(function)));
+				return None;
+			}
+			None => {}
+		}
+		self.depths.insert(function, None);
+		let depth = self.walk(function);
+		self.depths.insert(function, Some(depth));
+		depth
+	}
+
+	/// Follow every path through the function starting at the given index and
+	/// return the maximum stack depth.
+	fn walk(&mut self, function: usize) -> Option<VmPtr> {
+		let word = vm_ptr(size_of::<VmPtr>());
+		let mut depths = BTreeMap::<usize, VmPtr>::new();
+		let mut pending = vec![(function, 0)];
+		let mut max_depth = Some(0);
+		while let Some((index, depth)) = pending.pop() {
+			let Some((_, instruction)) = self.instructions.get(index) else { continue };
+			match depths.insert(index, depth) {
+				Some(previous) if previous == depth => continue,
+				Some(_) => {
+					self.unbounded.insert(format!(
+						"Function {}: stack depth differs between paths at {}",
+						self.name(function),
+						self.location(index)
+					));
+					return None;
+				}
+				None => {}
+			}
+			let next = index + 1;
+			let reached = match instruction {
+				Instruction::Push | Instruction::PushRegister(_) => {
+					pending.push((next, depth + word));
+					Some(depth + word)
+				}
+				// Popping more than was pushed is reported by the lint.
+				Instruction::Pop | Instruction::PopRegister(_) => {
+					pending.push((next, depth.saturating_sub(word)));
+					Some(depth)
+				}
+				Instruction::WriteStackPointer => {
+					self.unbounded.insert(format!(
+						"Function {} writes the stack pointer at {}",
+						self.name(function),
+						self.location(index)
+					));
+					return None;
+				}
+				Instruction::Halt | Instruction::Return => Some(depth),
+				Instruction::Call(addr) => {
+					pending.push((next, depth));
+					match self.index_of.get(addr) {
+						Some(callee) => {
+							self.function_depth(*callee).map(|callee| depth + word + callee)
+						}
+						None => Some(depth + word),
+					}
+				}
+				Instruction::Jump(addr) => {
+					pending.extend(self.index_of.get(addr).map(|&to| (to, depth)));
+					Some(depth)
+				}
+				Instruction::JumpEqual(addr)
+				| Instruction::JumpNotEqual(addr)
+				| Instruction::JumpGreater(addr)
+				| Instruction::JumpLess(addr)
+				| Instruction::JumpGreaterEqual(addr)
+				| Instruction::JumpLessEqual(addr)
+				| Instruction::JumpZero(addr)
+				| Instruction::JumpNonzero(addr) => {
+					pending.extend(self.index_of.get(addr).map(|&to| (to, depth)));
+					pending.push((next, depth));
+					Some(depth)
+				}
+				_ => {
+					pending.push((next, depth));
+					Some(depth)
+				}
+			};
+			max_depth = max_depth.zip(reached).map(|(max, reached)| max.max(reached));
+		}
+		max_depth
+	}
+}
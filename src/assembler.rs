@@ -0,0 +1,813 @@
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	ffi::CString,
+	fmt,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+	instruction::Instruction,
+	linker::Object,
+	program::{Program, SourceLine},
+	stdlib,
+	util::{closest_match, native_ptr, parse_value},
+	VmPtr,
+};
+
+/// Assembler state while translating the text assembly language into a
+/// [`Program`].
+#[derive(Debug, Default)]
+pub struct Assembler {
+	/// Program that is being built.
+	program: Program,
+	/// Index of the next instruction.
+	next_index: usize,
+	/// Instruction index and the locations of all definitions of all labels.
+	label_index: HashMap<String, (usize, Vec<Location>)>,
+	/// Jumps or calls that need to be resolved to a label: instruction index,
+	/// label and location.
+	dummy_jumps: Vec<(usize, String, Location)>,
+	/// Copy data instructions that need to be resolved to a label: instruction
+	/// index, label and location.
+	dummy_copy_data: Vec<(usize, String, Location)>,
+	/// Named constants usable in operands and conditions.
+	constants: HashMap<String, VmPtr>,
+	/// Currently open conditional sections, innermost last.
+	conditions: Vec<Condition>,
+	/// Repeat block that is currently being collected.
+	repeat: Option<Repeat>,
+	/// Number of statements all `.rept` blocks expanded to so far.
+	repeat_expansion: usize,
+	/// Labels of all entry point directives and their locations. Only one is
+	/// allowed.
+	entry: Vec<(String, Location)>,
+	/// Struct layout that is currently being defined.
+	structure: Option<Struct>,
+	/// Whether statements go to the data section instead of the code.
+	in_data_section: bool,
+	/// Labels exported from an object and the locations of the directives.
+	globals: Vec<(String, Location)>,
+	/// Whether unresolved jump and call targets are kept as imports of an
+	/// object instead of being errors.
+	allow_imports: bool,
+	/// Directory relative includes of the current file are resolved against.
+	dir: Option<PathBuf>,
+	/// Included file that is currently assembled, `None` for the input itself.
+	file: Option<String>,
+	/// Files that are included but not assembled yet.
+	includes: VecDeque<Include>,
+	/// Names of all included files, so that every file is included once.
+	included: HashSet<String>,
+	/// Errors found so far. Assembly continues after errors to report all of
+	/// them at once.
+	errors: Vec<AssemblyError>,
+}
+
+/// Error found while assembling, see [`Program::parse_with_diagnostics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+	/// Included file of the error, `None` for the input itself.
+	pub file: Option<String>,
+	/// Source line of the error, if it belongs to a single line.
+	pub line: Option<usize>,
+	/// Description of the error.
+	pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (&self.file, self.line) {
+			(Some(file), Some(line)) => write!(f, "{file}:{line}: {}", self.message),
+			(Some(file), None) => write!(f, "{file}: {}", self.message),
+			(None, Some(line)) => write!(f, "line {line}: {}", self.message),
+			(None, None) => write!(f, "{}", self.message),
+		}
+	}
+}
+
+/// Error recorded by the assembler, with its location.
+#[derive(Debug)]
+struct AssemblyError {
+	/// Included file of the error, `None` for the input itself.
+	file: Option<String>,
+	/// Source line of the error, if it belongs to a single line.
+	line: Option<usize>,
+	/// The error itself.
+	error: anyhow::Error,
+}
+
+impl AssemblyError {
+	/// Convert into a diagnostic with the full error message.
+	fn to_diagnostic(&self) -> Diagnostic {
+		Diagnostic {
+			file: self.file.clone(),
+			line: self.line,
+			message: format!("{:#}", self.error),
+		}
+	}
+
+	/// Combine the errors into one: a single error is returned with its
+	/// location, several are listed with their locations.
+	fn combine(errors: Vec<Self>) -> anyhow::Error {
+		if let [error] = errors.as_slice() {
+			return anyhow::format_err!("{}", error.to_diagnostic());
+		}
+		let diagnostics =
+			errors.iter().map(|error| error.to_diagnostic().to_string()).collect::<Vec<_>>();
+		anyhow::format_err!("{} errors:\n{}", errors.len(), diagnostics.join("\n"))
+	}
+}
+
+/// Location of a statement in the input or an included file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Location {
+	/// Included file, `None` for the input itself.
+	file: Option<String>,
+	/// Line number.
+	line: usize,
+}
+
+impl fmt::Display for Location {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.file {
+			Some(file) => write!(f, "{file}:{}", self.line),
+			None => write!(f, "line {}", self.line),
+		}
+	}
+}
+
+impl Location {
+	/// List the locations for an error message, e.g. `line 1, line 2 and
+	/// lib.asm:3`.
+	fn list(locations: &[Self]) -> String {
+		let mut locations = locations.iter().map(Self::to_string).collect::<Vec<_>>();
+		let last = locations.pop().unwrap_or_default();
+		if locations.is_empty() {
+			return last;
+		}
+		format!("{} and {last}", locations.join(", "))
+	}
+}
+
+/// File included with `include "<path>"`.
+#[derive(Debug, Clone)]
+struct Include {
+	/// Name of the file: the include path for the standard library, otherwise
+	/// the resolved file path.
+	name: String,
+	/// Path of the file, `None` for the standard library.
+	path: Option<PathBuf>,
+}
+
+/// Statements that are allowed in the data section, besides directives.
+const DATA_SECTION_KEYWORDS: [&str; 7] =
+	["#", "//", "label", "datastring", "databytes", "align", "include"];
+
+/// Layout of a struct that is being defined.
+#[derive(Debug, Clone)]
+struct Struct {
+	/// Line number of the opening directive.
+	line: usize,
+	/// Name of the struct.
+	name: String,
+	/// Offset of the next field, i.e. the size so far.
+	offset: VmPtr,
+}
+
+/// Maximum number of statements all `.rept` blocks of a program, including
+/// nested ones, may expand to.
+const MAX_REPEAT_EXPANSION: usize = 100_000;
+
+/// Body of a `.rept` block that is being collected.
+#[derive(Debug, Clone)]
+struct Repeat {
+	/// Line number of the opening directive.
+	line: usize,
+	/// Number of repetitions.
+	count: VmPtr,
+	/// Name of the iteration counter, substituted for `{name}` in the body.
+	variable: Option<String>,
+	/// Nesting depth of further `.rept` blocks inside the body.
+	depth: usize,
+	/// Collected statements and their line numbers.
+	body: Vec<(usize, String)>,
+}
+
+/// State of an open `.if` section.
+#[derive(Debug, Clone, Copy)]
+struct Condition {
+	/// Line number of the opening directive.
+	line: usize,
+	/// Whether the surrounding section is active.
+	parent_active: bool,
+	/// Whether the current branch is active.
+	active: bool,
+	/// Whether any branch of this section was active already.
+	taken: bool,
+	/// Whether the `.else` branch was reached.
+	in_else: bool,
+}
+
+impl Assembler {
+	/// Assemble the whole input to a program. All errors are reported at
+	/// once.
+	pub fn assemble(input: &str) -> anyhow::Result<Program> {
+		Self::assemble_with_defines(input, HashMap::new())
+	}
+
+	/// Assemble the whole input to a program, with predefined constants.
+	pub fn assemble_with_defines(
+		input: &str,
+		defines: HashMap<String, VmPtr>,
+	) -> anyhow::Result<Program> {
+		let mut assembler = Self { constants: defines, ..Self::default() };
+		assembler.assemble_input(input);
+		assembler.finish().map_err(AssemblyError::combine)
+	}
+
+	/// Assemble the whole input to a program, returning all errors as
+	/// diagnostics.
+	pub fn assemble_with_diagnostics(input: &str) -> Result<Program, Vec<Diagnostic>> {
+		let mut assembler = Self::default();
+		assembler.assemble_input(input);
+		assembler
+			.finish()
+			.map_err(|errors| errors.iter().map(AssemblyError::to_diagnostic).collect())
+	}
+
+	/// Assemble the whole input to a program, resolving relative includes
+	/// against the given directory instead of the working directory.
+	pub fn assemble_in_dir(input: &str, dir: Option<&Path>) -> anyhow::Result<Program> {
+		let mut assembler = Self { dir: dir.map(Path::to_path_buf), ..Self::default() };
+		assembler.assemble_input(input);
+		assembler.finish().map_err(AssemblyError::combine)
+	}
+
+	/// Assemble the whole input to a relocatable object. Jump and call targets
+	/// that are not defined in the input are kept as imports.
+	pub fn assemble_object(input: &str) -> anyhow::Result<Object> {
+		let mut assembler = Self { allow_imports: true, ..Self::default() };
+		assembler.assemble_input(input);
+		assembler.finish().map_err(AssemblyError::combine)?.to_object()
+	}
+
+	/// Assemble the lines of the input, followed by the files it includes.
+	/// Included files are appended after the input in the order they are first
+	/// included, so that their routines are not executed by falling through.
+	/// Errors are recorded and assembly continues with the next line.
+	fn assemble_input(&mut self, input: &str) {
+		self.assemble_lines(input);
+		while let Some(include) = self.includes.pop_front() {
+			self.dir = include.path.as_deref().and_then(Path::parent).map(Path::to_path_buf);
+			self.file = Some(include.name.clone());
+			self.in_data_section = false;
+			let source = match &include.path {
+				Some(path) => match std::fs::read_to_string(path) {
+					Ok(source) => source,
+					Err(err) => {
+						let error = anyhow::Error::new(err)
+							.context(format!("Cannot read included file {}", path.display()));
+						self.report(None, error);
+						continue;
+					}
+				},
+				None => stdlib::module(&include.name).expect("checked when included").to_owned(),
+			};
+			self.assemble_lines(&source);
+			if let Some(path) = include.path {
+				self.program.add_included_file(path);
+			}
+		}
+	}
+
+	/// Assemble the lines of a file and check that all blocks are closed at
+	/// its end.
+	fn assemble_lines(&mut self, input: &str) {
+		for (line_number, line) in input.lines().enumerate() {
+			if let Err(error) = self.parse_line(line_number + 1, line) {
+				self.report(Some(line_number + 1), error);
+			}
+		}
+		if let Err((line, error)) = self.check_closed() {
+			self.report(Some(line), error);
+			self.structure = None;
+			self.repeat = None;
+			self.conditions.clear();
+		}
+	}
+
+	/// Record an error in the current file.
+	fn report(&mut self, line: Option<usize>, error: anyhow::Error) {
+		self.errors.push(AssemblyError { file: self.file.clone(), line, error });
+	}
+
+	/// Location of the given line in the current file.
+	fn location(&self, line: usize) -> Location {
+		Location { file: self.file.clone(), line }
+	}
+
+	/// Queue the file for assembly, unless it was included already.
+	fn include(&mut self, path: &str) -> anyhow::Result<()> {
+		let include = if path.starts_with("std/") {
+			anyhow::ensure!(
+				stdlib::module(path).is_some(),
+				"Unknown standard library module {path}"
+			);
+			Include { name: path.to_owned(), path: None }
+		} else {
+			let path = match &self.dir {
+				Some(dir) => dir.join(path),
+				None => PathBuf::from(path),
+			};
+			Include { name: path.display().to_string(), path: Some(path) }
+		};
+		if self.included.insert(include.name.clone()) {
+			self.includes.push_back(include);
+		}
+		Ok(())
+	}
+
+	/// Parse a source line. A line can hold multiple statements separated by
+	/// `;`. A comment or a data string consumes the rest of the line.
+	fn parse_line(&mut self, line_number: usize, line: &str) -> anyhow::Result<()> {
+		let mut rest = line;
+		loop {
+			let statement = rest.trim();
+			let keyword = statement.split_whitespace().next().unwrap_or_default();
+			if ["#", "//"].contains(&keyword) || keyword.eq_ignore_ascii_case("datastring") {
+				return self.parse_statement(line_number, statement);
+			}
+			let Some((statement, remainder)) = statement.split_once(';') else {
+				return self.parse_statement(line_number, statement);
+			};
+			self.parse_statement(line_number, statement.trim())?;
+			rest = remainder;
+		}
+	}
+
+	/// Parse a single statement and add it to the program. Empty statements
+	/// are ignored.
+	fn parse_statement(&mut self, line_number: usize, statement: &str) -> anyhow::Result<()> {
+		let parts = statement.split_whitespace().collect::<Vec<_>>();
+		if parts.is_empty() || self.collect_repeat(line_number, statement, &parts)? {
+			return Ok(());
+		}
+		if self.parse_conditional(line_number, &parts)? || !self.is_active() {
+			return Ok(());
+		}
+		let first_index = self.program.len();
+		let keyword = parts[0].to_lowercase();
+		if let Some(structure) = &self.structure {
+			if !["#", "//", "field", "endstruct"].contains(&keyword.as_str()) {
+				anyhow::bail!("Only fields are allowed in struct {}", structure.name);
+			}
+		}
+		if self.in_data_section
+			&& !keyword.starts_with('.')
+			&& !DATA_SECTION_KEYWORDS.contains(&keyword.as_str())
+		{
+			anyhow::bail!("Instruction {} in data section", parts[0]);
+		}
+		match keyword.as_str() {
+			// Comments.
+			"#" | "//" => {}
+			// .data [<base address>]
+			".data" if parts.len() <= 2 => {
+				if let Some(base) = parts.get(1) {
+					let base = self.value(base)?;
+					self.program.set_data_base(base)?;
+				}
+				self.in_data_section = true;
+			}
+			// .code
+			".code" if parts.len() == 1 => self.in_data_section = false,
+			// Include "<path>"
+			"include" if parts.len() > 1 => {
+				let path = statement[7..]
+					.trim()
+					.strip_prefix('"')
+					.and_then(|path| path.strip_suffix('"'))
+					.context("Include path must be quoted")?;
+				self.include(path)?;
+			}
+			// Label <name> in the data section defines a constant address.
+			"label" if parts.len() == 2 && self.in_data_section => {
+				let addr = self.program.data_end()?;
+				self.define(parts[1].to_owned(), addr)?;
+			}
+			// Align <alignment> in the data section.
+			"align" if parts.len() == 2 && self.in_data_section => {
+				let alignment = self.value(parts[1])?;
+				self.program.align_static_data(alignment)?;
+			}
+			// DataString <str> in the data section.
+			"datastring" if self.in_data_section => {
+				let cstr = CString::new(statement.split_at(10).1.trim())?;
+				self.program.add_static_data(cstr.into_bytes_with_nul())?;
+			}
+			// DataBytes <byte>... in the data section.
+			"databytes" if self.in_data_section => {
+				let data = parts[1..]
+					.iter()
+					.map(|byte| self.byte(byte))
+					.collect::<anyhow::Result<Vec<_>>>()?;
+				self.program.add_static_data(data)?;
+			}
+			// Struct <name>
+			"struct" if parts.len() == 2 => {
+				self.structure =
+					Some(Struct { line: line_number, name: parts[1].to_owned(), offset: 0 });
+			}
+			// Field <name> <size>
+			"field" if parts.len() == 3 => {
+				let size = self.value(parts[2])?;
+				let structure = self.structure.as_mut().context("Field outside of struct")?;
+				let name = format!("{}.{}", structure.name, parts[1]);
+				let offset = structure.offset;
+				structure.offset = offset
+					.checked_add(size)
+					.with_context(|| format!("Struct {} is too large", structure.name))?;
+				self.define(name, offset)?;
+			}
+			// Endstruct
+			"endstruct" if parts.len() == 1 => {
+				let structure = self.structure.take().context("endstruct without struct")?;
+				self.define(format!("{}.size", structure.name), structure.offset)?;
+			} // .define <name> <value>
+			".define" if parts.len() == 3 => {
+				let value = self.value(parts[2])?;
+				self.define(parts[1].to_owned(), value)?;
+			}
+			// .rept <count> [<variable>]
+			".rept" if (2..=3).contains(&parts.len()) => {
+				self.repeat = Some(Repeat {
+					line: line_number,
+					count: self.value(parts[1])?,
+					variable: parts.get(2).map(|variable| (*variable).to_owned()),
+					depth: 0,
+					body: Vec::new(),
+				});
+			}
+			".endr" if parts.len() == 1 => {
+				anyhow::bail!(".endr without .rept");
+			}
+			// Entry <label>
+			"entry" if parts.len() == 2 => {
+				self.entry.push((parts[1].to_owned(), self.location(line_number)));
+				if self.entry.len() > 1 {
+					let locations =
+						self.entry.iter().map(|(_, location)| location.clone()).collect::<Vec<_>>();
+					anyhow::bail!(
+						"Entry point is defined multiple times ({})",
+						Location::list(&locations)
+					);
+				}
+			}
+			// Global <label>
+			"global" if parts.len() == 2 => {
+				self.globals.push((parts[1].to_owned(), self.location(line_number)));
+			}
+			// Align <alignment>
+			"align" if parts.len() == 2 => {
+				let alignment = self.value(parts[1])?;
+				self.next_index += self.program.add_align(alignment)?;
+			}
+			// Label <name>
+			"label" if parts.len() == 2 => {
+				let location = self.location(line_number);
+				if let Some((_, locations)) = self.label_index.get_mut(parts[1]) {
+					locations.push(location);
+					anyhow::bail!(
+						"Label {} is defined multiple times ({})",
+						parts[1],
+						Location::list(locations)
+					);
+				}
+				self.label_index.insert(parts[1].to_owned(), (self.next_index, vec![location]));
+			}
+			// CopyCodeMemory <target_data_label>
+			"copycodememory" if parts.len() == 2 => {
+				let index = self.program.add_dummy_copy_data();
+				self.dummy_copy_data.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// Jump <label>
+			"jump" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// Call <label>
+			"call" if parts.len() == 2 => {
+				let index = self.program.add_dummy_call();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// JumpEqual <label>
+			"jumpequal" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump_equal();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// JumpNotEqual <label>
+			"jumpnotequal" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump_not_equal();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// JumpGreater <label>
+			"jumpgreater" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump_greater();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// JumpLess <label>
+			"jumpless" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump_less();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// JumpGreaterEqual <label>
+			"jumpgreaterequal" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump_greater_equal();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// JumpLessEqual <label>
+			"jumplessequal" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump_less_equal();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// JumpZero <label>
+			"jumpzero" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump_zero();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// JumpNonzero <label>
+			"jumpnonzero" if parts.len() == 2 => {
+				let index = self.program.add_dummy_jump_nonzero();
+				self.dummy_jumps.push((index, parts[1].to_owned(), self.location(line_number)));
+				self.next_index += 1;
+			}
+			// Any other instruction with constant operands.
+			_ => {
+				let instruction =
+					Instruction::from_statement(statement, |operand| self.value(operand))?
+						.with_context(|| {
+							format!("Unknown command or wrong number of arguments: {}", parts[0])
+						})?;
+				self.program.add_instruction(instruction);
+				self.next_index += 1;
+			}
+		}
+		for index in first_index..self.program.len() {
+			let source = SourceLine {
+				file: self.file.clone(),
+				line: line_number,
+				text: statement.to_owned(),
+			};
+			self.program.set_source(index, source);
+		}
+		Ok(())
+	}
+
+	/// Collect the statement into the body of the current `.rept` block and
+	/// expand the block once it is closed. Return whether the statement was
+	/// consumed.
+	fn collect_repeat(
+		&mut self,
+		line_number: usize,
+		statement: &str,
+		parts: &[&str],
+	) -> anyhow::Result<bool> {
+		let Some(repeat) = self.repeat.as_mut() else {
+			return Ok(false);
+		};
+		match parts[0].to_lowercase().as_str() {
+			".rept" => repeat.depth += 1,
+			".endr" if repeat.depth == 0 => {
+				let repeat = self.repeat.take().expect("checked above");
+				if repeat.body.is_empty() {
+					return Ok(true);
+				}
+				let expansion = native_ptr(repeat.count)
+					.checked_mul(repeat.body.len())
+					.and_then(|expansion| expansion.checked_add(self.repeat_expansion))
+					.filter(|expansion| *expansion <= MAX_REPEAT_EXPANSION);
+				anyhow::ensure!(
+					expansion.is_some(),
+					".rept blocks expand to more than {MAX_REPEAT_EXPANSION} statements"
+				);
+				// Errors are reported at their line in the body, only once for all
+				// iterations.
+				let mut failed = HashSet::new();
+				for iteration in 0..repeat.count {
+					for (line_number, statement) in &repeat.body {
+						let statement = match &repeat.variable {
+							Some(variable) => statement
+								.replace(&format!("{{{variable}}}"), &iteration.to_string()),
+							None => statement.clone(),
+						};
+						self.repeat_expansion += 1;
+						if let Err(error) = self.parse_statement(*line_number, &statement) {
+							if failed.insert(*line_number) {
+								self.report(Some(*line_number), error);
+							}
+						}
+					}
+				}
+				return Ok(true);
+			}
+			".endr" => repeat.depth -= 1,
+			_ => {}
+		}
+		repeat.body.push((line_number, statement.to_owned()));
+		Ok(true)
+	}
+
+	/// Whether statements in the current conditional section are assembled.
+	fn is_active(&self) -> bool {
+		self.conditions.last().is_none_or(|condition| condition.active)
+	}
+
+	/// Handle conditional assembly directives. Return whether the statement
+	/// was such a directive.
+	fn parse_conditional(&mut self, line_number: usize, parts: &[&str]) -> anyhow::Result<bool> {
+		let parent_active = self.is_active();
+		match parts[0].to_lowercase().as_str() {
+			// .if <expression>
+			".if" if parts.len() > 1 => {
+				let condition = if parent_active { self.evaluate(&parts[1..]) } else { Ok(false) };
+				// Open the section even if the condition is invalid, so that
+				// its `.endif` is no error as well.
+				let active = condition.as_ref().is_ok_and(|active| *active);
+				self.open_condition(line_number, parent_active, active);
+				condition?;
+			}
+			// .ifdef <name>
+			".ifdef" if parts.len() == 2 => {
+				let condition = self.constants.contains_key(parts[1]);
+				self.open_condition(line_number, parent_active, condition);
+			}
+			// .ifndef <name>
+			".ifndef" if parts.len() == 2 => {
+				let condition = !self.constants.contains_key(parts[1]);
+				self.open_condition(line_number, parent_active, condition);
+			}
+			// .else
+			".else" if parts.len() == 1 => {
+				let condition = self.conditions.last_mut().context(".else without .if")?;
+				if condition.in_else {
+					anyhow::bail!("Multiple .else for the .if at line {}", condition.line);
+				}
+				condition.in_else = true;
+				condition.active = condition.parent_active && !condition.taken;
+				condition.taken |= condition.active;
+			}
+			// .endif
+			".endif" if parts.len() == 1 => {
+				self.conditions.pop().context(".endif without .if")?;
+			}
+			_ => return Ok(false),
+		}
+		Ok(true)
+	}
+
+	/// Open a new conditional section.
+	fn open_condition(&mut self, line: usize, parent_active: bool, condition: bool) {
+		self.conditions.push(Condition {
+			line,
+			parent_active,
+			active: condition,
+			taken: condition,
+			in_else: false,
+		});
+	}
+
+	/// Evaluate a constant expression of the form `<value>` (true if nonzero)
+	/// or `<value> <comparison> <value>`.
+	fn evaluate(&self, expression: &[&str]) -> anyhow::Result<bool> {
+		match expression {
+			[value] => Ok(self.value(value)? != 0),
+			[left, operator, right] => {
+				let (left, right) = (self.value(left)?, self.value(right)?);
+				match *operator {
+					"==" => Ok(left == right),
+					"!=" => Ok(left != right),
+					"<" => Ok(left < right),
+					">" => Ok(left > right),
+					"<=" => Ok(left <= right),
+					">=" => Ok(left >= right),
+					_ => Err(anyhow::format_err!("Unknown comparison operator: {operator}")),
+				}
+			}
+			_ => Err(anyhow::format_err!("Invalid expression: {}", expression.join(" "))),
+		}
+	}
+
+	/// Define a new constant.
+	fn define(&mut self, name: String, value: VmPtr) -> anyhow::Result<()> {
+		if self.constants.contains_key(&name) {
+			anyhow::bail!("Constant {name} is defined multiple times");
+		}
+		self.constants.insert(name, value);
+		Ok(())
+	}
+
+	/// Parse a numeric operand, which is either a literal or a constant.
+	/// Negative literals are encoded as two's complement.
+	fn value(&self, operand: &str) -> anyhow::Result<VmPtr> {
+		if let Some(value) = self.constants.get(operand) {
+			return Ok(*value);
+		}
+		parse_value(operand)
+			.with_context(|| format!("Invalid number or unknown constant: {operand}"))
+	}
+
+	/// Parse a byte sized operand like a register or syscall index.
+	fn byte(&self, operand: &str) -> anyhow::Result<u8> {
+		let value = self.value(operand)?;
+		u8::try_from(value).with_context(|| format!("Operand {operand} does not fit into a byte"))
+	}
+
+	/// Ensure that no struct, `.rept` or `.if` block is left open at the end of
+	/// a file. Otherwise return the line of the opening directive along with
+	/// the error.
+	fn check_closed(&self) -> Result<(), (usize, anyhow::Error)> {
+		if let Some(structure) = &self.structure {
+			let error = anyhow::format_err!("Unterminated struct {}", structure.name);
+			return Err((structure.line, error));
+		}
+		if let Some(repeat) = &self.repeat {
+			return Err((repeat.line, anyhow::format_err!("Unterminated .rept")));
+		}
+		if let Some(condition) = self.conditions.last() {
+			return Err((condition.line, anyhow::format_err!("Unterminated .if")));
+		}
+		Ok(())
+	}
+
+	/// Resolve the dummies to their labels and return the finished program or
+	/// all errors.
+	fn finish(mut self) -> Result<Program, Vec<AssemblyError>> {
+		let mut errors = std::mem::take(&mut self.errors);
+		let label_index = &self.label_index;
+		let resolve_label = |label: &str| -> anyhow::Result<usize> {
+			if let Some((target, _)) = label_index.get(label) {
+				return Ok(*target);
+			}
+			let suggestion = closest_match(label, label_index.keys().map(String::as_str))
+				.map(|name| format!(", did you mean `{name}`?"))
+				.unwrap_or_default();
+			Err(anyhow::format_err!("Unresolved label {label}{suggestion}"))
+		};
+		let mut report = |location: &Location, error| {
+			errors.push(AssemblyError {
+				file: location.file.clone(),
+				line: Some(location.line),
+				error,
+			});
+		};
+		for (index, label, location) in &self.dummy_jumps {
+			if self.allow_imports && !label_index.contains_key(label) {
+				self.program.add_import(*index, label.clone());
+				continue;
+			}
+			let result = resolve_label(label)
+				.and_then(|target| self.program.replace_dummy_address(*index, target));
+			if let Err(error) = result {
+				report(location, error);
+			}
+		}
+		for (index, label, location) in &self.dummy_copy_data {
+			let result = resolve_label(label)
+				.and_then(|target| self.program.replace_dummy_copy_data(*index, target));
+			if let Err(error) = result {
+				report(location, error);
+			}
+		}
+		if let Some((label, location)) = self.entry.first() {
+			let result = resolve_label(label).and_then(|target| self.program.set_entry(target));
+			if let Err(error) = result {
+				report(location, error);
+			}
+		}
+		for (label, location) in &self.globals {
+			match resolve_label(label) {
+				Ok(_) => self.program.insert_global(label.clone()),
+				Err(error) => report(location, error),
+			}
+		}
+		if !errors.is_empty() {
+			return Err(errors);
+		}
+		for (name, (index, _)) in self.label_index {
+			self.program.insert_label(name, index);
+		}
+		Ok(self.program)
+	}
+}
@@ -0,0 +1,168 @@
+//! Audio output device for tones and PCM samples, driven by syscalls 12 and
+//! 13. Sounds are recorded for inspection or, with the `audio` feature, played
+//! on the default output device.
+
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "audio")]
+use std::{sync::mpsc, thread, time::Duration};
+
+use crate::VmPtr;
+
+/// Sound queued on the audio device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sound {
+	/// Sine tone with the frequency in Hz and the duration in
+	/// milliseconds. A frequency of 0 is silence.
+	Tone {
+		/// Frequency in Hz.
+		frequency: VmPtr,
+		/// Duration in milliseconds.
+		duration_ms: VmPtr,
+	},
+	/// Unsigned 8 bit mono PCM samples at [`Audio::SAMPLE_RATE`], where 128 is
+	/// the center.
+	Samples(Vec<u8>),
+}
+
+/// Where the sounds go.
+#[derive(Debug, Clone)]
+enum Backend {
+	/// Store the sounds.
+	Recording(Arc<Mutex<Vec<Sound>>>),
+	/// Send the sounds to the playback thread.
+	#[cfg(feature = "audio")]
+	Playback(mpsc::Sender<Command>),
+}
+
+/// Command for the playback thread.
+#[cfg(feature = "audio")]
+enum Command {
+	/// Queue the sound after the previous ones.
+	Play(Sound),
+	/// Reply once all queued sounds are played.
+	Wait(mpsc::Sender<()>),
+}
+
+/// Handle to an audio device. Sounds are queued and played one after the
+/// other while the program continues. Clones share the same device.
+#[derive(Debug, Clone)]
+pub struct Audio {
+	/// Backend of the device.
+	backend: Backend,
+}
+
+impl Audio {
+	/// Sample rate of PCM samples in Hz.
+	pub const SAMPLE_RATE: u32 = 8000;
+
+	/// Create an audio device that records the sounds instead of playing
+	/// them, see [`Self::recorded`].
+	pub fn recording() -> Self {
+		Self { backend: Backend::Recording(Arc::new(Mutex::new(Vec::new()))) }
+	}
+
+	/// Create an audio device playing on the default output device of the
+	/// host.
+	#[cfg(feature = "audio")]
+	pub fn playback() -> anyhow::Result<Self> {
+		use anyhow::Context;
+		use rodio::{buffer::SamplesBuffer, source::SineWave, OutputStream, Sink, Source};
+
+		let (sender, receiver) = mpsc::channel();
+		let (ready_sender, ready) = mpsc::channel();
+		// The output stream cannot be sent between threads, so it lives in the
+		// playback thread.
+		thread::spawn(move || {
+			let sink = OutputStream::try_default()
+				.context("Cannot open the audio output device")
+				.and_then(|(stream, handle)| {
+					Ok((stream, Sink::try_new(&handle).context("Cannot play audio")?))
+				});
+			let (_stream, sink) = match sink {
+				Ok(sink) => {
+					_ = ready_sender.send(Ok(()));
+					sink
+				}
+				Err(err) => {
+					_ = ready_sender.send(Err(err));
+					return;
+				}
+			};
+			for command in receiver {
+				match command {
+					Command::Play(Sound::Tone { frequency, duration_ms }) => {
+						let duration = Duration::from_millis(duration_ms.into());
+						let amplitude = if frequency == 0 { 0.0 } else { 0.2 };
+						sink.append(
+							SineWave::new(frequency as f32)
+								.take_duration(duration)
+								.amplify(amplitude),
+						);
+					}
+					Command::Play(Sound::Samples(samples)) => {
+						let samples = samples
+							.into_iter()
+							.map(|sample| (i16::from(sample) - 128) << 8)
+							.collect::<Vec<_>>();
+						sink.append(SamplesBuffer::new(1, Self::SAMPLE_RATE, samples));
+					}
+					Command::Wait(done) => {
+						sink.sleep_until_end();
+						_ = done.send(());
+					}
+				}
+			}
+		});
+		ready.recv().context("Audio playback thread stopped")??;
+		Ok(Self { backend: Backend::Playback(sender) })
+	}
+
+	/// Queue the sound.
+	pub fn play(&self, sound: Sound) {
+		match &self.backend {
+			Backend::Recording(sounds) => {
+				sounds.lock().expect("audio lock poisoned").push(sound);
+			}
+			#[cfg(feature = "audio")]
+			Backend::Playback(sender) => {
+				// Without playback thread, there is nothing to play on.
+				_ = sender.send(Command::Play(sound));
+			}
+		}
+	}
+
+	/// Block until all queued sounds are played.
+	pub fn wait(&self) {
+		match &self.backend {
+			Backend::Recording(_) => {}
+			#[cfg(feature = "audio")]
+			Backend::Playback(sender) => {
+				let (done, finished) = mpsc::channel();
+				if sender.send(Command::Wait(done)).is_ok() {
+					_ = finished.recv();
+				}
+			}
+		}
+	}
+
+	/// Sounds recorded so far, empty when playing.
+	pub fn recorded(&self) -> Vec<Sound> {
+		match &self.backend {
+			Backend::Recording(sounds) => sounds.lock().expect("audio lock poisoned").clone(),
+			#[cfg(feature = "audio")]
+			Backend::Playback(_) => Vec::new(),
+		}
+	}
+}
+
+impl PartialEq for Audio {
+	/// Audio devices are equal if they are the same recording, playing devices
+	/// are never equal.
+	fn eq(&self, other: &Self) -> bool {
+		match (&self.backend, &other.backend) {
+			(Backend::Recording(a), Backend::Recording(b)) => Arc::ptr_eq(a, b),
+			#[cfg(feature = "audio")]
+			_ => false,
+		}
+	}
+}
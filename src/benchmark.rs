@@ -0,0 +1,85 @@
+//! Throughput measurement of programs, the baseline for performance work on
+//! the interpreter.
+
+use std::{
+	fmt,
+	time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+use crate::Machine;
+
+/// Measured execution speed of repeated program runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throughput {
+	/// Number of program runs.
+	iterations: u64,
+	/// Total number of executed instructions.
+	instructions: u64,
+	/// Total time spent running, without setting up the machines.
+	elapsed: Duration,
+}
+
+impl Throughput {
+	/// Run clones of the prepared machine `iterations` times until they halt,
+	/// counting the executed instructions. Attach captured output to avoid
+	/// printing every run. A limited fuel of the machine applies to every run.
+	pub fn measure<const SIDE_REGS: usize>(
+		machine: &Machine<SIDE_REGS>,
+		iterations: u64,
+	) -> anyhow::Result<Self> {
+		anyhow::ensure!(iterations > 0, "Number of iterations must not be 0");
+		let fuel = machine.remaining_fuel().unwrap_or(u64::MAX);
+		let mut throughput = Self { iterations, instructions: 0, elapsed: Duration::ZERO };
+		for iteration in 0..iterations {
+			let mut machine = machine.clone().with_fuel(fuel);
+			let start = Instant::now();
+			let result = machine.run();
+			throughput.elapsed += start.elapsed();
+			result.with_context(|| format!("Benchmark iteration {iteration} failed"))?;
+			throughput.instructions += fuel - machine.remaining_fuel().unwrap_or_default();
+		}
+		Ok(throughput)
+	}
+
+	/// Number of program runs.
+	pub fn iterations(&self) -> u64 {
+		self.iterations
+	}
+
+	/// Total number of executed instructions.
+	pub fn instructions(&self) -> u64 {
+		self.instructions
+	}
+
+	/// Number of executed instructions of a single run.
+	pub fn instructions_per_iteration(&self) -> u64 {
+		self.instructions / self.iterations
+	}
+
+	/// Total time spent running.
+	pub fn elapsed(&self) -> Duration {
+		self.elapsed
+	}
+
+	/// Executed instructions per second.
+	pub fn instructions_per_second(&self) -> f64 {
+		self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+	}
+}
+
+impl fmt::Display for Throughput {
+	/// Human readable summary, e.g. `10 iterations, 52 instructions each, in
+	/// 1.2ms: 433333 instructions/s`.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} iterations, {} instructions each, in {:.1?}: {:.0} instructions/s",
+			self.iterations,
+			self.instructions_per_iteration(),
+			self.elapsed,
+			self.instructions_per_second()
+		)
+	}
+}
@@ -0,0 +1,125 @@
+//! Block storage device backed by a host file, accessed sector by sector with
+//! syscalls 6-8.
+
+use std::{
+	fs::{File, OpenOptions},
+	io::{Read, Seek, SeekFrom, Write},
+	path::Path,
+	sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+
+use crate::{util::native_ptr, VmPtr};
+
+/// Handle to a virtual block device. Clones share the same backing file.
+#[derive(Debug, Clone)]
+pub struct BlockDevice {
+	/// Backing file.
+	file: Arc<Mutex<File>>,
+	/// Size of a sector in bytes.
+	sector_size: VmPtr,
+	/// Number of sectors.
+	sectors: VmPtr,
+}
+
+impl BlockDevice {
+	/// Default sector size in bytes.
+	pub const DEFAULT_SECTOR_SIZE: VmPtr = 512;
+
+	/// Open an existing file as block device with the given sector size. The
+	/// number of sectors is the file size divided by the sector size, rounded
+	/// up; the missing bytes of a partial last sector read as 0.
+	pub fn open(path: impl AsRef<Path>, sector_size: VmPtr) -> anyhow::Result<Self> {
+		let path = path.as_ref();
+		anyhow::ensure!(sector_size > 0, "Sector size must not be 0");
+		let file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.open(path)
+			.with_context(|| format!("Cannot open block device {}", path.display()))?;
+		let len = file.metadata()?.len();
+		let sectors = VmPtr::try_from(len.div_ceil(u64::from(sector_size)))
+			.context("Block device has too many sectors")?;
+		Ok(Self { file: Arc::new(Mutex::new(file)), sector_size, sectors })
+	}
+
+	/// Create a block device with the given sector size and number of sectors,
+	/// creating the file or resizing an existing one. Existing contents within
+	/// the new size are kept, new sectors are zeroed.
+	pub fn create(
+		path: impl AsRef<Path>,
+		sector_size: VmPtr,
+		sectors: VmPtr,
+	) -> anyhow::Result<Self> {
+		let path = path.as_ref();
+		let file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(path)
+			.with_context(|| format!("Cannot create block device {}", path.display()))?;
+		file.set_len(u64::from(sector_size) * u64::from(sectors))
+			.with_context(|| format!("Cannot resize block device {}", path.display()))?;
+		drop(file);
+		Self::open(path, sector_size)
+	}
+
+	/// Size of a sector in bytes.
+	pub fn sector_size(&self) -> VmPtr {
+		self.sector_size
+	}
+
+	/// Number of sectors.
+	pub fn sectors(&self) -> VmPtr {
+		self.sectors
+	}
+
+	/// Read the sector into the buffer, which must be one sector long.
+	pub fn read_sector(&self, sector: VmPtr, buffer: &mut [u8]) -> anyhow::Result<()> {
+		let offset = self.offset(sector, buffer.len())?;
+		let mut file = self.file.lock().expect("block device lock poisoned");
+		file.seek(SeekFrom::Start(offset))?;
+		let mut read = 0;
+		while read < buffer.len() {
+			match file.read(&mut buffer[read..])? {
+				0 => break,
+				n => read += n,
+			}
+		}
+		buffer[read..].fill(0);
+		Ok(())
+	}
+
+	/// Write the data, which must be one sector long, to the sector.
+	pub fn write_sector(&self, sector: VmPtr, data: &[u8]) -> anyhow::Result<()> {
+		let offset = self.offset(sector, data.len())?;
+		let mut file = self.file.lock().expect("block device lock poisoned");
+		file.seek(SeekFrom::Start(offset))?;
+		file.write_all(data).with_context(|| format!("Cannot write sector {sector}"))
+	}
+
+	/// Byte offset of the sector in the file, checking the sector number and
+	/// the buffer length.
+	fn offset(&self, sector: VmPtr, len: usize) -> anyhow::Result<u64> {
+		anyhow::ensure!(
+			sector < self.sectors,
+			"Sector {sector} is out of range, the block device has {} sectors",
+			self.sectors
+		);
+		anyhow::ensure!(
+			len == native_ptr(self.sector_size),
+			"Sector buffer has {len} bytes instead of {}",
+			self.sector_size
+		);
+		Ok(u64::from(sector) * u64::from(self.sector_size))
+	}
+}
+
+impl PartialEq for BlockDevice {
+	/// Block devices are equal if they share the same backing file.
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.file, &other.file)
+	}
+}
@@ -0,0 +1,150 @@
+//! Brainfuck frontend, translating Brainfuck source to a [`Program`].
+//!
+//! The tape lives in VM memory starting at address 0, with the tape pointer
+//! in side register 0. Cells are bytes that wrap around. `.` and `,` use the
+//! byte output and input syscalls, and `,` stores 0 at the end of the input.
+//! Runs of `+`, `-`, `<` and `>` are folded into single additions, and `[-]`
+//! and `[+]` clear the cell directly.
+
+use anyhow::Context;
+
+use crate::{Instruction, Program, VmPtr};
+
+/// Default number of cells of the tape.
+pub const BRAINFUCK_TAPE_SIZE: VmPtr = 30_000;
+
+/// Side register holding the tape pointer.
+const POINTER: u8 = 0;
+/// Side register used for operands.
+const OPERAND: u8 = 1;
+
+/// Compile Brainfuck source to a program with a tape of the given number of
+/// cells. All characters besides the eight commands are comments. The program
+/// needs 2 side registers and its memory size is set to the tape size. Moving
+/// the tape pointer outside of the tape is a memory access error at runtime.
+pub fn compile_brainfuck(source: &str, tape_size: VmPtr) -> anyhow::Result<Program> {
+	let commands = source
+		.lines()
+		.enumerate()
+		.flat_map(|(line, text)| {
+			text.char_indices().map(move |(column, c)| (c, line + 1, column + 1))
+		})
+		.filter(|(c, ..)| "+-<>.,[]".contains(*c))
+		.collect::<Vec<_>>();
+
+	let mut program = Program::new();
+	program.set_memory_size(tape_size);
+	// Open loops: label number, line and column of the `[`.
+	let mut loops = Vec::new();
+	let mut labels = 0;
+	let mut position = 0;
+	while let Some(&(command, line, column)) = commands.get(position) {
+		let run = commands[position..].iter().take_while(|(c, ..)| *c == command).count();
+		match command {
+			'+' | '-' => {
+				let amount = VmPtr::try_from(run % 256).expect("smaller than 256");
+				program.add_instruction(Instruction::Deref8(POINTER));
+				add_to_main(&mut program, amount, command == '+');
+				program.add_instruction(Instruction::Write8(POINTER));
+				position += run;
+			}
+			'>' | '<' => {
+				let amount = VmPtr::try_from(run).context("Too many pointer moves")?;
+				match (amount, command) {
+					(1, '>') => {
+						program.add_instruction(Instruction::IncrementRegister(POINTER));
+					}
+					(1, _) => {
+						program.add_instruction(Instruction::DecrementRegister(POINTER));
+					}
+					_ => {
+						program.add_instruction(Instruction::Swap(POINTER));
+						add_to_main(&mut program, amount, command == '>');
+						program.add_instruction(Instruction::Swap(POINTER));
+					}
+				}
+				position += run;
+			}
+			'.' => {
+				program.add_instruction(Instruction::Deref8(POINTER));
+				program.add_syscall(4);
+				position += 1;
+			}
+			',' => {
+				// The read syscall returns VmPtr::MAX at the end of the input,
+				// which is incremented to 0.
+				labels += 1;
+				let end_of_input = format!("input_{labels}_end");
+				program.add_syscall(3);
+				program.add_instruction(Instruction::Increment);
+				program.add_jump_zero_label(&end_of_input);
+				program.add_instruction(Instruction::Decrement);
+				program.add_label(end_of_input)?;
+				program.add_instruction(Instruction::Write8(POINTER));
+				position += 1;
+			}
+			'[' if is_clear_loop(&commands[position..]) => {
+				program.add_instruction(Instruction::Set(0));
+				program.add_instruction(Instruction::Write8(POINTER));
+				position += 3;
+			}
+			'[' => {
+				labels += 1;
+				program.add_instruction(Instruction::Deref8(POINTER));
+				test_zero(&mut program);
+				program.add_jump_zero_label(&format!("loop_{labels}_end"));
+				program.add_label(format!("loop_{labels}"))?;
+				loops.push((labels, line, column));
+				position += 1;
+			}
+			_ => {
+				let (label, ..) = loops
+					.pop()
+					.with_context(|| format!("Unmatched ] at line {line}, column {column}"))?;
+				program.add_instruction(Instruction::Deref8(POINTER));
+				test_zero(&mut program);
+				program.add_jump_nonzero_label(&format!("loop_{label}"));
+				program.add_label(format!("loop_{label}_end"))?;
+				position += 1;
+			}
+		}
+	}
+	if let Some((_, line, column)) = loops.pop() {
+		anyhow::bail!("Unmatched [ at line {line}, column {column}");
+	}
+	program.add_halt();
+	Ok(program)
+}
+
+/// Whether the commands start with `[-]` or `[+]`, which clear the cell.
+fn is_clear_loop(commands: &[(char, usize, usize)]) -> bool {
+	matches!(commands, [('[', ..), ('-' | '+', ..), (']', ..), ..])
+}
+
+/// Add or subtract the amount to the main register, using the operand
+/// register.
+fn add_to_main(program: &mut Program, amount: VmPtr, add: bool) {
+	match (amount, add) {
+		(0, _) => {}
+		(1, true) => {
+			program.add_instruction(Instruction::Increment);
+		}
+		(1, false) => {
+			program.add_instruction(Instruction::Decrement);
+		}
+		_ => {
+			program.add_instruction(Instruction::SetRegister(OPERAND, amount));
+			program.add_instruction(if add {
+				Instruction::Add(OPERAND)
+			} else {
+				Instruction::Sub(OPERAND)
+			});
+		}
+	}
+}
+
+/// Set the zero flag to whether the main register is 0.
+fn test_zero(program: &mut Program) {
+	program.add_instruction(Instruction::Increment);
+	program.add_instruction(Instruction::Decrement);
+}
@@ -0,0 +1,204 @@
+//! Calling convention helpers for building programs with functions that take
+//! arguments on the stack and have local variables.
+//!
+//! Stack frame of a function with frame pointer `fp` (side register 4):
+//!
+//! ```text
+//! fp + 8 + 4 * (n_args - 1 - i)  argument i, pushed by the caller in order
+//! fp + 4                         return address
+//! fp                             frame pointer of the caller
+//! fp - 4 * (j + 1)               local variable j, initialized to 0
+//! ```
+//!
+//! The result is returned in the main register. The callee removes its
+//! arguments from the stack when returning. Side registers 0-3 are scratch
+//! registers that are not preserved across calls, the frame pointer and all
+//! further side registers are preserved. This matches the standard library,
+//! so its routines can be called from such functions.
+
+use anyhow::Context;
+
+use crate::{Instruction, Program, VmPtr};
+
+/// Side register holding the frame pointer of the current function.
+pub const FRAME_POINTER: u8 = 4;
+
+/// Scratch register used by the generated code.
+const SCRATCH: u8 = 3;
+
+/// Function that is currently being built.
+#[derive(Debug, Clone)]
+pub(crate) struct Frame {
+	/// Name of the function.
+	name: String,
+	/// Number of arguments.
+	args: usize,
+	/// Number of local variables.
+	locals: usize,
+}
+
+impl Program {
+	/// Start a function with the given number of arguments and local variables
+	/// at a label with its name and emit the prologue, which sets up the frame
+	/// pointer and reserves the zero-initialized locals. Return the index of
+	/// the first instruction, to be used by jumps or calls.
+	pub fn begin_function(
+		&mut self,
+		name: &str,
+		n_args: usize,
+		n_locals: usize,
+	) -> anyhow::Result<usize> {
+		if let Some(frame) = &self.frame {
+			anyhow::bail!("Function {} is not ended before function {name}", frame.name);
+		}
+		let index = self.add_label(name)?;
+		self.add_instruction(Instruction::PushRegister(FRAME_POINTER));
+		self.add_instruction(Instruction::ReadStackPointer);
+		self.add_instruction(Instruction::Swap(FRAME_POINTER));
+		if n_locals > 0 {
+			self.add_instruction(Instruction::Set(0));
+			for _ in 0..n_locals {
+				self.add_instruction(Instruction::Push);
+			}
+		}
+		self.frame = Some(Frame { name: name.to_owned(), args: n_args, locals: n_locals });
+		Ok(index)
+	}
+
+	/// Return from the current function with the result in the main
+	/// register. Can be used anywhere in the function body. Return the index of
+	/// the first instruction.
+	pub fn add_function_return(&mut self) -> anyhow::Result<usize> {
+		let args = self.frame.as_ref().context("Return outside of a function")?.args;
+		// Reset the stack pointer to the frame pointer, keeping the result.
+		let index = self.add_instruction(Instruction::Swap(FRAME_POINTER));
+		self.add_instruction(Instruction::WriteStackPointer);
+		self.add_instruction(Instruction::Swap(FRAME_POINTER));
+		self.add_instruction(Instruction::PopRegister(FRAME_POINTER));
+		if args > 0 {
+			// Remove the arguments below the return address.
+			self.add_instruction(Instruction::PopRegister(SCRATCH));
+			for _ in 0..args {
+				self.add_instruction(Instruction::PopRegister(SCRATCH - 1));
+			}
+			self.add_instruction(Instruction::PushRegister(SCRATCH));
+		}
+		self.add_return();
+		Ok(index)
+	}
+
+	/// End the current function by returning with the result in the main
+	/// register. Return the index of the first instruction of the epilogue.
+	pub fn end_function(&mut self) -> anyhow::Result<usize> {
+		let index = self.add_function_return()?;
+		self.frame = None;
+		Ok(index)
+	}
+
+	/// Push the values of the side registers as arguments of the next call, in
+	/// order. Return the index of the first instruction.
+	pub fn push_args(&mut self, registers: &[u8]) -> usize {
+		let index = self.len();
+		for register in registers {
+			self.add_instruction(Instruction::PushRegister(*register));
+		}
+		index
+	}
+
+	/// Call the function with the given name, which can also be defined later.
+	/// Its arguments must have been pushed before, see [`Self::push_args`].
+	/// Afterwards, the result is in the main register and side registers 0-3
+	/// are clobbered. Return the index of the call instruction.
+	pub fn call_function(&mut self, name: &str) -> usize {
+		self.add_call_label(name)
+	}
+
+	/// Load argument `index` of the current function into the main register.
+	/// Clobbers side register 3. Return the index of the first instruction.
+	pub fn load_arg(&mut self, index: usize) -> anyhow::Result<usize> {
+		let offset = self.arg_offset(index)?;
+		Ok(self.load_frame(offset))
+	}
+
+	/// Store the main register into argument `index` of the current function.
+	/// Clobbers side registers 2 and 3. Return the index of the first
+	/// instruction.
+	pub fn store_arg(&mut self, index: usize) -> anyhow::Result<usize> {
+		let offset = self.arg_offset(index)?;
+		Ok(self.store_frame(offset))
+	}
+
+	/// Load local variable `index` of the current function into the main
+	/// register. Clobbers side register 3. Return the index of the first
+	/// instruction.
+	pub fn load_local(&mut self, index: usize) -> anyhow::Result<usize> {
+		let offset = self.local_offset(index)?;
+		Ok(self.load_frame(offset))
+	}
+
+	/// Store the main register into local variable `index` of the current
+	/// function. Clobbers side registers 2 and 3. Return the index of the first
+	/// instruction.
+	pub fn store_local(&mut self, index: usize) -> anyhow::Result<usize> {
+		let offset = self.local_offset(index)?;
+		Ok(self.store_frame(offset))
+	}
+
+	/// Offset of the argument from the frame pointer.
+	fn arg_offset(&self, index: usize) -> anyhow::Result<VmPtr> {
+		let frame = self.frame.as_ref().context("Argument access outside of a function")?;
+		anyhow::ensure!(
+			index < frame.args,
+			"Function {} has no argument {index}, it takes {}",
+			frame.name,
+			frame.args
+		);
+		VmPtr::try_from(frame.args - 1 - index)
+			.ok()
+			.and_then(|slot| slot.checked_mul(4)?.checked_add(8))
+			.context("Too many arguments")
+	}
+
+	/// Offset of the local variable from the frame pointer, wrapping around as
+	/// it is below the frame pointer.
+	fn local_offset(&self, index: usize) -> anyhow::Result<VmPtr> {
+		let frame = self.frame.as_ref().context("Local variable access outside of a function")?;
+		anyhow::ensure!(
+			index < frame.locals,
+			"Function {} has no local variable {index}, it has {}",
+			frame.name,
+			frame.locals
+		);
+		VmPtr::try_from(index + 1)
+			.ok()
+			.and_then(|slot| slot.checked_mul(4))
+			.map(VmPtr::wrapping_neg)
+			.context("Too many local variables")
+	}
+
+	/// Compute the frame pointer plus the offset into the scratch register.
+	fn frame_address(&mut self, offset: VmPtr) -> usize {
+		let index = self.add_instruction(Instruction::Set(0));
+		self.add_instruction(Instruction::Add(FRAME_POINTER));
+		self.add_instruction(Instruction::SetRegister(SCRATCH, offset));
+		self.add_instruction(Instruction::Add(SCRATCH));
+		self.add_instruction(Instruction::Swap(SCRATCH));
+		index
+	}
+
+	/// Load the 32 bit value at the offset from the frame pointer.
+	fn load_frame(&mut self, offset: VmPtr) -> usize {
+		let index = self.frame_address(offset);
+		self.add_instruction(Instruction::Deref32(SCRATCH));
+		index
+	}
+
+	/// Store the main register at the offset from the frame pointer.
+	fn store_frame(&mut self, offset: VmPtr) -> usize {
+		let index = self.add_instruction(Instruction::Swap(SCRATCH - 1));
+		self.frame_address(offset);
+		self.add_instruction(Instruction::Swap(SCRATCH - 1));
+		self.add_instruction(Instruction::Write32(SCRATCH));
+		index
+	}
+}
@@ -0,0 +1,43 @@
+//! Compiler for a small C-like language targeting the VM.
+//!
+//! ```text
+//! // Line comments.
+//! fn add(a, b) {
+//!     return a + b;
+//! }
+//!
+//! fn main() {
+//!     let i = 0;
+//!     while (i < 3) {
+//!         if (i % 2 == 0) { print("even "); } else { print("odd "); }
+//!         print_number(add(i, 10));
+//!         println("");
+//!         i = i + 1;
+//!     }
+//! }
+//! ```
+//!
+//! All values are unsigned 32 bit numbers, arithmetic wraps around. String
+//! literals evaluate to the memory address of the nul terminated string in the
+//! data section. Conditions are true if they are not 0; comparisons and the
+//! logical operators `!`, `&&` and `||` result in 0 or 1, the latter two short
+//! circuit. Functions return 0 if they end without return statement.
+//! Builtins: `println(str)`, `print(str)` and `print_number(n)`.
+//!
+//! The generated program calls `main` and halts afterwards. It needs 2 side
+//! registers.
+
+mod codegen;
+mod lexer;
+mod parser;
+
+use crate::Program;
+
+/// Compile source code of the high-level language to a program. Errors name
+/// the line they occurred on. The instructions carry the source line they were
+/// generated for, functions are labelled with their names.
+pub fn compile(source: &str) -> anyhow::Result<Program> {
+	let tokens = lexer::tokenize(source)?;
+	let functions = parser::parse(tokens)?;
+	codegen::generate(&functions, source)
+}
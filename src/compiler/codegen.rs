@@ -0,0 +1,418 @@
+//! Code generation from the syntax tree to a [`Program`].
+//!
+//! Expressions are evaluated into the main register, using the stack for
+//! intermediate values and side registers 0 and 1 as scratch registers.
+//! Parameters and local variables live on the stack. Since the compiler knows
+//! how many words are pushed at every point, variables are addressed relative
+//! to the stack pointer without a frame pointer.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use super::parser::{BinaryOp, Expr, Function, Statement, StatementKind, UnaryOp};
+use crate::{Instruction, Program, SourceLine, VmPtr};
+
+/// Size of a stack slot in bytes.
+const WORD: VmPtr = 4;
+
+/// Builtin functions with their number of arguments and syscall.
+const BUILTINS: [(&str, usize, u8); 3] =
+	[("println", 1, 0), ("print_number", 1, 1), ("print", 1, 2)];
+
+/// Generate the program for the functions, starting at `main`.
+pub(super) fn generate(functions: &[Function], source: &str) -> anyhow::Result<Program> {
+	let mut arities = HashMap::new();
+	for function in functions {
+		anyhow::ensure!(
+			!BUILTINS.iter().any(|(name, ..)| *name == function.name),
+			"line {}: Function {} shadows a builtin",
+			function.line,
+			function.name
+		);
+		anyhow::ensure!(
+			arities.insert(function.name.as_str(), function.params.len()).is_none(),
+			"line {}: Function {} is defined twice",
+			function.line,
+			function.name
+		);
+	}
+	match arities.get("main") {
+		Some(0) => {}
+		Some(_) => anyhow::bail!("Function main must not take parameters"),
+		None => anyhow::bail!("No main function"),
+	}
+
+	let mut generator = Generator {
+		program: Program::new(),
+		lines: source.lines().collect(),
+		arities,
+		strings: HashMap::new(),
+		function: String::new(),
+		labels: 0,
+		variables: Vec::new(),
+		depth: 0,
+	};
+	generator.program.add_call_label("main");
+	generator.program.add_halt();
+	for function in functions {
+		generator.function(function)?;
+	}
+	Ok(generator.program)
+}
+
+/// Code generation state.
+struct Generator<'a> {
+	/// Program being generated.
+	program: Program,
+	/// Source lines, attached to the generated instructions.
+	lines: Vec<&'a str>,
+	/// Number of parameters of the functions.
+	arities: HashMap<&'a str, usize>,
+	/// Memory addresses of the string literals in the data section.
+	strings: HashMap<String, VmPtr>,
+	/// Name of the current function, used as prefix of its labels.
+	function: String,
+	/// Counter for unique labels in the current function.
+	labels: usize,
+	/// Variables in scope with the stack slot they are stored in. Later ones
+	/// shadow earlier ones.
+	variables: Vec<(String, i64)>,
+	/// Number of words pushed since the function was entered, i.e. the stack
+	/// slot of the last pushed word. The return address is at slot 0,
+	/// parameters have negative slots.
+	depth: i64,
+}
+
+impl Generator<'_> {
+	/// Generate a function, which is labelled with its name.
+	fn function(&mut self, function: &Function) -> anyhow::Result<()> {
+		let start = self.program.add_label(function.name.clone())?;
+		self.function.clone_from(&function.name);
+		self.labels = 0;
+		self.depth = 0;
+		let params = function.params.len() as i64;
+		self.variables = function
+			.params
+			.iter()
+			.zip(-params..)
+			.map(|(name, slot)| (name.clone(), slot))
+			.collect();
+		self.block(&function.body)?;
+		// Return 0 when the end is reached without return statement.
+		self.add(Instruction::Set(0));
+		self.program.add_return();
+		self.attach_source(start, function.line);
+		Ok(())
+	}
+
+	/// Generate the statements of a block and drop its variables afterwards.
+	fn block(&mut self, statements: &[Statement]) -> anyhow::Result<()> {
+		let variables = self.variables.len();
+		let depth = self.depth;
+		for statement in statements {
+			let start = self.program.len();
+			self.statement(statement)?;
+			self.attach_source(start, statement.line);
+		}
+		self.variables.truncate(variables);
+		self.drop_words(self.depth - depth);
+		Ok(())
+	}
+
+	/// Generate a statement.
+	fn statement(&mut self, statement: &Statement) -> anyhow::Result<()> {
+		let line = statement.line;
+		match &statement.kind {
+			StatementKind::Let(name, value) => {
+				self.expression(value)?;
+				self.push();
+				self.variables.push((name.clone(), self.depth));
+			}
+			StatementKind::Assign(name, value) => {
+				self.expression(value)?;
+				let offset = self.offset(name, line)?;
+				// Keep the value in r1 while computing the address in r0.
+				self.add(Instruction::Swap(1));
+				self.stack_address(offset);
+				self.add(Instruction::Swap(0));
+				self.add(Instruction::Swap(1));
+				self.add(Instruction::Write32(0));
+			}
+			StatementKind::If(condition, then, otherwise) => {
+				let else_label = self.label("else");
+				let end_label = self.label("end_if");
+				self.expression(condition)?;
+				self.jump_if_zero(&else_label);
+				self.block(then)?;
+				if otherwise.is_empty() {
+					self.program.add_label(else_label)?;
+				} else {
+					self.program.add_jump_label(&end_label);
+					self.program.add_label(else_label)?;
+					self.block(otherwise)?;
+				}
+				self.program.add_label(end_label)?;
+			}
+			StatementKind::While(condition, body) => {
+				let loop_label = self.label("while");
+				let end_label = self.label("end_while");
+				self.program.add_label(loop_label.clone())?;
+				self.expression(condition)?;
+				self.jump_if_zero(&end_label);
+				self.block(body)?;
+				self.program.add_jump_label(&loop_label);
+				self.program.add_label(end_label)?;
+			}
+			StatementKind::Return(value) => {
+				match value {
+					Some(value) => self.expression(value)?,
+					None => {
+						self.add(Instruction::Set(0));
+					}
+				}
+				// Drop the variables without changing the tracked depth, as the
+				// code after a return in the same block still has them.
+				let depth = self.depth;
+				self.drop_words(depth);
+				self.depth = depth;
+				self.program.add_return();
+			}
+			StatementKind::Expr(value) => self.expression(value)?,
+		}
+		Ok(())
+	}
+
+	/// Generate code that evaluates the expression into the main register.
+	fn expression(&mut self, expr: &Expr) -> anyhow::Result<()> {
+		match expr {
+			Expr::Number(value) => {
+				self.add(Instruction::Set(*value));
+			}
+			Expr::Str(text) => {
+				let addr = match self.strings.get(text) {
+					Some(addr) => *addr,
+					None => {
+						let mut bytes = text.clone().into_bytes();
+						bytes.push(0);
+						let addr = self.program.add_static_data(bytes)?;
+						self.strings.insert(text.clone(), addr);
+						addr
+					}
+				};
+				self.add(Instruction::Set(addr));
+			}
+			Expr::Variable(name, line) => {
+				let offset = self.offset(name, *line)?;
+				self.stack_address(offset);
+				self.add(Instruction::Swap(0));
+				self.add(Instruction::Deref32(0));
+			}
+			Expr::Unary(UnaryOp::Negate, value) => {
+				self.expression(value)?;
+				self.add(Instruction::Swap(1));
+				self.add(Instruction::Set(0));
+				self.add(Instruction::Sub(1));
+			}
+			Expr::Unary(UnaryOp::Not, value) => {
+				self.expression(value)?;
+				let zero_label = self.label("not_zero");
+				let end_label = self.label("end_not");
+				self.test_zero();
+				self.program.add_jump_zero_label(&zero_label);
+				self.bool_result(&zero_label, &end_label)?;
+			}
+			Expr::Binary(op @ (BinaryOp::And | BinaryOp::Or), lhs, rhs) => {
+				// Short circuit: the rhs is only evaluated if the lhs does not
+				// decide the result.
+				let decided_label = self.label("decided");
+				let end_label = self.label("end_logic");
+				for value in [lhs, rhs] {
+					self.expression(value)?;
+					self.test_zero();
+					match op {
+						BinaryOp::And => self.program.add_jump_zero_label(&decided_label),
+						_ => self.program.add_jump_nonzero_label(&decided_label),
+					};
+				}
+				self.add(Instruction::Set((*op == BinaryOp::And).into()));
+				self.program.add_jump_label(&end_label);
+				self.program.add_label(decided_label)?;
+				self.add(Instruction::Set((*op == BinaryOp::Or).into()));
+				self.program.add_label(end_label)?;
+			}
+			Expr::Binary(op, lhs, rhs) => {
+				self.expression(lhs)?;
+				self.push();
+				self.expression(rhs)?;
+				self.add(Instruction::Swap(1));
+				self.pop();
+				self.binary(*op)?;
+			}
+			Expr::Call(name, args, line) => {
+				if let Some((_, arity, syscall)) =
+					BUILTINS.iter().find(|(builtin, ..)| builtin == name)
+				{
+					anyhow::ensure!(
+						args.len() == *arity,
+						"line {line}: {name} takes {arity} arguments, but {} were given",
+						args.len()
+					);
+					self.expression(&args[0])?;
+					self.program.add_syscall(*syscall);
+					return Ok(());
+				}
+				let arity = *self
+					.arities
+					.get(name.as_str())
+					.with_context(|| format!("line {line}: Unknown function {name}"))?;
+				anyhow::ensure!(
+					args.len() == arity,
+					"line {line}: {name} takes {arity} arguments, but {} were given",
+					args.len()
+				);
+				for arg in args {
+					self.expression(arg)?;
+					self.push();
+				}
+				self.program.add_call_label(name);
+				self.drop_words(arity as i64);
+			}
+		}
+		Ok(())
+	}
+
+	/// Generate a binary operation of the main register (lhs) and r1 (rhs)
+	/// into the main register.
+	fn binary(&mut self, op: BinaryOp) -> anyhow::Result<()> {
+		match op {
+			BinaryOp::Add => {
+				self.add(Instruction::Add(1));
+			}
+			BinaryOp::Sub => {
+				self.add(Instruction::Sub(1));
+			}
+			BinaryOp::Mul => {
+				self.add(Instruction::Mul(1));
+			}
+			BinaryOp::Div => {
+				self.add(Instruction::Div(1));
+			}
+			BinaryOp::Rem => {
+				self.add(Instruction::Div(1));
+				self.add(Instruction::Swap(1));
+			}
+			BinaryOp::And | BinaryOp::Or => unreachable!("logical operators short circuit"),
+			comparison => {
+				let true_label = self.label("true");
+				let end_label = self.label("end_compare");
+				self.add(Instruction::Compare(1));
+				match comparison {
+					BinaryOp::Equal => self.program.add_jump_equal_label(&true_label),
+					BinaryOp::NotEqual => self.program.add_jump_not_equal_label(&true_label),
+					BinaryOp::Less => self.program.add_jump_less_label(&true_label),
+					BinaryOp::LessEqual => self.program.add_jump_less_equal_label(&true_label),
+					BinaryOp::Greater => self.program.add_jump_greater_label(&true_label),
+					_ => self.program.add_jump_greater_equal_label(&true_label),
+				};
+				self.bool_result(&true_label, &end_label)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Set the main register to 0 when falling through and to 1 when
+	/// jumping to the true label.
+	fn bool_result(&mut self, true_label: &str, end_label: &str) -> anyhow::Result<()> {
+		self.add(Instruction::Set(0));
+		self.program.add_jump_label(end_label);
+		self.program.add_label(true_label)?;
+		self.add(Instruction::Set(1));
+		self.program.add_label(end_label)?;
+		Ok(())
+	}
+
+	/// Set the zero flag to whether the main register is zero.
+	fn test_zero(&mut self) {
+		self.add(Instruction::Increment);
+		self.add(Instruction::Decrement);
+	}
+
+	/// Jump to the label if the main register is zero.
+	fn jump_if_zero(&mut self, label: &str) {
+		self.test_zero();
+		self.program.add_jump_zero_label(label);
+	}
+
+	/// Byte offset of the variable from the stack pointer.
+	fn offset(&self, name: &str, line: usize) -> anyhow::Result<VmPtr> {
+		let (_, slot) = self
+			.variables
+			.iter()
+			.rev()
+			.find(|(variable, _)| variable == name)
+			.with_context(|| format!("line {line}: Unknown variable {name}"))?;
+		let words = VmPtr::try_from(self.depth - slot).context("Stack frame too large")?;
+		words.checked_mul(WORD).context("Stack frame too large")
+	}
+
+	/// Compute the stack pointer plus the offset into the main register.
+	/// Overwrites r0.
+	fn stack_address(&mut self, offset: VmPtr) {
+		self.add(Instruction::ReadStackPointer);
+		if offset != 0 {
+			self.add(Instruction::SetRegister(0, offset));
+			self.add(Instruction::Add(0));
+		}
+	}
+
+	/// Push the main register.
+	fn push(&mut self) {
+		self.add(Instruction::Push);
+		self.depth += 1;
+	}
+
+	/// Pop into the main register.
+	fn pop(&mut self) {
+		self.add(Instruction::Pop);
+		self.depth -= 1;
+	}
+
+	/// Drop words from the stack, keeping the main register.
+	fn drop_words(&mut self, words: i64) {
+		if words <= 2 {
+			for _ in 0..words {
+				self.add(Instruction::PopRegister(0));
+			}
+		} else {
+			self.add(Instruction::Swap(1));
+			self.stack_address(words as VmPtr * WORD);
+			self.add(Instruction::WriteStackPointer);
+			self.add(Instruction::Swap(1));
+		}
+		self.depth -= words;
+	}
+
+	/// Create a unique label in the current function. Labels of the
+	/// high-level language cannot contain dots, so they do not collide.
+	fn label(&mut self, kind: &str) -> String {
+		self.labels += 1;
+		format!("{}.{kind}.{}", self.function, self.labels)
+	}
+
+	/// Add an instruction.
+	fn add(&mut self, instruction: Instruction) -> usize {
+		self.program.add_instruction(instruction)
+	}
+
+	/// Attach the source line to the instructions from `start` on that do not
+	/// have one yet.
+	fn attach_source(&mut self, start: usize, line: usize) {
+		let text = self.lines.get(line - 1).map_or("", |text| text.trim()).to_owned();
+		for index in start..self.program.len() {
+			if self.program.source(index).is_none() {
+				self.program.set_source(index, SourceLine { file: None, line, text: text.clone() });
+			}
+		}
+	}
+}
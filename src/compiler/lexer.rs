@@ -0,0 +1,111 @@
+//! Tokenizer of the high-level language.
+
+use anyhow::Context;
+
+use crate::VmPtr;
+
+/// Token of the high-level language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Token {
+	/// Identifier or keyword.
+	Ident(String),
+	/// Number or character literal.
+	Number(VmPtr),
+	/// String literal, with escapes resolved.
+	Str(String),
+	/// Operator or punctuation.
+	Punct(&'static str),
+}
+
+/// Operators and punctuation, longer ones first so they are matched first.
+const PUNCTUATION: [&str; 21] = [
+	"==", "!=", "<=", ">=", "&&", "||", "(", ")", "{", "}", ",", ";", "=", "+", "-", "*", "/", "%",
+	"<", ">", "!",
+];
+
+/// Split the source into tokens with their line numbers.
+pub(super) fn tokenize(source: &str) -> anyhow::Result<Vec<(Token, usize)>> {
+	let mut tokens = Vec::new();
+	for (line_index, line) in source.lines().enumerate() {
+		let line_number = line_index + 1;
+		tokenize_line(line, line_number, &mut tokens)
+			.with_context(|| format!("line {line_number}"))?;
+	}
+	Ok(tokens)
+}
+
+/// Tokenize a single line, appending to the tokens.
+fn tokenize_line(
+	line: &str,
+	line_number: usize,
+	tokens: &mut Vec<(Token, usize)>,
+) -> anyhow::Result<()> {
+	let mut rest = line;
+	loop {
+		rest = rest.trim_start();
+		if rest.is_empty() || rest.starts_with("//") {
+			return Ok(());
+		}
+		let c = rest.chars().next().expect("rest is not empty");
+		let token = if c.is_ascii_alphabetic() || c == '_' {
+			let end =
+				rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+			let (ident, tail) = rest.split_at(end);
+			rest = tail;
+			Token::Ident(ident.to_owned())
+		} else if c.is_ascii_digit() {
+			let end = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+			let (number, tail) = rest.split_at(end);
+			rest = tail;
+			let value = match number.strip_prefix("0x") {
+				Some(hex) => VmPtr::from_str_radix(hex, 16),
+				None => number.parse(),
+			};
+			Token::Number(value.with_context(|| format!("Invalid number {number}"))?)
+		} else if c == '"' {
+			let (text, tail) = literal(&rest[1..], '"')?;
+			rest = tail;
+			Token::Str(text)
+		} else if c == '\'' {
+			let (text, tail) = literal(&rest[1..], '\'')?;
+			rest = tail;
+			let mut chars = text.chars();
+			let value = chars
+				.next()
+				.filter(|_| chars.next().is_none())
+				.context("Character literals must contain exactly one character")?;
+			Token::Number(VmPtr::from(value))
+		} else {
+			let punct = PUNCTUATION
+				.into_iter()
+				.find(|punct| rest.starts_with(punct))
+				.with_context(|| format!("Unexpected character {c:?}"))?;
+			rest = &rest[punct.len()..];
+			Token::Punct(punct)
+		};
+		tokens.push((token, line_number));
+	}
+}
+
+/// Read a string or character literal up to the closing quote, resolving
+/// escapes. Return the text and the rest after the closing quote.
+fn literal(input: &str, quote: char) -> anyhow::Result<(String, &str)> {
+	let mut text = String::new();
+	let mut chars = input.char_indices();
+	while let Some((index, c)) = chars.next() {
+		match c {
+			c if c == quote => return Ok((text, &input[index + 1..])),
+			'\\' => {
+				let (_, escaped) = chars.next().context("Unterminated literal")?;
+				text.push(match escaped {
+					'n' => '\n',
+					't' => '\t',
+					'\\' | '"' | '\'' => escaped,
+					_ => anyhow::bail!("Unknown escape sequence \\{escaped}"),
+				});
+			}
+			c => text.push(c),
+		}
+	}
+	anyhow::bail!("Unterminated literal")
+}
@@ -0,0 +1,351 @@
+//! Recursive descent parser of the high-level language into a syntax tree.
+
+use anyhow::Context;
+
+use super::lexer::Token;
+use crate::VmPtr;
+
+/// Function definition.
+#[derive(Debug, Clone)]
+pub(super) struct Function {
+	/// Name of the function, also used as its label.
+	pub name: String,
+	/// Parameter names.
+	pub params: Vec<String>,
+	/// Statements of the function body.
+	pub body: Vec<Statement>,
+	/// Line of the definition.
+	pub line: usize,
+}
+
+/// Statement with the line it starts on.
+#[derive(Debug, Clone)]
+pub(super) struct Statement {
+	/// Kind of statement.
+	pub kind: StatementKind,
+	/// Line number, starting at 1.
+	pub line: usize,
+}
+
+/// Kind of statement.
+#[derive(Debug, Clone)]
+pub(super) enum StatementKind {
+	/// `let <name> = <expr>;`
+	Let(String, Expr),
+	/// `<name> = <expr>;`
+	Assign(String, Expr),
+	/// `if (<cond>) { .. } else { .. }`, where `else if` is nested in the else
+	/// branch.
+	If(Expr, Vec<Statement>, Vec<Statement>),
+	/// `while (<cond>) { .. }`
+	While(Expr, Vec<Statement>),
+	/// `return <expr>;` or `return;`
+	Return(Option<Expr>),
+	/// Expression evaluated for its side effects, e.g. a call.
+	Expr(Expr),
+}
+
+/// Expression.
+#[derive(Debug, Clone)]
+pub(super) enum Expr {
+	/// Number or character literal.
+	Number(VmPtr),
+	/// String literal, evaluating to the address of the string.
+	Str(String),
+	/// Variable or parameter with the line it is used on.
+	Variable(String, usize),
+	/// Unary operation.
+	Unary(UnaryOp, Box<Expr>),
+	/// Binary operation.
+	Binary(BinaryOp, Box<Expr>, Box<Expr>),
+	/// Call of a function or builtin with the line it is called on.
+	Call(String, Vec<Expr>, usize),
+}
+
+/// Unary operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum UnaryOp {
+	/// `-`
+	Negate,
+	/// `!`
+	Not,
+}
+
+/// Binary operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BinaryOp {
+	/// `+`
+	Add,
+	/// `-`
+	Sub,
+	/// `*`
+	Mul,
+	/// `/`
+	Div,
+	/// `%`
+	Rem,
+	/// `==`
+	Equal,
+	/// `!=`
+	NotEqual,
+	/// `<`
+	Less,
+	/// `<=`
+	LessEqual,
+	/// `>`
+	Greater,
+	/// `>=`
+	GreaterEqual,
+	/// `&&`
+	And,
+	/// `||`
+	Or,
+}
+
+/// Binary operators by precedence level, lowest first.
+const PRECEDENCE: [&[(&str, BinaryOp)]; 5] = [
+	&[("||", BinaryOp::Or)],
+	&[("&&", BinaryOp::And)],
+	&[
+		("==", BinaryOp::Equal),
+		("!=", BinaryOp::NotEqual),
+		("<", BinaryOp::Less),
+		("<=", BinaryOp::LessEqual),
+		(">", BinaryOp::Greater),
+		(">=", BinaryOp::GreaterEqual),
+	],
+	&[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+	&[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+];
+
+/// Keywords, which cannot be used as names.
+const KEYWORDS: [&str; 6] = ["fn", "let", "if", "else", "while", "return"];
+
+/// Parser state over the tokens.
+struct Parser {
+	/// Tokens with their line numbers.
+	tokens: Vec<(Token, usize)>,
+	/// Index of the next token.
+	position: usize,
+}
+
+/// Parse the tokens into function definitions.
+pub(super) fn parse(tokens: Vec<(Token, usize)>) -> anyhow::Result<Vec<Function>> {
+	let mut parser = Parser { tokens, position: 0 };
+	let mut functions = Vec::new();
+	while parser.peek().is_some() {
+		functions.push(parser.function()?);
+	}
+	Ok(functions)
+}
+
+impl Parser {
+	/// The next token, if any.
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.position).map(|(token, _)| token)
+	}
+
+	/// Line of the next token, or of the last token at the end.
+	fn line(&self) -> usize {
+		self.tokens.get(self.position).or(self.tokens.last()).map_or(1, |(_, line)| *line)
+	}
+
+	/// Consume the next token.
+	fn next(&mut self) -> anyhow::Result<Token> {
+		let (token, _) = self
+			.tokens
+			.get(self.position)
+			.cloned()
+			.with_context(|| format!("line {}: Unexpected end of input", self.line()))?;
+		self.position += 1;
+		Ok(token)
+	}
+
+	/// Consume the next token if it is the given punctuation or keyword.
+	fn eat(&mut self, expected: &str) -> bool {
+		let matches = match self.peek() {
+			Some(Token::Punct(punct)) => *punct == expected,
+			Some(Token::Ident(ident)) => ident == expected,
+			_ => false,
+		};
+		if matches {
+			self.position += 1;
+		}
+		matches
+	}
+
+	/// Consume the given punctuation or keyword or fail.
+	fn expect(&mut self, expected: &str) -> anyhow::Result<()> {
+		let line = self.line();
+		anyhow::ensure!(self.eat(expected), "line {line}: Expected `{expected}`");
+		Ok(())
+	}
+
+	/// Consume a name, which must not be a keyword.
+	fn name(&mut self) -> anyhow::Result<String> {
+		let line = self.line();
+		match self.next()? {
+			Token::Ident(name) if !KEYWORDS.contains(&name.as_str()) => Ok(name),
+			token => anyhow::bail!("line {line}: Expected a name, found {token:?}"),
+		}
+	}
+
+	/// `fn <name>(<params>) { .. }`
+	fn function(&mut self) -> anyhow::Result<Function> {
+		let line = self.line();
+		self.expect("fn")?;
+		let name = self.name()?;
+		self.expect("(")?;
+		let mut params = Vec::new();
+		if !self.eat(")") {
+			loop {
+				params.push(self.name()?);
+				if self.eat(")") {
+					break;
+				}
+				self.expect(",")?;
+			}
+		}
+		let body = self.block()?;
+		Ok(Function { name, params, body, line })
+	}
+
+	/// `{ <statements> }`
+	fn block(&mut self) -> anyhow::Result<Vec<Statement>> {
+		self.expect("{")?;
+		let mut statements = Vec::new();
+		while !self.eat("}") {
+			statements.push(self.statement()?);
+		}
+		Ok(statements)
+	}
+
+	/// Parse a statement.
+	fn statement(&mut self) -> anyhow::Result<Statement> {
+		let line = self.line();
+		let kind = if self.eat("let") {
+			let name = self.name()?;
+			self.expect("=")?;
+			let value = self.expression()?;
+			self.expect(";")?;
+			StatementKind::Let(name, value)
+		} else if self.eat("if") {
+			self.if_statement()?
+		} else if self.eat("while") {
+			let condition = self.condition()?;
+			StatementKind::While(condition, self.block()?)
+		} else if self.eat("return") {
+			let value = if self.eat(";") {
+				None
+			} else {
+				let value = self.expression()?;
+				self.expect(";")?;
+				Some(value)
+			};
+			StatementKind::Return(value)
+		} else if matches!(self.peek(), Some(Token::Ident(_)))
+			&& matches!(self.tokens.get(self.position + 1), Some((Token::Punct("="), _)))
+		{
+			let name = self.name()?;
+			self.expect("=")?;
+			let value = self.expression()?;
+			self.expect(";")?;
+			StatementKind::Assign(name, value)
+		} else {
+			let value = self.expression()?;
+			self.expect(";")?;
+			StatementKind::Expr(value)
+		};
+		Ok(Statement { kind, line })
+	}
+
+	/// The rest of an if statement after `if`.
+	fn if_statement(&mut self) -> anyhow::Result<StatementKind> {
+		let condition = self.condition()?;
+		let then = self.block()?;
+		let otherwise = if !self.eat("else") {
+			Vec::new()
+		} else if self.peek() == Some(&Token::Ident("if".to_owned())) {
+			let line = self.line();
+			self.position += 1;
+			vec![Statement { kind: self.if_statement()?, line }]
+		} else {
+			self.block()?
+		};
+		Ok(StatementKind::If(condition, then, otherwise))
+	}
+
+	/// `(<expr>)` of if and while.
+	fn condition(&mut self) -> anyhow::Result<Expr> {
+		self.expect("(")?;
+		let condition = self.expression()?;
+		self.expect(")")?;
+		Ok(condition)
+	}
+
+	/// Parse an expression.
+	fn expression(&mut self) -> anyhow::Result<Expr> {
+		self.binary(0)
+	}
+
+	/// Parse a left-associative chain of binary operators of the given
+	/// precedence level or higher.
+	fn binary(&mut self, level: usize) -> anyhow::Result<Expr> {
+		let Some(operators) = PRECEDENCE.get(level) else {
+			return self.unary();
+		};
+		let mut lhs = self.binary(level + 1)?;
+		'chain: loop {
+			for (punct, op) in operators.iter() {
+				if self.eat(punct) {
+					let rhs = self.binary(level + 1)?;
+					lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+					continue 'chain;
+				}
+			}
+			return Ok(lhs);
+		}
+	}
+
+	/// Parse a unary operation or a primary expression.
+	fn unary(&mut self) -> anyhow::Result<Expr> {
+		if self.eat("-") {
+			Ok(Expr::Unary(UnaryOp::Negate, Box::new(self.unary()?)))
+		} else if self.eat("!") {
+			Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+		} else {
+			self.primary()
+		}
+	}
+
+	/// Parse a literal, variable, call or parenthesized expression.
+	fn primary(&mut self) -> anyhow::Result<Expr> {
+		let line = self.line();
+		match self.next()? {
+			Token::Number(value) => Ok(Expr::Number(value)),
+			Token::Str(text) => Ok(Expr::Str(text)),
+			Token::Punct("(") => {
+				let value = self.expression()?;
+				self.expect(")")?;
+				Ok(value)
+			}
+			Token::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+				if !self.eat("(") {
+					return Ok(Expr::Variable(name, line));
+				}
+				let mut args = Vec::new();
+				if !self.eat(")") {
+					loop {
+						args.push(self.expression()?);
+						if self.eat(")") {
+							break;
+						}
+						self.expect(",")?;
+					}
+				}
+				Ok(Expr::Call(name, args, line))
+			}
+			token => anyhow::bail!("line {line}: Expected an expression, found {token:?}"),
+		}
+	}
+}
@@ -0,0 +1,110 @@
+//! Code coverage of programs, from the execution counts of machines.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::Context;
+
+use crate::{Instruction, Machine, Program, VmPtr};
+
+/// Code coverage: how often the instruction at each code address was executed,
+/// possibly accumulated over several runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+	/// Number of executions by code address.
+	counts: BTreeMap<VmPtr, u64>,
+}
+
+impl Coverage {
+	/// Create empty coverage.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Collect the coverage of a machine that counted executions, see
+	/// [`Machine::with_execution_counts`].
+	pub fn from_machine<const SIDE_REGS: usize>(
+		machine: &Machine<SIDE_REGS>,
+	) -> anyhow::Result<Self> {
+		let counts = machine
+			.execution_counts()
+			.context("Execution counts are not enabled on the machine")?
+			.clone();
+		Ok(Self { counts })
+	}
+
+	/// Add the coverage of another run of the same program.
+	pub fn merge(&mut self, other: &Coverage) {
+		for (addr, count) in &other.counts {
+			*self.counts.entry(*addr).or_default() += count;
+		}
+	}
+
+	/// Number of executions of the instruction at the code address.
+	pub fn hits(&self, addr: VmPtr) -> u64 {
+		self.counts.get(&addr).copied().unwrap_or_default()
+	}
+
+	/// Number of executed and total number of instructions of the program,
+	/// not counting data segments.
+	pub fn covered_instructions(&self, program: &Program) -> (usize, usize) {
+		let code = program
+			.iter()
+			.filter(|(_, instruction)| !matches!(instruction, Instruction::Data(_, _)));
+		code.fold((0, 0), |(covered, total), (addr, _)| {
+			(covered + usize::from(self.hits(addr) > 0), total + 1)
+		})
+	}
+
+	/// Labels of the program, whose instruction was never executed. Labels of
+	/// data segments are not included.
+	pub fn uncovered_labels(&self, program: &Program) -> Vec<String> {
+		let code = program
+			.iter()
+			.filter(|(_, instruction)| !matches!(instruction, Instruction::Data(_, _)))
+			.map(|(addr, _)| addr)
+			.collect::<Vec<_>>();
+		program
+			.symbols()
+			.iter()
+			.filter(|(_, addr)| code.contains(addr) && self.hits(*addr) == 0)
+			.map(|(name, _)| name.to_owned())
+			.collect()
+	}
+
+	/// Annotate the listing of the program with the execution count of every
+	/// instruction, followed by a summary and the uncovered labels. Never
+	/// executed instructions are marked with `#####`, data segments have no
+	/// count.
+	pub fn report(&self, program: &Program) -> String {
+		let symbols = program.symbols();
+		let mut report = String::new();
+		for (index, (addr, instruction)) in program.iter().enumerate() {
+			for name in symbols.names_at(addr) {
+				writeln!(report, "{:>8}  {:8}         label {name}", "", "")
+					.expect("writing to String cannot fail");
+			}
+			let hits = match (instruction, self.hits(addr)) {
+				(Instruction::Data(_, _), _) => String::new(),
+				(_, 0) => "#####".to_owned(),
+				(_, hits) => hits.to_string(),
+			};
+			let text = match program.source(index) {
+				Some(source) => source.to_string(),
+				None => format!("       {instruction}"),
+			};
+			writeln!(report, "{hits:>8}  {addr:08x}  {text}")
+				.expect("writing to String cannot fail");
+		}
+
+		let (covered, total) = self.covered_instructions(program);
+		let percent = covered as f64 * 100.0 / total.max(1) as f64;
+		writeln!(report, "\nCovered {covered} of {total} instructions ({percent:.2}%)")
+			.expect("writing to String cannot fail");
+		let uncovered = self.uncovered_labels(program);
+		if !uncovered.is_empty() {
+			writeln!(report, "Uncovered labels: {}", uncovered.join(", "))
+				.expect("writing to String cannot fail");
+		}
+		report
+	}
+}
@@ -0,0 +1,136 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::Context;
+
+use crate::{SymbolTable, VmPtr};
+
+/// Source line of an instruction in the source file or in a file it included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Location {
+	/// Included file, `None` for the source file itself.
+	file: Option<String>,
+	/// Line number, starting at 1.
+	line: usize,
+}
+
+/// Debug information of a program: maps code addresses to source lines and
+/// labels.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+	/// Name of the source file.
+	file: String,
+	/// Source location by start address of the instruction, `None` for
+	/// instructions that were not parsed from assembly.
+	lines: BTreeMap<VmPtr, Option<Location>>,
+	/// Labels of the program.
+	symbols: SymbolTable,
+}
+
+impl DebugInfo {
+	/// Create new empty debug information for the given source file name.
+	pub fn new(file: impl Into<String>) -> Self {
+		Self { file: file.into(), ..Self::default() }
+	}
+
+	/// Name of the source file.
+	pub fn file(&self) -> &str {
+		&self.file
+	}
+
+	/// Record the source line of the instruction starting at the given code
+	/// address.
+	pub fn insert_line(&mut self, addr: VmPtr, line: usize) {
+		self.lines.insert(addr, Some(Location { file: None, line }));
+	}
+
+	/// Record the source line in an included file of the instruction starting
+	/// at the given code address.
+	pub fn insert_included_line(&mut self, addr: VmPtr, file: impl Into<String>, line: usize) {
+		self.lines.insert(addr, Some(Location { file: Some(file.into()), line }));
+	}
+
+	/// Record that the instruction starting at the given code address has no
+	/// source line, so that it is not attributed to the line before it.
+	pub fn insert_unknown(&mut self, addr: VmPtr) {
+		self.lines.insert(addr, None);
+	}
+
+	/// Labels of the program.
+	pub fn symbols(&self) -> &SymbolTable {
+		&self.symbols
+	}
+
+	/// Set the labels of the program.
+	pub fn set_symbols(&mut self, symbols: SymbolTable) {
+		self.symbols = symbols;
+	}
+
+	/// Get the file name and source line of the instruction at or containing
+	/// the given code address. The file is either the source file or a file it
+	/// included.
+	pub fn location(&self, addr: VmPtr) -> Option<(&str, usize)> {
+		let location = self.lines.range(..=addr).next_back()?.1.as_ref()?;
+		Some((location.file.as_deref().unwrap_or(&self.file), location.line))
+	}
+
+	/// Describe the code address in human readable form, e.g.
+	/// `program.asm:42 (label foo)`.
+	pub fn describe(&self, addr: VmPtr) -> String {
+		let mut description = match self.location(addr) {
+			Some((file, line)) => format!("{file}:{line}"),
+			None => format!("{} at code address {addr}", self.file),
+		};
+		if let Some((label, _offset)) = self.symbols.lookup(addr) {
+			description.push_str(&format!(" (label {label})"));
+		}
+		description
+	}
+
+	/// Serialize the file name and the line mapping to a simple text format:
+	/// `file <name>` on the first line, followed by one line per instruction
+	/// with the hexadecimal address and the line number, followed by the file
+	/// name for lines in included files, or `-` for instructions without
+	/// source line. Symbols are not included, see [`SymbolTable::to_sidecar`].
+	pub fn to_sidecar(&self) -> String {
+		let mut output = format!("file {}\n", self.file);
+		for (addr, location) in &self.lines {
+			match location {
+				Some(Location { file: None, line }) => writeln!(output, "{addr:08x} {line}"),
+				Some(Location { file: Some(file), line }) => {
+					writeln!(output, "{addr:08x} {line} {file}")
+				}
+				None => writeln!(output, "{addr:08x} -"),
+			}
+			.expect("writing to String cannot fail");
+		}
+		output
+	}
+
+	/// Parse the format written by [`Self::to_sidecar`].
+	pub fn from_sidecar(input: &str) -> anyhow::Result<Self> {
+		let mut lines = input.lines();
+		let file = lines
+			.next()
+			.and_then(|line| line.strip_prefix("file "))
+			.context("Debug info is missing the file name")?;
+		let mut debug_info = Self::new(file);
+		for (line_number, line) in lines.enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+			let parse = || -> Option<(VmPtr, Option<Location>)> {
+				let mut parts = line.trim().splitn(3, ' ');
+				let addr = VmPtr::from_str_radix(parts.next()?, 16).ok()?;
+				let location = match parts.next()? {
+					"-" => None,
+					line => Some(Location {
+						file: parts.next().map(str::to_owned),
+						line: line.parse().ok()?,
+					}),
+				};
+				Some((addr, location))
+			};
+			let (addr, location) = parse()
+				.with_context(|| format!("Invalid debug info at line {}", line_number + 2))?;
+			debug_info.lines.insert(addr, location);
+		}
+		Ok(debug_info)
+	}
+}
@@ -0,0 +1,283 @@
+//! Interactive debugger on top of the machine's step and breakpoint APIs.
+
+use std::{collections::BTreeSet, fmt::Write};
+
+use anyhow::{bail, Context};
+
+use crate::{
+	util::{hexdump_line, native_ptr, parse_value, read_vm_ptr, vm_ptr},
+	Instruction, Machine, StopReason, VmPtr,
+};
+
+/// Help text listing the debugger commands.
+const HELP: &str = "\
+step [n]            execute the next n instructions (default 1), alias s
+continue            run until a breakpoint or halt, alias c
+break <loc>         set a breakpoint at a label or code address, alias b
+delete <loc>        remove a breakpoint
+breakpoints         list all breakpoints
+registers           print registers and flags, alias r
+memory <loc> [len]  print len bytes of memory (default 64), alias x
+stack [n]           print the top n words of the stack (default 8)
+backtrace           print the return addresses on the stack, alias bt
+list                print the instructions around the current one, alias l
+where               print the current location and instruction, alias w
+help                print this help";
+
+/// Debugger driving a machine with textual commands, e.g. for a REPL.
+#[derive(Debug)]
+pub struct Debugger<const SIDE_REGS: usize> {
+	/// The machine being debugged.
+	machine: Machine<SIDE_REGS>,
+	/// Whether the machine has halted.
+	halted: bool,
+}
+
+impl<const SIDE_REGS: usize> Debugger<SIDE_REGS> {
+	/// Create a debugger for the machine.
+	pub fn new(machine: Machine<SIDE_REGS>) -> Self {
+		Self { machine, halted: false }
+	}
+
+	/// The machine being debugged.
+	pub fn machine(&self) -> &Machine<SIDE_REGS> {
+		&self.machine
+	}
+
+	/// The machine being debugged, mutably.
+	pub fn machine_mut(&mut self) -> &mut Machine<SIDE_REGS> {
+		&mut self.machine
+	}
+
+	/// Whether the machine has halted.
+	pub fn is_halted(&self) -> bool {
+		self.halted
+	}
+
+	/// Execute a debugger command and return its output. Empty commands do
+	/// nothing.
+	pub fn execute(&mut self, command: &str) -> anyhow::Result<String> {
+		let mut words = command.split_whitespace();
+		let Some(name) = words.next() else {
+			return Ok(String::new());
+		};
+		let arguments: Vec<&str> = words.collect();
+		match (name, arguments.as_slice()) {
+			("step" | "s", []) => self.step(1),
+			("step" | "s", [count]) => self.step(parse_value(count)?),
+			("continue" | "c", []) => self.resume(),
+			("break" | "b", [location]) => {
+				let addr = self.address(location)?;
+				self.machine.add_breakpoint(addr);
+				Ok(format!("Breakpoint at {}", self.describe(addr)))
+			}
+			("delete", [location]) => {
+				let addr = self.address(location)?;
+				if !self.machine.remove_breakpoint(addr) {
+					bail!("No breakpoint at {}", self.describe(addr));
+				}
+				Ok(format!("Removed breakpoint at {}", self.describe(addr)))
+			}
+			("breakpoints", []) => Ok(self
+				.machine
+				.breakpoints()
+				.map(|addr| self.describe(addr))
+				.collect::<Vec<_>>()
+				.join("\n")),
+			("registers" | "r", []) => Ok(self.registers()),
+			("memory" | "x", [location]) => self.hexdump(self.address(location)?, 64),
+			("memory" | "x", [location, len]) => {
+				self.hexdump(self.address(location)?, parse_value(len)?)
+			}
+			("stack", []) => Ok(self.stack(8)),
+			("stack", [count]) => Ok(self.stack(parse_value(count)?)),
+			("backtrace" | "bt", []) => Ok(self.backtrace()),
+			("list" | "l", []) => Ok(self.disassembly(5)),
+			("where" | "w", []) => Ok(self.location()),
+			("help" | "h", []) => Ok(HELP.to_owned()),
+			_ => bail!("Invalid command `{command}`, see `help`"),
+		}
+	}
+
+	/// Execute up to `count` instructions.
+	fn step(&mut self, count: VmPtr) -> anyhow::Result<String> {
+		for _ in 0..count {
+			if self.halted {
+				break;
+			}
+			self.halted = !self.machine.step()?;
+		}
+		Ok(self.location())
+	}
+
+	/// Run until a breakpoint is hit or the machine halts.
+	fn resume(&mut self) -> anyhow::Result<String> {
+		if self.halted {
+			return Ok(self.location());
+		}
+		match self.machine.run_until_break()? {
+			StopReason::Halted => self.halted = true,
+			StopReason::Breakpoint(addr) => {
+				return Ok(format!(
+					"Hit breakpoint at {}\n{}",
+					self.describe(addr),
+					self.location()
+				));
+			}
+		}
+		Ok(self.location())
+	}
+
+	/// Resolve a label or a numeric address.
+	fn address(&self, location: &str) -> anyhow::Result<VmPtr> {
+		if location.starts_with(|c: char| c.is_ascii_digit()) {
+			return parse_value(location);
+		}
+		self.machine
+			.debug_info()
+			.and_then(|debug_info| debug_info.symbols().address(location))
+			.with_context(|| format!("Unknown label `{location}`"))
+	}
+
+	/// Describe a code address with source location and label, if known.
+	fn describe(&self, addr: VmPtr) -> String {
+		match self.machine.debug_info() {
+			Some(debug_info) => format!("{addr:#010x} {}", debug_info.describe(addr)),
+			None => format!("{addr:#010x}"),
+		}
+	}
+
+	/// Describe the current location and the next instruction.
+	pub fn location(&self) -> String {
+		if self.halted {
+			return "Halted".to_owned();
+		}
+		let ip = self.machine.instruction_pointer();
+		let instruction = self
+			.machine
+			.code()
+			.get(ip as usize..)
+			.and_then(|code| Instruction::parse(code).ok())
+			.map_or_else(|| "<invalid>".to_owned(), |instruction| instruction.to_string());
+		format!("{}: {instruction}", self.describe(ip))
+	}
+
+	/// Print registers and flags.
+	pub fn registers(&self) -> String {
+		let mut output = format!(
+			"ip={:#010x} sp={:#010x} main={:#010x} zero={} cmp={:?}",
+			self.machine.instruction_pointer(),
+			self.machine.stack_pointer(),
+			self.machine.main_register(),
+			self.machine.flag_zero(),
+			self.machine.flag_comparison(),
+		);
+		for (index, value) in self.machine.side_registers().iter().enumerate() {
+			write!(output, "\nr{index}={value:#010x}").expect("writing to string");
+		}
+		output
+	}
+
+	/// Hex dump of `len` bytes of memory at the given address.
+	pub fn hexdump(&self, addr: VmPtr, len: VmPtr) -> anyhow::Result<String> {
+		let bytes = self.machine.read_memory(addr, len as usize)?;
+		let lines = bytes
+			.chunks(16)
+			.zip((addr..).step_by(16))
+			.map(|(chunk, addr)| hexdump_line(addr, chunk));
+		Ok(lines.collect::<Vec<_>>().join("\n"))
+	}
+
+	/// Print the top words of the stack, symbolicating code addresses.
+	pub fn stack(&self, count: VmPtr) -> String {
+		self.stack_words()
+			.take(count as usize)
+			.map(|(addr, value)| format!("{addr:#010x}: {value:#010x}{}", self.symbolicate(value)))
+			.collect::<Vec<_>>()
+			.join("\n")
+	}
+
+	/// Print the call stack: the current location followed by all stack words
+	/// that are return addresses, i.e. directly follow a call instruction.
+	pub fn backtrace(&self) -> String {
+		let instructions = self.instructions();
+		let return_addresses = instructions
+			.windows(2)
+			.filter(|pair| matches!(pair[0].1, Instruction::Call(_)))
+			.map(|pair| pair[1].0)
+			.collect::<BTreeSet<_>>();
+		let mut frames = vec![format!(
+			"#0 {:#010x}{}",
+			self.machine.instruction_pointer(),
+			self.symbolicate(self.machine.instruction_pointer())
+		)];
+		for (_addr, value) in self.stack_words() {
+			if return_addresses.contains(&value) {
+				frames.push(format!("#{} {value:#010x}{}", frames.len(), self.symbolicate(value)));
+			}
+		}
+		frames.join("\n")
+	}
+
+	/// Print the `context` instructions before and after the current one,
+	/// marking the current instruction with `>` and breakpoints with `*`.
+	pub fn disassembly(&self, context: usize) -> String {
+		let instructions = self.instructions();
+		let ip = self.machine.instruction_pointer();
+		let current = instructions.partition_point(|(addr, _)| *addr < ip);
+		let start = current.saturating_sub(context);
+		let end = instructions.len().min(current + context + 1);
+		let breakpoints = self.machine.breakpoints().collect::<BTreeSet<_>>();
+		let mut output = Vec::new();
+		for (addr, instruction) in &instructions[start..end] {
+			let labels = self
+				.machine
+				.debug_info()
+				.into_iter()
+				.flat_map(|debug_info| debug_info.symbols().names_at(*addr));
+			output.extend(labels.map(|label| format!("  {label}:")));
+			let marker = match (*addr == ip, breakpoints.contains(addr)) {
+				(true, true) => ">*",
+				(true, false) => "> ",
+				(false, true) => " *",
+				(false, false) => "  ",
+			};
+			output.push(format!("{marker} {addr:#010x}  {instruction}"));
+		}
+		output.join("\n")
+	}
+
+	/// Decode the code from the start, until it ends or is invalid.
+	fn instructions(&self) -> Vec<(VmPtr, Instruction)> {
+		let code = self.machine.code();
+		let mut instructions = Vec::new();
+		let mut addr = 0;
+		while let Some(instruction) =
+			code.get(addr..).and_then(|code| Instruction::parse(code).ok())
+		{
+			let size = instruction.size();
+			instructions.push((vm_ptr(addr), instruction));
+			addr += size;
+		}
+		instructions
+	}
+
+	/// Iterate over the stack words from the stack pointer to the end of
+	/// memory, with their address.
+	fn stack_words(&self) -> impl Iterator<Item = (VmPtr, VmPtr)> + '_ {
+		(self.machine.stack_pointer()..).step_by(4).map_while(|addr| {
+			let bytes = self.machine.read_memory(addr, 4).ok()?;
+			Some((addr, read_vm_ptr(bytes).ok()?))
+		})
+	}
+
+	/// Describe the value as label and offset, if it is in a labeled code
+	/// region.
+	fn symbolicate(&self, value: VmPtr) -> String {
+		self.machine
+			.debug_info()
+			.and_then(|debug_info| debug_info.symbols().lookup(value))
+			.filter(|_| native_ptr(value) < self.machine.code().len())
+			.map_or_else(String::new, |(label, offset)| format!(" ({label}+{offset})"))
+	}
+}
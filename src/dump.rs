@@ -0,0 +1,98 @@
+//! Human readable dump of the machine state.
+
+use std::fmt;
+
+use crate::{
+	util::{hexdump_line, native_ptr, read_vm_ptr, vm_ptr},
+	Instruction, Machine, VmPtr,
+};
+
+/// Number of stack words shown from the top of the stack.
+const STACK_WORDS: usize = 8;
+/// Maximum number of hexdump lines of the non-zero memory regions.
+const MEMORY_LINES: usize = 32;
+
+impl<const SIDE_REGS: usize> Machine<SIDE_REGS> {
+	/// Dump the state of the machine in human readable form, see the
+	/// [`Display`](fmt::Display) implementation.
+	pub fn dump_state(&self) -> String {
+		self.to_string()
+	}
+
+	/// Describe the code address as label and offset, if it is in a labeled
+	/// code region.
+	fn symbolicate(&self, value: VmPtr) -> String {
+		self.debug_info()
+			.and_then(|debug_info| debug_info.symbols().lookup(value))
+			.filter(|_| native_ptr(value) < self.code().len())
+			.map_or_else(String::new, |(label, offset)| format!(" ({label}+{offset})"))
+	}
+}
+
+impl<const SIDE_REGS: usize> fmt::Display for Machine<SIDE_REGS> {
+	/// Registers, flags, the decoded next instruction, the top words of the
+	/// stack and a hexdump of the non-zero memory regions, truncated to 32
+	/// lines. Runs of zero lines are shown as `*`.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let ip = self.instruction_pointer;
+		writeln!(
+			f,
+			"ip={ip:#010x}{} sp={:#010x} main={:#010x} zero={} cmp={:?}",
+			self.symbolicate(ip),
+			self.stack_pointer,
+			self.main_register,
+			self.flag_zero,
+			self.flag_comparison,
+		)?;
+		for (index, value) in self.side_registers.iter().enumerate() {
+			let separator = if index % 4 == 3 || index + 1 == SIDE_REGS { "\n" } else { " " };
+			write!(f, "r{index}={value:#010x}{separator}")?;
+		}
+
+		let code = self.program.get(native_ptr(ip)..).filter(|code| !code.is_empty());
+		match code.map(Instruction::parse) {
+			Some(Ok(instruction)) => write!(f, "Next instruction: {instruction}")?,
+			Some(Err(_)) => write!(f, "Next instruction: <invalid>")?,
+			None => write!(f, "Next instruction: <outside of the code>")?,
+		}
+		match self.debug_info() {
+			Some(debug_info) => writeln!(f, " at {}", debug_info.describe(ip))?,
+			None => writeln!(f)?,
+		}
+
+		let stack = (self.stack_pointer..)
+			.step_by(size_of::<VmPtr>())
+			.map_while(|addr| Some((addr, read_vm_ptr(self.memory(addr).ok()?).ok()?)))
+			.collect::<Vec<_>>();
+		writeln!(f, "Stack ({} words):", stack.len())?;
+		for (addr, value) in stack.iter().take(STACK_WORDS) {
+			writeln!(f, "  {addr:#010x}: {value:#010x}{}", self.symbolicate(*value))?;
+		}
+		if stack.len() > STACK_WORDS {
+			writeln!(f, "  ... {} more", stack.len() - STACK_WORDS)?;
+		}
+
+		write!(f, "Memory ({} bytes, non-zero regions):", self.memory.len())?;
+		let mut lines = 0;
+		let mut skipped = false;
+		for (index, chunk) in self.memory.chunks(16).enumerate() {
+			if chunk.iter().all(|byte| *byte == 0) {
+				skipped = true;
+				continue;
+			}
+			if lines == MEMORY_LINES {
+				return write!(f, "\n  ... truncated");
+			}
+			if skipped && lines > 0 {
+				write!(f, "\n  *")?;
+			}
+			write!(f, "\n  {}", hexdump_line(vm_ptr(index * 16), chunk))?;
+			lines += 1;
+			skipped = false;
+		}
+		if lines == 0 {
+			write!(f, "\n  all zero")?;
+		}
+		Ok(())
+	}
+}
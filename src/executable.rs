@@ -0,0 +1,251 @@
+use std::{mem::size_of, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+	util::{native_ptr, read_bytes, read_u16, read_u32, read_u8, read_vm_ptr, vm_ptr},
+	DebugInfo, Instruction, SymbolTable, VmPtr, ISA_VERSION,
+};
+
+/// Magic bytes at the start of every executable.
+pub const MAGIC: [u8; 4] = *b"MYVM";
+/// Current version of the executable format. Version 2 added compressed data
+/// sections, version 3 the instruction set version.
+pub const FORMAT_VERSION: u16 = 3;
+
+/// Size of the fixed header in bytes.
+const HEADER_SIZE: usize = 22;
+/// Size of the fixed header before version 3, which had no instruction set
+/// version.
+const HEADER_SIZE_V2: usize = 20;
+
+/// Kinds of sections in the executable.
+const SECTION_CODE: u8 = 1;
+const SECTION_SYMBOLS: u8 = 2;
+const SECTION_DEBUG_INFO: u8 = 3;
+const SECTION_DATA: u8 = 4;
+const SECTION_DATA_COMPRESSED: u8 = 5;
+
+/// Compiled program in the executable container format.
+///
+/// Layout (big endian): magic, format version (u16), required side registers
+/// (u16), memory size hint (u32, 0 if unspecified), entry point (u32), number
+/// of sections (u32), instruction set version (u16), followed by the sections,
+/// each consisting of the kind (u8), the length (u32) and the payload. The data
+/// section payload starts with its base address (u32). A compressed data
+/// section additionally stores the uncompressed length (u32) after the base
+/// address, followed by the zstd compressed data. Unknown sections are skipped
+/// when reading. Code of older instruction set versions is upgraded to the
+/// current one when reading.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Executable {
+	/// Number of side registers the program requires.
+	pub side_registers: u16,
+	/// Suggested memory size, if specified.
+	pub memory_size: Option<VmPtr>,
+	/// Code address where execution starts.
+	pub entry_point: VmPtr,
+	/// Compiled program code.
+	pub code: Vec<u8>,
+	/// Memory address the data section is loaded to.
+	pub data_base: VmPtr,
+	/// Static data, which is loaded into memory before execution.
+	pub data: Vec<u8>,
+	/// Whether to compress the data section when serializing. This is set when
+	/// reading an executable with compressed data. Requires the `compression`
+	/// feature, otherwise the data is written uncompressed.
+	pub compress_data: bool,
+	/// Labels of the program.
+	pub symbols: Option<SymbolTable>,
+	/// Mapping of code addresses to source lines.
+	pub debug_info: Option<DebugInfo>,
+}
+
+impl Executable {
+	/// Create an executable from raw bytecode without header. Execution starts
+	/// at code address 0 and there is no data or debug information.
+	pub fn from_code(code: impl Into<Vec<u8>>) -> Self {
+		Self { code: code.into(), ..Self::default() }
+	}
+
+	/// Whether the bytes start with the [`MAGIC`] bytes of an executable.
+	pub fn is_executable(bytes: &[u8]) -> bool {
+		bytes.starts_with(&MAGIC)
+	}
+
+	/// Serialize the executable to bytes.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut sections = vec![(SECTION_CODE, self.code.clone())];
+		if !self.data.is_empty() {
+			sections.push(self.data_section());
+		}
+		if let Some(symbols) = &self.symbols {
+			sections.push((SECTION_SYMBOLS, symbols.to_sidecar().into_bytes()));
+		}
+		if let Some(debug_info) = &self.debug_info {
+			sections.push((SECTION_DEBUG_INFO, debug_info.to_sidecar().into_bytes()));
+		}
+
+		let mut bytes = Vec::with_capacity(
+			HEADER_SIZE + sections.iter().map(|(_, payload)| 5 + payload.len()).sum::<usize>(),
+		);
+		bytes.extend_from_slice(&MAGIC);
+		bytes.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+		bytes.extend_from_slice(&self.side_registers.to_be_bytes());
+		bytes.extend_from_slice(&self.memory_size.unwrap_or(0).to_be_bytes());
+		bytes.extend_from_slice(&self.entry_point.to_be_bytes());
+		bytes.extend_from_slice(&vm_ptr(sections.len()).to_be_bytes());
+		bytes.extend_from_slice(&ISA_VERSION.to_be_bytes());
+		for (kind, payload) in sections {
+			bytes.push(kind);
+			bytes.extend_from_slice(&vm_ptr(payload.len()).to_be_bytes());
+			bytes.extend_from_slice(&payload);
+		}
+		bytes
+	}
+
+	/// Parse an executable from bytes.
+	pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+		let header = read_bytes(bytes, HEADER_SIZE_V2).context("Executable header is truncated")?;
+		if header[0..4] != MAGIC {
+			anyhow::bail!("Not an executable: invalid magic bytes");
+		}
+		let version = read_u16(&header[4..])?;
+		if version > FORMAT_VERSION {
+			anyhow::bail!("Unsupported executable format version {version}");
+		}
+		let mut executable = Self {
+			side_registers: read_u16(&header[6..])?,
+			memory_size: Some(read_vm_ptr(&header[8..])?).filter(|size| *size != 0),
+			entry_point: read_vm_ptr(&header[12..])?,
+			..Self::default()
+		};
+		let section_count = read_u32(&header[16..])?;
+		let (isa_version, header_size) = if version >= 3 {
+			let isa_version =
+				read_u16(&bytes[HEADER_SIZE_V2..]).context("Executable header is truncated")?;
+			(isa_version, HEADER_SIZE)
+		} else {
+			(1, HEADER_SIZE_V2)
+		};
+
+		let mut rest = &bytes[header_size..];
+		let mut symbols = None;
+		for _ in 0..section_count {
+			let kind = read_u8(rest).context("Section header is truncated")?;
+			let len = native_ptr(read_vm_ptr(&rest[1..]).context("Section header is truncated")?);
+			let payload = read_bytes(&rest[5..], len)
+				.with_context(|| format!("Section {kind} is truncated"))?;
+			match kind {
+				SECTION_CODE => executable.code = payload.to_vec(),
+				SECTION_DATA => {
+					executable.data_base =
+						read_vm_ptr(payload).context("Data section is truncated")?;
+					executable.data = payload[size_of::<VmPtr>()..].to_vec();
+				}
+				SECTION_DATA_COMPRESSED => {
+					executable.data_base =
+						read_vm_ptr(payload).context("Data section is truncated")?;
+					let len = read_vm_ptr(&payload[size_of::<VmPtr>()..])
+						.context("Data section is truncated")?;
+					let available = u64::from(VmPtr::MAX - executable.data_base) + 1;
+					anyhow::ensure!(
+						u64::from(len) <= available,
+						"Data section of {len} bytes at {} exceeds the address space",
+						executable.data_base
+					);
+					executable.data = decompress(&payload[2 * size_of::<VmPtr>()..], len)?;
+					executable.compress_data = true;
+				}
+				SECTION_SYMBOLS => {
+					let text = std::str::from_utf8(payload).context("Invalid symbols section")?;
+					symbols = Some(SymbolTable::from_sidecar(text)?);
+				}
+				SECTION_DEBUG_INFO => {
+					let text =
+						std::str::from_utf8(payload).context("Invalid debug info section")?;
+					executable.debug_info = Some(DebugInfo::from_sidecar(text)?);
+				}
+				_ => {}
+			}
+			rest = &rest[5 + len..];
+		}
+		if let (Some(debug_info), Some(symbols)) = (&mut executable.debug_info, &symbols) {
+			debug_info.set_symbols(symbols.clone());
+		}
+		executable.symbols = symbols;
+		if isa_version != ISA_VERSION {
+			executable.code = upgrade_code(&executable.code, isa_version)?;
+		}
+		Ok(executable)
+	}
+
+	/// Kind and payload of the data section, compressed if requested and
+	/// supported.
+	fn data_section(&self) -> (u8, Vec<u8>) {
+		let mut payload = self.data_base.to_be_bytes().to_vec();
+		#[cfg(feature = "compression")]
+		if self.compress_data {
+			payload.extend_from_slice(&vm_ptr(self.data.len()).to_be_bytes());
+			let compressed = zstd::bulk::compress(&self.data, zstd::DEFAULT_COMPRESSION_LEVEL)
+				.expect("compressing into memory cannot fail");
+			payload.extend_from_slice(&compressed);
+			return (SECTION_DATA_COMPRESSED, payload);
+		}
+		payload.extend_from_slice(&self.data);
+		(SECTION_DATA, payload)
+	}
+
+	/// Write the executable to a file.
+	pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+		let path = path.as_ref();
+		std::fs::write(path, self.to_bytes())
+			.with_context(|| format!("Cannot write executable {}", path.display()))
+	}
+
+	/// Read an executable from a file.
+	pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+		let path = path.as_ref();
+		let bytes = std::fs::read(path)
+			.with_context(|| format!("Cannot read executable {}", path.display()))?;
+		Self::from_bytes(&bytes).with_context(|| format!("Invalid executable {}", path.display()))
+	}
+}
+
+/// Decompress a compressed data section with the given uncompressed length.
+/// The output grows with the decoded data instead of allocating the untrusted
+/// length up front, and decoding stops one byte after the expected end.
+#[cfg(feature = "compression")]
+fn decompress(compressed: &[u8], len: VmPtr) -> anyhow::Result<Vec<u8>> {
+	use std::io::Read;
+
+	let mut data = Vec::new();
+	zstd::stream::read::Decoder::new(compressed)
+		.context("Invalid compressed data section")?
+		.take(u64::from(len) + 1)
+		.read_to_end(&mut data)
+		.context("Invalid compressed data section")?;
+	anyhow::ensure!(data.len() == native_ptr(len), "Compressed data section has the wrong length");
+	Ok(data)
+}
+
+/// Decompress a compressed data section, which is not supported without the
+/// `compression` feature.
+#[cfg(not(feature = "compression"))]
+fn decompress(_compressed: &[u8], _len: VmPtr) -> anyhow::Result<Vec<u8>> {
+	anyhow::bail!("Compressed data sections require the `compression` feature")
+}
+
+/// Translate code of an older instruction set version to the current one,
+/// instruction by instruction.
+fn upgrade_code(code: &[u8], isa_version: u16) -> anyhow::Result<Vec<u8>> {
+	let mut upgraded = Vec::with_capacity(code.len());
+	let mut addr = 0;
+	while addr < code.len() {
+		let instruction = Instruction::parse_versioned(&code[addr..], isa_version)
+			.with_context(|| format!("Failed decoding instruction at code address {addr}"))?;
+		addr += instruction.size();
+		upgraded.extend_from_slice(&instruction.bytes());
+	}
+	Ok(upgraded)
+}
@@ -0,0 +1,224 @@
+//! Formatter for text assembly, normalizing the layout while preserving
+//! comments and the meaning of the program.
+
+/// Indentation of one nesting level.
+const INDENT: &str = "    ";
+
+/// Canonical spelling of all keywords, by their lowercase form.
+const KEYWORDS: &[&str] = &[
+	// Instructions.
+	"nop",
+	"halt",
+	"load8",
+	"store8",
+	"load16",
+	"store16",
+	"load32",
+	"store32",
+	"set",
+	"deref8",
+	"deref16",
+	"deref32",
+	"syscall",
+	"copyCodeMemory",
+	"dataString",
+	"dataBytes",
+	"swap",
+	"write8",
+	"write16",
+	"write32",
+	"readStackPointer",
+	"writeStackPointer",
+	"jump",
+	"call",
+	"return",
+	"increment",
+	"decrement",
+	"add",
+	"sub",
+	"compare",
+	"jumpEqual",
+	"jumpNotEqual",
+	"jumpGreater",
+	"jumpLess",
+	"jumpGreaterEqual",
+	"jumpLessEqual",
+	"jumpZero",
+	"jumpNonzero",
+	"push",
+	"pop",
+	"pushRegister",
+	"popRegister",
+	"mul",
+	"div",
+	"incrementRegister",
+	"decrementRegister",
+	"setRegister",
+	// Directives.
+	"label",
+	"align",
+	"entry",
+	"global",
+	"include",
+	"struct",
+	"field",
+	"endstruct",
+	".data",
+	".code",
+	".define",
+	".rept",
+	".endr",
+	".if",
+	".ifdef",
+	".ifndef",
+	".else",
+	".endif",
+];
+
+/// Directives that open a block, whose content is indented.
+const BLOCK_START: [&str; 5] = [".rept", ".if", ".ifdef", ".ifndef", "struct"];
+/// Directives that close a block.
+const BLOCK_END: [&str; 3] = [".endr", ".endif", "endstruct"];
+/// Directives that belong to no label, so they are not indented under one.
+const TOP_LEVEL: [&str; 6] = ["entry", "global", "include", ".define", ".data", ".code"];
+
+/// Source line split into its parts.
+#[derive(Debug)]
+struct Line {
+	/// Statements of the line, already normalized.
+	statements: Vec<String>,
+	/// Comment at the end of the line, or the whole line.
+	comment: Option<String>,
+	/// Nesting level of the line, if it is no comment line.
+	level: Option<usize>,
+}
+
+/// Format text assembly: keywords get their canonical casing (e.g.
+/// `copyCodeMemory`), operands are separated by single spaces and statements
+/// after a label and within blocks like `.rept` are indented. Comments at the
+/// end of lines are aligned per paragraph, full line comments are indented like
+/// the following statement. At most one empty line is kept in a row.
+pub fn format_asm(input: &str) -> String {
+	let mut lines = Vec::new();
+	let mut depth = 0_usize;
+	let mut under_label = false;
+	for line in input.lines() {
+		let (statements, comment) = split_line(line);
+		let Some(first) = statements.first() else {
+			lines.push(Line { statements, comment, level: None });
+			continue;
+		};
+		let keyword = first.split_whitespace().next().unwrap_or_default();
+		let keyword_lowercase = keyword.to_lowercase();
+		if BLOCK_END.contains(&keyword_lowercase.as_str()) || keyword_lowercase == ".else" {
+			depth = depth.saturating_sub(1);
+		}
+		if keyword_lowercase == "label" || TOP_LEVEL.contains(&keyword_lowercase.as_str()) {
+			under_label = keyword_lowercase == "label";
+			lines.push(Line { statements, comment, level: Some(depth) });
+		} else {
+			lines.push(Line { statements, comment, level: Some(depth + usize::from(under_label)) });
+		}
+		if BLOCK_START.contains(&keyword_lowercase.as_str()) || keyword_lowercase == ".else" {
+			depth += 1;
+		}
+	}
+
+	// Full line comments are indented like the next statement.
+	let mut next_level = 0;
+	for line in lines.iter_mut().rev() {
+		match line.level {
+			Some(level) => next_level = level,
+			None if line.comment.is_some() => line.level = Some(next_level),
+			None => {}
+		}
+	}
+
+	let mut output = String::new();
+	let mut paragraph = Vec::new();
+	for line in lines {
+		if line.statements.is_empty() && line.comment.is_none() {
+			write_paragraph(&mut output, &paragraph);
+			paragraph.clear();
+			if !output.is_empty() && !output.ends_with("\n\n") {
+				output.push('\n');
+			}
+		} else {
+			paragraph.push(line);
+		}
+	}
+	write_paragraph(&mut output, &paragraph);
+	while output.ends_with("\n\n") {
+		output.pop();
+	}
+	output
+}
+
+/// Write lines without empty lines in between, aligning comments at the end
+/// of lines.
+fn write_paragraph(output: &mut String, lines: &[Line]) {
+	let code = lines
+		.iter()
+		.map(|line| {
+			let indent = INDENT.repeat(line.level.unwrap_or_default());
+			format!("{indent}{}", line.statements.join(" ; "))
+		})
+		.collect::<Vec<_>>();
+	let comment_column = lines
+		.iter()
+		.zip(&code)
+		.filter(|(line, _)| !line.statements.is_empty() && line.comment.is_some())
+		.map(|(_, code)| code.chars().count())
+		.max()
+		.unwrap_or_default();
+	for (line, code) in lines.iter().zip(code) {
+		match &line.comment {
+			Some(comment) if !line.statements.is_empty() => {
+				output.push_str(&format!("{code:comment_column$} ; {comment}\n"));
+			}
+			Some(comment) => output.push_str(&format!("{code}{comment}\n")),
+			None => output.push_str(&format!("{code}\n")),
+		}
+	}
+}
+
+/// Split a line into its normalized statements and the comment. Like the
+/// assembler, a comment or data string consumes the rest of the line.
+fn split_line(line: &str) -> (Vec<String>, Option<String>) {
+	let mut statements = Vec::new();
+	let mut rest = line;
+	loop {
+		let statement = rest.trim();
+		let keyword = statement.split_whitespace().next().unwrap_or_default();
+		if statement.starts_with('#') || statement.starts_with("//") {
+			return (statements, Some(statement.to_owned()));
+		}
+		if keyword.eq_ignore_ascii_case("datastring") {
+			let text = statement[keyword.len()..].trim();
+			statements.push(format!("dataString {text}").trim_end().to_owned());
+			return (statements, None);
+		}
+		let (statement, remainder) = match statement.split_once(';') {
+			Some((statement, remainder)) => (statement, Some(remainder)),
+			None => (statement, None),
+		};
+		if !statement.trim().is_empty() {
+			statements.push(normalize_statement(statement));
+		}
+		match remainder {
+			Some(remainder) => rest = remainder,
+			None => return (statements, None),
+		}
+	}
+}
+
+/// Normalize the keyword casing and the spacing of a single statement.
+fn normalize_statement(statement: &str) -> String {
+	let mut parts = statement.split_whitespace();
+	let keyword = parts.next().unwrap_or_default();
+	let keyword = KEYWORDS
+		.iter()
+		.find(|canonical| canonical.eq_ignore_ascii_case(keyword))
+		.map_or(keyword, |canonical| canonical);
+	std::iter::once(keyword).chain(parts).collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,58 @@
+//! Fuzzing harnesses for the decoders and the interpreter, used by the
+//! cargo-fuzz targets in `fuzz/`. They must not panic on any input.
+
+use arbitrary::Unstructured;
+
+use crate::{Executable, Instruction, Machine, Object, Program, VmPtr};
+
+/// Memory size of the machines used for fuzzing.
+const MEMORY_SIZE: VmPtr = 256;
+/// Maximum number of instructions executed per run.
+const FUEL: u64 = 10_000;
+
+/// Decode arbitrary bytes as instruction, program, executable and object.
+/// Decoded instructions must encode to the same bytes again.
+pub fn decode(data: &[u8]) {
+	if let Ok(instruction) = Instruction::parse(data) {
+		let bytes = instruction.bytes();
+		assert_eq!(bytes.len(), instruction.size(), "Encoded size of {instruction:?} is wrong");
+		assert_eq!(
+			bytes.as_slice(),
+			&data[..bytes.len()],
+			"Decoded {instruction:?} encodes differently"
+		);
+	}
+	if let Ok(program) = Program::disassemble(data) {
+		_ = program.compile();
+		_ = program.to_asm();
+	}
+	if let Ok(mut executable) = Executable::from_bytes(data) {
+		_ = Program::from_executable(&executable);
+		// Do not allocate the huge memory sizes random headers ask for.
+		executable.memory_size = executable.memory_size.map(|size| size.min(MEMORY_SIZE));
+		_ = Machine::<4>::from_executable(executable);
+	}
+	_ = Object::from_bytes(data);
+}
+
+/// Execute arbitrary bytes as code on a small machine with limited fuel.
+pub fn execute_bytes(data: &[u8]) {
+	let mut machine =
+		Machine::<4>::new(data, MEMORY_SIZE).with_fuel(FUEL).with_captured_output().with_input([]);
+	_ = machine.run();
+}
+
+/// Generate a well-formed program from the arbitrary bytes and execute it on a
+/// small machine with limited fuel.
+pub fn execute_program(data: &[u8]) {
+	let mut u = Unstructured::new(data);
+	let Ok(program) = u.arbitrary::<Program>() else {
+		return;
+	};
+	let executable = program.to_executable().expect("arbitrary programs compile");
+	let Ok(machine) = Machine::<256>::from_executable(executable) else {
+		return;
+	};
+	let mut machine = machine.with_fuel(FUEL).with_captured_output().with_input([]);
+	_ = machine.run();
+}
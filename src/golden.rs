@@ -0,0 +1,176 @@
+//! Golden-output tests: run assembly programs and compare their output to the
+//! expected output.
+
+use std::{
+	fmt::Write,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{Machine, Program};
+
+/// Prefix of comment lines holding the expected output in the program itself.
+const EXPECT_PREFIXES: [&str; 2] = ["#expect:", "# expect:"];
+
+/// Assembly program with its expected output. The expected output is read
+/// from the adjacent `.expected` file, e.g. `hello.expected` for `hello.asm`,
+/// or otherwise from `#expect: <line>` comments in the program, one per
+/// output line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenTest {
+	/// Path of the assembly file.
+	pub path: PathBuf,
+	/// Source code of the program.
+	pub source: String,
+	/// Expected output of the program.
+	pub expected: String,
+}
+
+/// Result of running a [`GoldenTest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenOutcome {
+	/// Path of the assembly file.
+	pub path: PathBuf,
+	/// Expected output of the program.
+	pub expected: String,
+	/// Actual output of the program.
+	pub output: String,
+	/// Error while assembling or running the program, if any.
+	pub error: Option<String>,
+}
+
+impl GoldenTest {
+	/// Read the program and its expected output.
+	pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+		let path = path.as_ref();
+		let source =
+			fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+		let expected_path = path.with_extension("expected");
+		let expected = if expected_path.exists() {
+			fs::read_to_string(&expected_path)
+				.with_context(|| format!("Cannot read {}", expected_path.display()))?
+		} else {
+			source.lines().filter_map(expectation).map(|line| format!("{line}\n")).collect()
+		};
+		Ok(Self { path: path.to_owned(), source, expected })
+	}
+
+	/// Find all `.asm` files in the directory and its subdirectories, sorted by
+	/// path, and load them.
+	pub fn discover(dir: impl AsRef<Path>) -> anyhow::Result<Vec<Self>> {
+		let mut paths = Vec::new();
+		let mut dirs = vec![dir.as_ref().to_owned()];
+		while let Some(dir) = dirs.pop() {
+			let entries = fs::read_dir(&dir)
+				.with_context(|| format!("Cannot read directory {}", dir.display()))?;
+			for entry in entries {
+				let path = entry?.path();
+				if path.is_dir() {
+					dirs.push(path);
+				} else if path.extension().is_some_and(|extension| extension == "asm") {
+					paths.push(path);
+				}
+			}
+		}
+		paths.sort();
+		paths.into_iter().map(Self::load).collect()
+	}
+
+	/// Assemble and run the program on a machine with the given number of side
+	/// registers, capturing its output. The input is empty. The fuel limits the
+	/// number of executed instructions, so that endless loops fail the test.
+	pub fn run<const SIDE_REGS: usize>(&self, fuel: u64) -> GoldenOutcome {
+		let mut output = String::new();
+		let result = self.execute::<SIDE_REGS>(fuel, &mut output);
+		GoldenOutcome {
+			path: self.path.clone(),
+			expected: self.expected.clone(),
+			output,
+			error: result.err().map(|err| format!("{err:#}")),
+		}
+	}
+
+	/// Assemble and run the program, writing the output even on errors.
+	fn execute<const SIDE_REGS: usize>(
+		&self,
+		fuel: u64,
+		output: &mut String,
+	) -> anyhow::Result<()> {
+		// Expectations are not valid assembly, keep their lines empty to keep
+		// line numbers intact.
+		let source = self
+			.source
+			.lines()
+			.map(|line| if expectation(line).is_some() { "" } else { line })
+			.collect::<Vec<_>>()
+			.join("\n");
+		let program: Program = source.parse()?;
+		let mut executable = program.to_executable()?;
+		executable.debug_info = Some(program.debug_info(self.path.display().to_string()));
+		let mut machine = Machine::<SIDE_REGS>::from_executable(executable)?
+			.with_fuel(fuel)
+			.with_captured_output()
+			.with_input([]);
+		let result = machine.run();
+		*output = machine.take_output();
+		result
+	}
+}
+
+impl GoldenOutcome {
+	/// Whether the program ran without error and printed the expected output.
+	pub fn passed(&self) -> bool {
+		self.error.is_none() && self.output == self.expected
+	}
+
+	/// Line diff between the expected and the actual output. Lines only
+	/// expected are prefixed with `-`, lines only printed with `+`.
+	pub fn diff(&self) -> String {
+		let expected = self.expected.lines().collect::<Vec<_>>();
+		let actual = self.output.lines().collect::<Vec<_>>();
+		// Longest common subsequence lengths of the suffixes.
+		let mut common = vec![vec![0_usize; actual.len() + 1]; expected.len() + 1];
+		for i in (0..expected.len()).rev() {
+			for j in (0..actual.len()).rev() {
+				common[i][j] = if expected[i] == actual[j] {
+					common[i + 1][j + 1] + 1
+				} else {
+					common[i + 1][j].max(common[i][j + 1])
+				};
+			}
+		}
+
+		let mut diff = String::new();
+		let (mut i, mut j) = (0, 0);
+		while i < expected.len() || j < actual.len() {
+			let line = if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+				i += 1;
+				j += 1;
+				format!("  {}", expected[i - 1])
+			} else if i < expected.len()
+				&& (j == actual.len() || common[i + 1][j] >= common[i][j + 1])
+			{
+				i += 1;
+				format!("- {}", expected[i - 1])
+			} else {
+				j += 1;
+				format!("+ {}", actual[j - 1])
+			};
+			writeln!(diff, "{line}").expect("writing to String cannot fail");
+		}
+		if self.expected.ends_with('\n') != self.output.ends_with('\n') {
+			writeln!(diff, "(differing newline at the end)")
+				.expect("writing to String cannot fail");
+		}
+		diff
+	}
+}
+
+/// Get the expected output line of an expectation comment.
+fn expectation(line: &str) -> Option<&str> {
+	let line = line.trim_start();
+	let expected = EXPECT_PREFIXES.iter().find_map(|prefix| line.strip_prefix(prefix))?;
+	Some(expected.strip_prefix(' ').unwrap_or(expected))
+}
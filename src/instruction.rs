@@ -1,9 +1,15 @@
-use std::mem::size_of;
+use std::{
+	ffi::{CStr, CString},
+	fmt,
+	mem::size_of,
+	str::FromStr,
+};
 
 use anyhow::Context;
 
 use crate::{
-	util::{native_ptr, read_bytes, read_u8, read_vm_ptr},
+	opcode::Opcode,
+	util::{native_ptr, parse_value, read_bytes, read_u8, read_vm_ptr, vm_ptr},
 	VmPtr,
 };
 
@@ -174,236 +180,518 @@ impl Instruction {
 		}
 	}
 
+	/// Return the code address this instruction refers to, i.e. the target of
+	/// jumps and calls or the source of copy code memory instructions.
+	pub fn code_address(&self) -> Option<VmPtr> {
+		match self {
+			Self::CopyCodeMemory(addr, _)
+			| Self::Jump(addr)
+			| Self::Call(addr)
+			| Self::JumpEqual(addr)
+			| Self::JumpNotEqual(addr)
+			| Self::JumpGreater(addr)
+			| Self::JumpLess(addr)
+			| Self::JumpGreaterEqual(addr)
+			| Self::JumpLessEqual(addr)
+			| Self::JumpZero(addr)
+			| Self::JumpNonzero(addr) => Some(*addr),
+			_ => None,
+		}
+	}
+
+	/// Return a mutable reference to the code address this instruction refers
+	/// to, see [`Self::code_address`].
+	pub fn code_address_mut(&mut self) -> Option<&mut VmPtr> {
+		match self {
+			Self::CopyCodeMemory(addr, _)
+			| Self::Jump(addr)
+			| Self::Call(addr)
+			| Self::JumpEqual(addr)
+			| Self::JumpNotEqual(addr)
+			| Self::JumpGreater(addr)
+			| Self::JumpLess(addr)
+			| Self::JumpGreaterEqual(addr)
+			| Self::JumpLessEqual(addr)
+			| Self::JumpZero(addr)
+			| Self::JumpNonzero(addr) => Some(addr),
+			_ => None,
+		}
+	}
+
+	/// Return the side register this instruction uses, if any.
+	pub fn side_register(&self) -> Option<u8> {
+		match self {
+			Self::Deref8(reg)
+			| Self::Deref16(reg)
+			| Self::Deref32(reg)
+			| Self::Swap(reg)
+			| Self::Write8(reg)
+			| Self::Write16(reg)
+			| Self::Write32(reg)
+			| Self::Add(reg)
+			| Self::Sub(reg)
+			| Self::Compare(reg)
+			| Self::PushRegister(reg)
+			| Self::PopRegister(reg)
+			| Self::Mul(reg)
+			| Self::Div(reg)
+			| Self::IncrementRegister(reg)
+			| Self::DecrementRegister(reg)
+			| Self::SetRegister(reg, _) => Some(*reg),
+			_ => None,
+		}
+	}
+
+	/// Return the opcode of this instruction.
+	pub fn opcode(&self) -> Opcode {
+		match self {
+			Self::Nop => Opcode::Nop,
+			Self::Halt => Opcode::Halt,
+			Self::Load8(_) => Opcode::Load8,
+			Self::Store8(_) => Opcode::Store8,
+			Self::Load16(_) => Opcode::Load16,
+			Self::Store16(_) => Opcode::Store16,
+			Self::Load32(_) => Opcode::Load32,
+			Self::Store32(_) => Opcode::Store32,
+			Self::Set(_) => Opcode::Set,
+			Self::Deref8(_) => Opcode::Deref8,
+			Self::Deref16(_) => Opcode::Deref16,
+			Self::Deref32(_) => Opcode::Deref32,
+			Self::Syscall(_) => Opcode::Syscall,
+			Self::CopyCodeMemory(_, _) => Opcode::CopyCodeMemory,
+			Self::Data(_, _) => Opcode::Data,
+			Self::Swap(_) => Opcode::Swap,
+			Self::Write8(_) => Opcode::Write8,
+			Self::Write16(_) => Opcode::Write16,
+			Self::Write32(_) => Opcode::Write32,
+			Self::ReadStackPointer => Opcode::ReadStackPointer,
+			Self::WriteStackPointer => Opcode::WriteStackPointer,
+			Self::Jump(_) => Opcode::Jump,
+			Self::Call(_) => Opcode::Call,
+			Self::Return => Opcode::Return,
+			Self::Increment => Opcode::Increment,
+			Self::Decrement => Opcode::Decrement,
+			Self::Add(_) => Opcode::Add,
+			Self::Sub(_) => Opcode::Sub,
+			Self::Compare(_) => Opcode::Compare,
+			Self::JumpEqual(_) => Opcode::JumpEqual,
+			Self::JumpNotEqual(_) => Opcode::JumpNotEqual,
+			Self::JumpGreater(_) => Opcode::JumpGreater,
+			Self::JumpLess(_) => Opcode::JumpLess,
+			Self::JumpGreaterEqual(_) => Opcode::JumpGreaterEqual,
+			Self::JumpLessEqual(_) => Opcode::JumpLessEqual,
+			Self::JumpZero(_) => Opcode::JumpZero,
+			Self::JumpNonzero(_) => Opcode::JumpNonzero,
+			Self::Push => Opcode::Push,
+			Self::Pop => Opcode::Pop,
+			Self::PushRegister(_) => Opcode::PushRegister,
+			Self::PopRegister(_) => Opcode::PopRegister,
+			Self::Mul(_) => Opcode::Mul,
+			Self::Div(_) => Opcode::Div,
+			Self::IncrementRegister(_) => Opcode::IncrementRegister,
+			Self::DecrementRegister(_) => Opcode::DecrementRegister,
+			Self::SetRegister(_, _) => Opcode::SetRegister,
+		}
+	}
+
 	/// Parse the first instruction from the byte buffer.
 	pub fn parse(code: &[u8]) -> anyhow::Result<Self> {
 		let code_sub_slice = |index| code.get(index).context("not enough bytes");
 
-		match *code.first().context("Cannot parse instruction from empty code")? {
-			0 => Ok(Self::Nop),
-			1 => Ok(Self::Halt),
-			2 => Ok(Self::Load8(read_vm_ptr(code_sub_slice(1..)?)?)),
-			3 => Ok(Self::Store8(read_vm_ptr(code_sub_slice(1..)?)?)),
-			4 => Ok(Self::Load16(read_vm_ptr(code_sub_slice(1..)?)?)),
-			5 => Ok(Self::Store16(read_vm_ptr(code_sub_slice(1..)?)?)),
-			6 => Ok(Self::Load32(read_vm_ptr(code_sub_slice(1..)?)?)),
-			7 => Ok(Self::Store32(read_vm_ptr(code_sub_slice(1..)?)?)),
-			8 => Ok(Self::Set(read_vm_ptr(code_sub_slice(1..)?)?)),
-			9 => Ok(Self::Deref8(read_u8(code_sub_slice(1..)?)?)),
-			10 => Ok(Self::Deref16(read_u8(code_sub_slice(1..)?)?)),
-			11 => Ok(Self::Deref32(read_u8(code_sub_slice(1..)?)?)),
-			12 => Ok(Self::Syscall(read_u8(code_sub_slice(1..)?)?)),
-			13 => Ok(Self::CopyCodeMemory(
+		let opcode = *code.first().context("Cannot parse instruction from empty code")?;
+		match Opcode::try_from(opcode)? {
+			Opcode::Nop => Ok(Self::Nop),
+			Opcode::Halt => Ok(Self::Halt),
+			Opcode::Load8 => Ok(Self::Load8(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Store8 => Ok(Self::Store8(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Load16 => Ok(Self::Load16(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Store16 => Ok(Self::Store16(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Load32 => Ok(Self::Load32(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Store32 => Ok(Self::Store32(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Set => Ok(Self::Set(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Deref8 => Ok(Self::Deref8(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Deref16 => Ok(Self::Deref16(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Deref32 => Ok(Self::Deref32(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Syscall => Ok(Self::Syscall(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::CopyCodeMemory => Ok(Self::CopyCodeMemory(
 				read_vm_ptr(code_sub_slice(1..)?)?,
 				read_vm_ptr(code_sub_slice(5..)?)?,
 			)),
-			14 => {
+			Opcode::Data => {
 				let len = read_vm_ptr(code_sub_slice(1..)?)?;
 				Ok(Self::Data(len, read_bytes(code_sub_slice(5..)?, native_ptr(len))?.to_vec()))
 			}
-			15 => Ok(Self::Swap(read_u8(code_sub_slice(1..)?)?)),
-			16 => Ok(Self::Write8(read_u8(code_sub_slice(1..)?)?)),
-			17 => Ok(Self::Write16(read_u8(code_sub_slice(1..)?)?)),
-			18 => Ok(Self::Write32(read_u8(code_sub_slice(1..)?)?)),
-			19 => Ok(Self::ReadStackPointer),
-			20 => Ok(Self::WriteStackPointer),
-			21 => Ok(Self::Jump(read_vm_ptr(code_sub_slice(1..)?)?)),
-			22 => Ok(Self::Call(read_vm_ptr(code_sub_slice(1..)?)?)),
-			23 => Ok(Self::Return),
-			24 => Ok(Self::Increment),
-			25 => Ok(Self::Decrement),
-			26 => Ok(Self::Add(read_u8(code_sub_slice(1..)?)?)),
-			27 => Ok(Self::Sub(read_u8(code_sub_slice(1..)?)?)),
-			28 => Ok(Self::Compare(read_u8(code_sub_slice(1..)?)?)),
-			29 => Ok(Self::JumpEqual(read_vm_ptr(code_sub_slice(1..)?)?)),
-			30 => Ok(Self::JumpNotEqual(read_vm_ptr(code_sub_slice(1..)?)?)),
-			31 => Ok(Self::JumpGreater(read_vm_ptr(code_sub_slice(1..)?)?)),
-			32 => Ok(Self::JumpLess(read_vm_ptr(code_sub_slice(1..)?)?)),
-			33 => Ok(Self::JumpGreaterEqual(read_vm_ptr(code_sub_slice(1..)?)?)),
-			34 => Ok(Self::JumpLessEqual(read_vm_ptr(code_sub_slice(1..)?)?)),
-			35 => Ok(Self::JumpZero(read_vm_ptr(code_sub_slice(1..)?)?)),
-			36 => Ok(Self::JumpNonzero(read_vm_ptr(code_sub_slice(1..)?)?)),
-			37 => Ok(Self::Push),
-			38 => Ok(Self::Pop),
-			39 => Ok(Self::PushRegister(read_u8(code_sub_slice(1..)?)?)),
-			40 => Ok(Self::PopRegister(read_u8(code_sub_slice(1..)?)?)),
-			41 => Ok(Self::Mul(read_u8(code_sub_slice(1..)?)?)),
-			42 => Ok(Self::Div(read_u8(code_sub_slice(1..)?)?)),
-			43 => Ok(Self::IncrementRegister(read_u8(code_sub_slice(1..)?)?)),
-			44 => Ok(Self::DecrementRegister(read_u8(code_sub_slice(1..)?)?)),
-			45 => Ok(Self::SetRegister(
+			Opcode::Swap => Ok(Self::Swap(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Write8 => Ok(Self::Write8(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Write16 => Ok(Self::Write16(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Write32 => Ok(Self::Write32(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::ReadStackPointer => Ok(Self::ReadStackPointer),
+			Opcode::WriteStackPointer => Ok(Self::WriteStackPointer),
+			Opcode::Jump => Ok(Self::Jump(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Call => Ok(Self::Call(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Return => Ok(Self::Return),
+			Opcode::Increment => Ok(Self::Increment),
+			Opcode::Decrement => Ok(Self::Decrement),
+			Opcode::Add => Ok(Self::Add(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Sub => Ok(Self::Sub(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Compare => Ok(Self::Compare(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::JumpEqual => Ok(Self::JumpEqual(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::JumpNotEqual => Ok(Self::JumpNotEqual(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::JumpGreater => Ok(Self::JumpGreater(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::JumpLess => Ok(Self::JumpLess(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::JumpGreaterEqual => {
+				Ok(Self::JumpGreaterEqual(read_vm_ptr(code_sub_slice(1..)?)?))
+			}
+			Opcode::JumpLessEqual => Ok(Self::JumpLessEqual(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::JumpZero => Ok(Self::JumpZero(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::JumpNonzero => Ok(Self::JumpNonzero(read_vm_ptr(code_sub_slice(1..)?)?)),
+			Opcode::Push => Ok(Self::Push),
+			Opcode::Pop => Ok(Self::Pop),
+			Opcode::PushRegister => Ok(Self::PushRegister(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::PopRegister => Ok(Self::PopRegister(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Mul => Ok(Self::Mul(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::Div => Ok(Self::Div(read_u8(code_sub_slice(1..)?)?)),
+			Opcode::IncrementRegister => {
+				Ok(Self::IncrementRegister(read_u8(code_sub_slice(1..)?)?))
+			}
+			Opcode::DecrementRegister => {
+				Ok(Self::DecrementRegister(read_u8(code_sub_slice(1..)?)?))
+			}
+			Opcode::SetRegister => Ok(Self::SetRegister(
 				read_u8(code_sub_slice(1..)?)?,
 				read_vm_ptr(code_sub_slice(2..)?)?,
 			)),
-			c => Err(anyhow::format_err!("Unrecognized instruction: {c}")),
 		}
 	}
 
+	/// Parse the first instruction from the byte buffer, which was compiled for
+	/// the given instruction set version. See [`Opcode::from_versioned`].
+	pub fn parse_versioned(code: &[u8], isa_version: u16) -> anyhow::Result<Self> {
+		let first = *code.first().context("Cannot parse instruction from empty code")?;
+		let opcode = u8::from(Opcode::from_versioned(first, isa_version)?);
+		if opcode == first {
+			return Self::parse(code);
+		}
+		let mut code = code.to_vec();
+		code[0] = opcode;
+		Self::parse(&code)
+	}
+
 	/// Convert this instruction to opcode bytes.
 	pub fn bytes(&self) -> Vec<u8> {
 		let mut bytes = Vec::with_capacity(self.size());
+		bytes.push(self.opcode().into());
 		match self {
-			Self::Nop => bytes.push(0),
-			Self::Halt => bytes.push(1),
 			Self::Load8(ptr) => {
-				bytes.push(2);
 				bytes.extend_from_slice(&ptr.to_be_bytes());
 			}
 			Self::Store8(ptr) => {
-				bytes.push(3);
 				bytes.extend_from_slice(&ptr.to_be_bytes());
 			}
 			Self::Load16(ptr) => {
-				bytes.push(4);
 				bytes.extend_from_slice(&ptr.to_be_bytes());
 			}
 			Self::Store16(ptr) => {
-				bytes.push(5);
 				bytes.extend_from_slice(&ptr.to_be_bytes());
 			}
 			Self::Load32(ptr) => {
-				bytes.push(6);
 				bytes.extend_from_slice(&ptr.to_be_bytes());
 			}
 			Self::Store32(ptr) => {
-				bytes.push(7);
 				bytes.extend_from_slice(&ptr.to_be_bytes());
 			}
 			Self::Set(value) => {
-				bytes.push(8);
 				bytes.extend_from_slice(&value.to_be_bytes());
 			}
 			Self::Deref8(reg) => {
-				bytes.push(9);
 				bytes.push(*reg);
 			}
 			Self::Deref16(reg) => {
-				bytes.push(10);
 				bytes.push(*reg);
 			}
 			Self::Deref32(reg) => {
-				bytes.push(11);
 				bytes.push(*reg);
 			}
 			Self::Syscall(index) => {
-				bytes.push(12);
 				bytes.push(*index);
 			}
 			Self::CopyCodeMemory(src, size) => {
-				bytes.push(13);
 				bytes.extend_from_slice(&src.to_be_bytes());
 				bytes.extend_from_slice(&size.to_be_bytes());
 			}
 			Self::Data(len, data) => {
 				assert_eq!(data.len(), native_ptr(*len));
-				bytes.push(14);
 				bytes.extend_from_slice(&len.to_be_bytes());
 				bytes.extend_from_slice(data);
 			}
 			Self::Swap(reg) => {
-				bytes.push(15);
 				bytes.push(*reg);
 			}
 			Self::Write8(reg) => {
-				bytes.push(16);
 				bytes.push(*reg);
 			}
 			Self::Write16(reg) => {
-				bytes.push(17);
 				bytes.push(*reg);
 			}
 			Self::Write32(reg) => {
-				bytes.push(18);
 				bytes.push(*reg);
 			}
-			Self::ReadStackPointer => bytes.push(19),
-			Self::WriteStackPointer => bytes.push(20),
 			Self::Jump(addr) => {
-				bytes.push(21);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
 			Self::Call(addr) => {
-				bytes.push(22);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
-			Self::Return => bytes.push(23),
-			Self::Increment => bytes.push(24),
-			Self::Decrement => bytes.push(25),
 			Self::Add(reg) => {
-				bytes.push(26);
 				bytes.push(*reg);
 			}
 			Self::Sub(reg) => {
-				bytes.push(27);
 				bytes.push(*reg);
 			}
 			Self::Compare(reg) => {
-				bytes.push(28);
 				bytes.push(*reg);
 			}
 			Self::JumpEqual(addr) => {
-				bytes.push(29);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
 			Self::JumpNotEqual(addr) => {
-				bytes.push(30);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
 			Self::JumpGreater(addr) => {
-				bytes.push(31);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
 			Self::JumpLess(addr) => {
-				bytes.push(32);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
 			Self::JumpGreaterEqual(addr) => {
-				bytes.push(33);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
 			Self::JumpLessEqual(addr) => {
-				bytes.push(34);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
 			Self::JumpZero(addr) => {
-				bytes.push(35);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
 			Self::JumpNonzero(addr) => {
-				bytes.push(36);
 				bytes.extend_from_slice(&addr.to_be_bytes());
 			}
-			Self::Push => bytes.push(37),
-			Self::Pop => bytes.push(38),
 			Self::PushRegister(reg) => {
-				bytes.push(39);
 				bytes.push(*reg);
 			}
 			Self::PopRegister(reg) => {
-				bytes.push(40);
 				bytes.push(*reg);
 			}
 			Self::Mul(reg) => {
-				bytes.push(41);
 				bytes.push(*reg);
 			}
 			Self::Div(reg) => {
-				bytes.push(42);
 				bytes.push(*reg);
 			}
 			Self::IncrementRegister(reg) => {
-				bytes.push(43);
 				bytes.push(*reg);
 			}
 			Self::DecrementRegister(reg) => {
-				bytes.push(44);
 				bytes.push(*reg);
 			}
 			Self::SetRegister(reg, value) => {
-				bytes.push(45);
 				bytes.push(*reg);
 				bytes.extend_from_slice(&value.to_be_bytes());
 			}
+			Self::Nop
+			| Self::Halt
+			| Self::ReadStackPointer
+			| Self::WriteStackPointer
+			| Self::Return
+			| Self::Increment
+			| Self::Decrement
+			| Self::Push
+			| Self::Pop => {}
 		}
 		bytes
 	}
 }
+
+impl fmt::Display for Instruction {
+	/// Format the instruction as text assembly. Code addresses are written as
+	/// numbers, immediate values with the highest bit set as negative numbers.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let signed = |value: VmPtr| value as i32;
+		match self {
+			Self::Nop => write!(f, "nop"),
+			Self::Halt => write!(f, "halt"),
+			Self::Load8(ptr) => write!(f, "load8 {ptr}"),
+			Self::Store8(ptr) => write!(f, "store8 {ptr}"),
+			Self::Load16(ptr) => write!(f, "load16 {ptr}"),
+			Self::Store16(ptr) => write!(f, "store16 {ptr}"),
+			Self::Load32(ptr) => write!(f, "load32 {ptr}"),
+			Self::Store32(ptr) => write!(f, "store32 {ptr}"),
+			Self::Set(value) => write!(f, "set {}", signed(*value)),
+			Self::Deref8(reg) => write!(f, "deref8 {reg}"),
+			Self::Deref16(reg) => write!(f, "deref16 {reg}"),
+			Self::Deref32(reg) => write!(f, "deref32 {reg}"),
+			Self::Syscall(index) => write!(f, "syscall {index}"),
+			Self::CopyCodeMemory(src, size) => write!(f, "copyCodeMemory {src} {size}"),
+			Self::Data(_len, data) => match data_string(data) {
+				Some(text) => write!(f, "dataString {text}"),
+				None => {
+					write!(f, "dataBytes")?;
+					for byte in data {
+						write!(f, " {byte}")?;
+					}
+					Ok(())
+				}
+			},
+			Self::Swap(reg) => write!(f, "swap {reg}"),
+			Self::Write8(reg) => write!(f, "write8 {reg}"),
+			Self::Write16(reg) => write!(f, "write16 {reg}"),
+			Self::Write32(reg) => write!(f, "write32 {reg}"),
+			Self::ReadStackPointer => write!(f, "readStackPointer"),
+			Self::WriteStackPointer => write!(f, "writeStackPointer"),
+			Self::Jump(addr) => write!(f, "jump {addr}"),
+			Self::Call(addr) => write!(f, "call {addr}"),
+			Self::Return => write!(f, "return"),
+			Self::Increment => write!(f, "increment"),
+			Self::Decrement => write!(f, "decrement"),
+			Self::Add(reg) => write!(f, "add {reg}"),
+			Self::Sub(reg) => write!(f, "sub {reg}"),
+			Self::Compare(reg) => write!(f, "compare {reg}"),
+			Self::JumpEqual(addr) => write!(f, "jumpEqual {addr}"),
+			Self::JumpNotEqual(addr) => write!(f, "jumpNotEqual {addr}"),
+			Self::JumpGreater(addr) => write!(f, "jumpGreater {addr}"),
+			Self::JumpLess(addr) => write!(f, "jumpLess {addr}"),
+			Self::JumpGreaterEqual(addr) => write!(f, "jumpGreaterEqual {addr}"),
+			Self::JumpLessEqual(addr) => write!(f, "jumpLessEqual {addr}"),
+			Self::JumpZero(addr) => write!(f, "jumpZero {addr}"),
+			Self::JumpNonzero(addr) => write!(f, "jumpNonzero {addr}"),
+			Self::Push => write!(f, "push"),
+			Self::Pop => write!(f, "pop"),
+			Self::PushRegister(reg) => write!(f, "pushRegister {reg}"),
+			Self::PopRegister(reg) => write!(f, "popRegister {reg}"),
+			Self::Mul(reg) => write!(f, "mul {reg}"),
+			Self::Div(reg) => write!(f, "div {reg}"),
+			Self::IncrementRegister(reg) => write!(f, "incrementRegister {reg}"),
+			Self::DecrementRegister(reg) => write!(f, "decrementRegister {reg}"),
+			Self::SetRegister(reg, value) => write!(f, "setRegister {reg} {}", signed(*value)),
+		}
+	}
+}
+
+impl FromStr for Instruction {
+	type Err = anyhow::Error;
+
+	/// Parse a single instruction from text assembly, in the form it is
+	/// displayed in. Operands are numeric literals, so jumps, calls and copy
+	/// data instructions take code addresses instead of labels.
+	fn from_str(statement: &str) -> Result<Self, Self::Err> {
+		Self::from_statement(statement, parse_value)?.with_context(|| {
+			format!("Unknown instruction or wrong number of arguments: {}", statement.trim())
+		})
+	}
+}
+
+impl Instruction {
+	/// Parse a single instruction from a text assembly statement, using the
+	/// given function to evaluate numeric operands. Return `None` if the
+	/// mnemonic is unknown or has the wrong number of operands.
+	pub(crate) fn from_statement(
+		statement: &str,
+		value: impl Fn(&str) -> anyhow::Result<VmPtr>,
+	) -> anyhow::Result<Option<Self>> {
+		let statement = statement.trim();
+		let parts = statement.split_whitespace().collect::<Vec<_>>();
+		let Some(mnemonic) = parts.first() else {
+			return Ok(None);
+		};
+		let byte = |operand: &str| {
+			let value = value(operand)?;
+			u8::try_from(value)
+				.with_context(|| format!("Operand {operand} does not fit into a byte"))
+		};
+		let instruction = match (mnemonic.to_lowercase().as_str(), &parts[1..]) {
+			("nop", []) => Self::Nop,
+			("halt", []) => Self::Halt,
+			("load8", [ptr]) => Self::Load8(value(ptr)?),
+			("store8", [ptr]) => Self::Store8(value(ptr)?),
+			("load16", [ptr]) => Self::Load16(value(ptr)?),
+			("store16", [ptr]) => Self::Store16(value(ptr)?),
+			("load32", [ptr]) => Self::Load32(value(ptr)?),
+			("store32", [ptr]) => Self::Store32(value(ptr)?),
+			("set", [val]) => Self::Set(value(val)?),
+			("deref8", [reg]) => Self::Deref8(byte(reg)?),
+			("deref16", [reg]) => Self::Deref16(byte(reg)?),
+			("deref32", [reg]) => Self::Deref32(byte(reg)?),
+			("syscall", [index]) => Self::Syscall(byte(index)?),
+			("copycodememory", [src, size]) => Self::CopyCodeMemory(value(src)?, value(size)?),
+			("datastring", _) => {
+				let cstr = CString::new(statement.split_at(mnemonic.len()).1.trim())?;
+				let data = cstr.into_bytes_with_nul();
+				Self::Data(vm_ptr(data.len()), data)
+			}
+			("databytes", bytes) => {
+				let data = bytes.iter().map(|b| byte(b)).collect::<anyhow::Result<Vec<_>>>()?;
+				Self::Data(vm_ptr(data.len()), data)
+			}
+			("swap", [reg]) => Self::Swap(byte(reg)?),
+			("write8", [reg]) => Self::Write8(byte(reg)?),
+			("write16", [reg]) => Self::Write16(byte(reg)?),
+			("write32", [reg]) => Self::Write32(byte(reg)?),
+			("readstackpointer", []) => Self::ReadStackPointer,
+			("writestackpointer", []) => Self::WriteStackPointer,
+			("jump", [addr]) => Self::Jump(value(addr)?),
+			("call", [addr]) => Self::Call(value(addr)?),
+			("return", []) => Self::Return,
+			("increment", []) => Self::Increment,
+			("decrement", []) => Self::Decrement,
+			("add", [reg]) => Self::Add(byte(reg)?),
+			("sub", [reg]) => Self::Sub(byte(reg)?),
+			("compare", [reg]) => Self::Compare(byte(reg)?),
+			("jumpequal", [addr]) => Self::JumpEqual(value(addr)?),
+			("jumpnotequal", [addr]) => Self::JumpNotEqual(value(addr)?),
+			("jumpgreater", [addr]) => Self::JumpGreater(value(addr)?),
+			("jumpless", [addr]) => Self::JumpLess(value(addr)?),
+			("jumpgreaterequal", [addr]) => Self::JumpGreaterEqual(value(addr)?),
+			("jumplessequal", [addr]) => Self::JumpLessEqual(value(addr)?),
+			("jumpzero", [addr]) => Self::JumpZero(value(addr)?),
+			("jumpnonzero", [addr]) => Self::JumpNonzero(value(addr)?),
+			("push", []) => Self::Push,
+			("pop", []) => Self::Pop,
+			("pushregister", [reg]) => Self::PushRegister(byte(reg)?),
+			("popregister", [reg]) => Self::PopRegister(byte(reg)?),
+			("mul", [reg]) => Self::Mul(byte(reg)?),
+			("div", [reg]) => Self::Div(byte(reg)?),
+			("incrementregister", [reg]) => Self::IncrementRegister(byte(reg)?),
+			("decrementregister", [reg]) => Self::DecrementRegister(byte(reg)?),
+			("setregister", [reg, val]) => Self::SetRegister(byte(reg)?, value(val)?),
+			_ => return Ok(None),
+		};
+		Ok(Some(instruction))
+	}
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Instruction {
+	/// Generate a random instruction. Operands are arbitrary, but structurally
+	/// valid, e.g. the length of data segments matches their data.
+	fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+		let opcode = u.int_in_range(0..=u8::from(Opcode::LAST))?;
+		let mut bytes = vec![opcode];
+		if opcode == u8::from(Opcode::Data) {
+			let data: Vec<u8> = u.arbitrary()?;
+			bytes.extend_from_slice(&vm_ptr(data.len()).to_be_bytes());
+			bytes.extend_from_slice(&data);
+		} else {
+			// Enough operand bytes for every other instruction.
+			let operands: [u8; 2 * size_of::<VmPtr>()] = u.arbitrary()?;
+			bytes.extend_from_slice(&operands);
+		}
+		Ok(Self::parse(&bytes).expect("generated bytes are a valid instruction"))
+	}
+}
+
+/// Get the text of data that can be written as `dataString`, i.e. a single
+/// line nul terminated string without surrounding whitespace.
+fn data_string(data: &[u8]) -> Option<&str> {
+	let text = CStr::from_bytes_with_nul(data).ok()?.to_str().ok()?;
+	let representable = text.trim() == text && !text.contains(['\n', '\r']);
+	representable.then_some(text)
+}
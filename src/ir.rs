@@ -0,0 +1,201 @@
+//! Intermediate representation for code generators, with an unlimited number
+//! of virtual registers organized in basic blocks. Lowering maps the virtual
+//! registers onto side registers, spilling to the stack when they run out, and
+//! emits a [`Program`].
+//!
+//! Functions follow the calling convention of [`Program::begin_function`]:
+//! side registers 0-3 are scratch registers, 4 is the frame pointer and the
+//! further ones are allocated to virtual registers and preserved across calls.
+
+mod lower;
+
+use std::fmt;
+
+use crate::{Program, VmPtr};
+
+/// Virtual register holding a 32 bit value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VReg(usize);
+
+impl fmt::Display for VReg {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "v{}", self.0)
+	}
+}
+
+/// Basic block of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(usize);
+
+impl fmt::Display for BlockId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "b{}", self.0)
+	}
+}
+
+/// Arithmetic operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+	/// Wrapping addition.
+	Add,
+	/// Wrapping subtraction.
+	Sub,
+	/// Wrapping multiplication.
+	Mul,
+	/// Unsigned division.
+	Div,
+	/// Remainder of the unsigned division.
+	Rem,
+}
+
+/// Unsigned comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+	/// `lhs == rhs`
+	Equal,
+	/// `lhs != rhs`
+	NotEqual,
+	/// `lhs < rhs`
+	Less,
+	/// `lhs <= rhs`
+	LessEqual,
+	/// `lhs > rhs`
+	Greater,
+	/// `lhs >= rhs`
+	GreaterEqual,
+}
+
+/// Instruction of the intermediate representation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IrInstruction {
+	/// `dst = value`
+	Const { dst: VReg, value: VmPtr },
+	/// `dst = src`
+	Copy { dst: VReg, src: VReg },
+	/// `dst = lhs <op> rhs`
+	Binary { op: BinaryOp, dst: VReg, lhs: VReg, rhs: VReg },
+	/// `dst = 1` if the comparison holds, otherwise `dst = 0`.
+	Compare { condition: Condition, dst: VReg, lhs: VReg, rhs: VReg },
+	/// Load the 8 bit value at the address.
+	Load8 { dst: VReg, addr: VReg },
+	/// Load the 32 bit value at the address.
+	Load32 { dst: VReg, addr: VReg },
+	/// Store the lowest 8 bits of the value at the address.
+	Store8 { addr: VReg, value: VReg },
+	/// Store the 32 bit value at the address.
+	Store32 { addr: VReg, value: VReg },
+	/// Call a function of the module with the arguments, storing its result.
+	Call { dst: Option<VReg>, function: String, args: Vec<VReg> },
+	/// Make a syscall with the argument in the main register, storing the main
+	/// register afterwards.
+	Syscall { index: u8, arg: Option<VReg>, dst: Option<VReg> },
+}
+
+/// Instruction ending a basic block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+	/// Continue with the block.
+	Jump(BlockId),
+	/// Continue with `then` if the comparison holds, otherwise with
+	/// `otherwise`.
+	Branch { condition: Condition, lhs: VReg, rhs: VReg, then: BlockId, otherwise: BlockId },
+	/// Return from the function with the value, or 0.
+	Return(Option<VReg>),
+	/// Halt the machine.
+	Halt,
+}
+
+/// Basic block: instructions executed in sequence, followed by a terminator.
+#[derive(Debug, Clone, Default)]
+struct Block {
+	/// Instructions of the block.
+	instructions: Vec<IrInstruction>,
+	/// Terminator, which must be set before lowering.
+	terminator: Option<Terminator>,
+}
+
+/// Function of the intermediate representation.
+#[derive(Debug, Clone)]
+pub struct IrFunction {
+	/// Name of the function, used as its label.
+	name: String,
+	/// Number of parameters, which are the first virtual registers.
+	params: usize,
+	/// Number of virtual registers.
+	vregs: usize,
+	/// Basic blocks, the first one is the entry.
+	blocks: Vec<Block>,
+}
+
+impl IrFunction {
+	/// Create a function with the given number of parameters and an empty
+	/// entry block.
+	pub fn new(name: impl Into<String>, params: usize) -> Self {
+		Self { name: name.into(), params, vregs: params, blocks: vec![Block::default()] }
+	}
+
+	/// Name of the function.
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Virtual register holding the parameter with the given index.
+	pub fn param(&self, index: usize) -> VReg {
+		assert!(index < self.params, "Function {} has no parameter {index}", self.name);
+		VReg(index)
+	}
+
+	/// Create a new virtual register.
+	pub fn vreg(&mut self) -> VReg {
+		self.vregs += 1;
+		VReg(self.vregs - 1)
+	}
+
+	/// Entry block of the function.
+	pub fn entry(&self) -> BlockId {
+		BlockId(0)
+	}
+
+	/// Create a new, empty basic block. Blocks are laid out in the order they
+	/// are created.
+	pub fn block(&mut self) -> BlockId {
+		self.blocks.push(Block::default());
+		BlockId(self.blocks.len() - 1)
+	}
+
+	/// Append an instruction to the block.
+	pub fn push(&mut self, block: BlockId, instruction: IrInstruction) {
+		self.blocks[block.0].instructions.push(instruction);
+	}
+
+	/// Set the terminator of the block.
+	pub fn terminate(&mut self, block: BlockId, terminator: Terminator) {
+		self.blocks[block.0].terminator = Some(terminator);
+	}
+}
+
+/// Collection of functions that are lowered to a program together.
+#[derive(Debug, Clone, Default)]
+pub struct IrModule {
+	/// Functions of the module.
+	functions: Vec<IrFunction>,
+}
+
+impl IrModule {
+	/// Create an empty module.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add a function to the module.
+	pub fn add_function(&mut self, function: IrFunction) {
+		self.functions.push(function);
+	}
+
+	/// Lower the module to a program for a machine with the given number of
+	/// side registers, which must be at least 5. The program calls `main`,
+	/// which must not take parameters, and halts afterwards.
+	pub fn lower(&self, side_registers: usize) -> anyhow::Result<Program> {
+		lower::lower(self, side_registers)
+	}
+}
@@ -0,0 +1,534 @@
+//! Lowering of the intermediate representation: liveness analysis, linear scan
+//! register allocation and code emission.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Context;
+
+use super::{BinaryOp, BlockId, Condition, IrFunction, IrInstruction, IrModule, Terminator, VReg};
+use crate::{Instruction, Program, FRAME_POINTER};
+
+/// Scratch register for the left operand or address.
+const SCRATCH_ADDR: u8 = 0;
+/// Scratch register for the right operand.
+const SCRATCH_OPERAND: u8 = 1;
+
+/// Location of a virtual register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+	/// Side register.
+	Register(u8),
+	/// Local variable of the stack frame.
+	Slot(usize),
+}
+
+/// Live interval of a virtual register over the linearized instructions.
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+	/// Virtual register.
+	vreg: VReg,
+	/// First position where the register is live.
+	start: usize,
+	/// Last position where the register is live.
+	end: usize,
+}
+
+/// Result of the register allocation of a function.
+struct Allocation {
+	/// Location of every virtual register that is used.
+	locations: HashMap<VReg, Location>,
+	/// Side registers used, which are saved in the locals after the spill
+	/// slots.
+	saved: Vec<u8>,
+	/// Number of spill slots.
+	slots: usize,
+}
+
+/// Lower the module to a program.
+pub(super) fn lower(module: &IrModule, side_registers: usize) -> anyhow::Result<Program> {
+	anyhow::ensure!(
+		side_registers > usize::from(FRAME_POINTER),
+		"Lowering needs at least {} side registers, got {side_registers}",
+		FRAME_POINTER + 1
+	);
+	let main = module
+		.functions
+		.iter()
+		.find(|function| function.name == "main")
+		.context("Module has no main function")?;
+	anyhow::ensure!(main.params == 0, "Function main must not take parameters");
+	for function in &module.functions {
+		validate(module, function)?;
+	}
+
+	let mut program = Program::new();
+	program.call_function("main");
+	program.add_halt();
+	for function in &module.functions {
+		let allocation = allocate(function, side_registers);
+		Emitter { program: &mut program, function, allocation: &allocation }.emit()?;
+	}
+	Ok(program)
+}
+
+/// Check that all blocks are terminated and all references are valid.
+fn validate(module: &IrModule, function: &IrFunction) -> anyhow::Result<()> {
+	let name = &function.name;
+	anyhow::ensure!(
+		module.functions.iter().filter(|other| other.name == *name).count() == 1,
+		"Function {name} is defined more than once"
+	);
+	for (index, block) in function.blocks.iter().enumerate() {
+		let terminator = block
+			.terminator
+			.as_ref()
+			.with_context(|| format!("Block b{index} of function {name} has no terminator"))?;
+		let (uses, def) = block
+			.instructions
+			.iter()
+			.map(operands)
+			.chain(std::iter::once((terminator_uses(terminator), None)))
+			.fold((Vec::new(), Vec::new()), |(mut uses, mut defs), (used, def)| {
+				uses.extend(used);
+				defs.extend(def);
+				(uses, defs)
+			});
+		if let Some(vreg) = uses.iter().chain(&def).find(|vreg| vreg.0 >= function.vregs) {
+			anyhow::bail!("Virtual register {vreg} does not belong to function {name}");
+		}
+		if let Some(target) =
+			successors(terminator).find(|target| target.0 >= function.blocks.len())
+		{
+			anyhow::bail!("Block {target} does not belong to function {name}");
+		}
+		for instruction in &block.instructions {
+			if let IrInstruction::Call { function: callee, args, .. } = instruction {
+				let callee =
+					module.functions.iter().find(|other| other.name == *callee).with_context(
+						|| format!("Function {name} calls unknown function {callee}"),
+					)?;
+				anyhow::ensure!(
+					callee.params == args.len(),
+					"Function {name} calls {} with {} arguments, it takes {}",
+					callee.name,
+					args.len(),
+					callee.params
+				);
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Virtual registers read and written by the instruction.
+fn operands(instruction: &IrInstruction) -> (Vec<VReg>, Option<VReg>) {
+	match instruction {
+		IrInstruction::Const { dst, .. } => (Vec::new(), Some(*dst)),
+		IrInstruction::Copy { dst, src } => (vec![*src], Some(*dst)),
+		IrInstruction::Binary { dst, lhs, rhs, .. }
+		| IrInstruction::Compare { dst, lhs, rhs, .. } => (vec![*lhs, *rhs], Some(*dst)),
+		IrInstruction::Load8 { dst, addr } | IrInstruction::Load32 { dst, addr } => {
+			(vec![*addr], Some(*dst))
+		}
+		IrInstruction::Store8 { addr, value } | IrInstruction::Store32 { addr, value } => {
+			(vec![*addr, *value], None)
+		}
+		IrInstruction::Call { dst, args, .. } => (args.clone(), *dst),
+		IrInstruction::Syscall { arg, dst, .. } => (arg.iter().copied().collect(), *dst),
+	}
+}
+
+/// Virtual registers read by the terminator.
+fn terminator_uses(terminator: &Terminator) -> Vec<VReg> {
+	match terminator {
+		Terminator::Branch { lhs, rhs, .. } => vec![*lhs, *rhs],
+		Terminator::Return(value) => value.iter().copied().collect(),
+		Terminator::Jump(_) | Terminator::Halt => Vec::new(),
+	}
+}
+
+/// Blocks the terminator continues with.
+fn successors(terminator: &Terminator) -> impl Iterator<Item = BlockId> {
+	match terminator {
+		Terminator::Jump(target) => vec![*target],
+		Terminator::Branch { then, otherwise, .. } => vec![*then, *otherwise],
+		Terminator::Return(_) | Terminator::Halt => Vec::new(),
+	}
+	.into_iter()
+}
+
+/// Compute the live intervals of the virtual registers. Position 0 is the
+/// function entry where the parameters are defined, the instructions and
+/// terminators of the blocks follow in layout order.
+fn live_intervals(function: &IrFunction) -> Vec<Interval> {
+	let blocks = &function.blocks;
+	let terminator = |index: usize| blocks[index].terminator.as_ref().expect("validated");
+
+	// Registers read before being written and registers written per block.
+	let mut gen = vec![BTreeSet::new(); blocks.len()];
+	let mut kill = vec![BTreeSet::new(); blocks.len()];
+	for (index, block) in blocks.iter().enumerate() {
+		let accesses = block
+			.instructions
+			.iter()
+			.map(operands)
+			.chain(std::iter::once((terminator_uses(terminator(index)), None)));
+		for (uses, def) in accesses {
+			for vreg in uses {
+				if !kill[index].contains(&vreg) {
+					gen[index].insert(vreg);
+				}
+			}
+			kill[index].extend(def);
+		}
+	}
+
+	// Backwards dataflow until the live sets are stable.
+	let mut live_in = vec![BTreeSet::new(); blocks.len()];
+	let mut live_out = vec![BTreeSet::<VReg>::new(); blocks.len()];
+	let mut changed = true;
+	while changed {
+		changed = false;
+		for index in (0..blocks.len()).rev() {
+			let out = successors(terminator(index))
+				.flat_map(|successor| live_in[successor.0].iter().copied())
+				.collect::<BTreeSet<_>>();
+			let mut inputs = gen[index].clone();
+			inputs.extend(out.difference(&kill[index]).copied());
+			if inputs != live_in[index] || out != live_out[index] {
+				live_in[index] = inputs;
+				live_out[index] = out;
+				changed = true;
+			}
+		}
+	}
+
+	// Cover every access and block boundary where a register is live.
+	let mut ranges = HashMap::<VReg, (usize, usize)>::new();
+	let mut extend = |vreg: VReg, position: usize| {
+		let range = ranges.entry(vreg).or_insert((position, position));
+		range.0 = range.0.min(position);
+		range.1 = range.1.max(position);
+	};
+	for param in 0..function.params {
+		extend(VReg(param), 0);
+	}
+	let mut position = 1;
+	for (index, block) in blocks.iter().enumerate() {
+		let start = position;
+		for instruction in &block.instructions {
+			let (uses, def) = operands(instruction);
+			for vreg in uses.into_iter().chain(def) {
+				extend(vreg, position);
+			}
+			position += 1;
+		}
+		for vreg in terminator_uses(terminator(index)) {
+			extend(vreg, position);
+		}
+		for vreg in &live_in[index] {
+			extend(*vreg, start);
+		}
+		for vreg in &live_out[index] {
+			extend(*vreg, position);
+		}
+		position += 1;
+	}
+
+	let mut intervals = ranges
+		.into_iter()
+		.map(|(vreg, (start, end))| Interval { vreg, start, end })
+		.collect::<Vec<_>>();
+	intervals.sort_by_key(|interval| (interval.start, interval.vreg));
+	intervals
+}
+
+/// Allocate side registers after the frame pointer to the virtual registers
+/// with linear scan, spilling the intervals that end last to the stack.
+fn allocate(function: &IrFunction, side_registers: usize) -> Allocation {
+	let first = FRAME_POINTER + 1;
+	let last = u8::try_from(side_registers - 1).unwrap_or(u8::MAX);
+	let mut free = (first..=last).rev().collect::<Vec<_>>();
+	let mut active = Vec::<(Interval, u8)>::new();
+	let mut locations = HashMap::new();
+	let mut used = BTreeSet::new();
+	let mut slots = 0;
+
+	for interval in live_intervals(function) {
+		active.retain(|(other, register)| {
+			let expired = other.end < interval.start;
+			if expired {
+				free.push(*register);
+			}
+			!expired
+		});
+		if let Some(register) = free.pop() {
+			used.insert(register);
+			locations.insert(interval.vreg, Location::Register(register));
+			active.push((interval, register));
+			continue;
+		}
+		let furthest = active
+			.iter()
+			.enumerate()
+			.max_by_key(|(_, (other, _))| other.end)
+			.map(|(index, _)| index)
+			.filter(|index| active[*index].0.end > interval.end);
+		if let Some(index) = furthest {
+			let (spilled, register) = active.swap_remove(index);
+			locations.insert(spilled.vreg, Location::Slot(slots));
+			locations.insert(interval.vreg, Location::Register(register));
+			active.push((interval, register));
+		} else {
+			locations.insert(interval.vreg, Location::Slot(slots));
+		}
+		slots += 1;
+	}
+
+	Allocation { locations, saved: used.into_iter().collect(), slots }
+}
+
+/// Code emission of a function.
+struct Emitter<'a> {
+	/// Program to emit into.
+	program: &'a mut Program,
+	/// Function being lowered.
+	function: &'a IrFunction,
+	/// Register allocation of the function.
+	allocation: &'a Allocation,
+}
+
+impl Emitter<'_> {
+	/// Emit the function.
+	fn emit(mut self) -> anyhow::Result<()> {
+		let function = self.function;
+		let saved = &self.allocation.saved;
+		self.program.begin_function(
+			&function.name,
+			function.params,
+			self.allocation.slots + saved.len(),
+		)?;
+		for (index, register) in saved.iter().enumerate() {
+			self.add(Instruction::Set(0));
+			self.add(Instruction::Add(*register));
+			self.program.store_local(self.allocation.slots + index)?;
+		}
+		for param in 0..function.params {
+			self.program.load_arg(param)?;
+			self.store_main(self.location(VReg(param)))?;
+		}
+
+		for (index, block) in function.blocks.iter().enumerate() {
+			self.program.add_label(self.label(BlockId(index)))?;
+			for instruction in &block.instructions {
+				self.instruction(instruction)?;
+			}
+			let next = BlockId(index + 1);
+			self.terminator(block.terminator.as_ref().expect("validated"), next)?;
+		}
+		// All blocks are terminated, so the function needs no epilogue.
+		self.program.frame = None;
+		Ok(())
+	}
+
+	/// Add an instruction to the program.
+	fn add(&mut self, instruction: Instruction) {
+		self.program.add_instruction(instruction);
+	}
+
+	/// Label of the block.
+	fn label(&self, block: BlockId) -> String {
+		format!("{}.{block}", self.function.name)
+	}
+
+	/// Location of the virtual register.
+	fn location(&self, vreg: VReg) -> Location {
+		*self.allocation.locations.get(&vreg).expect("all accessed registers are allocated")
+	}
+
+	/// Load the virtual register into the main register. Clobbers side
+	/// register 3.
+	fn load_main(&mut self, vreg: VReg) -> anyhow::Result<()> {
+		match self.location(vreg) {
+			Location::Register(register) => {
+				self.add(Instruction::Set(0));
+				self.add(Instruction::Add(register));
+			}
+			Location::Slot(slot) => {
+				self.program.load_local(slot)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Store the main register into the location, clobbering the main
+	/// register and side registers 2 and 3.
+	fn store_main(&mut self, location: Location) -> anyhow::Result<()> {
+		match location {
+			Location::Register(register) => {
+				self.add(Instruction::Swap(register));
+			}
+			Location::Slot(slot) => {
+				self.program.store_local(slot)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Side register holding the value of the virtual register, loading
+	/// spilled values into the scratch register. Clobbers the main register.
+	fn operand(&mut self, vreg: VReg, scratch: u8) -> anyhow::Result<u8> {
+		match self.location(vreg) {
+			Location::Register(register) => Ok(register),
+			Location::Slot(slot) => {
+				self.program.load_local(slot)?;
+				self.add(Instruction::Swap(scratch));
+				Ok(scratch)
+			}
+		}
+	}
+
+	/// Compare the virtual registers, setting the comparison flag.
+	fn compare(&mut self, lhs: VReg, rhs: VReg) -> anyhow::Result<()> {
+		let rhs = self.operand(rhs, SCRATCH_OPERAND)?;
+		self.load_main(lhs)?;
+		self.add(Instruction::Compare(rhs));
+		Ok(())
+	}
+
+	/// Jump to the label if the last comparison satisfies the condition.
+	fn jump_if(&mut self, condition: Condition, label: &str) {
+		match condition {
+			Condition::Equal => self.program.add_jump_equal_label(label),
+			Condition::NotEqual => self.program.add_jump_not_equal_label(label),
+			Condition::Less => self.program.add_jump_less_label(label),
+			Condition::LessEqual => self.program.add_jump_less_equal_label(label),
+			Condition::Greater => self.program.add_jump_greater_label(label),
+			Condition::GreaterEqual => self.program.add_jump_greater_equal_label(label),
+		};
+	}
+
+	/// Emit an instruction.
+	fn instruction(&mut self, instruction: &IrInstruction) -> anyhow::Result<()> {
+		let (_, dst) = operands(instruction);
+		match instruction {
+			IrInstruction::Const { value, .. } => {
+				self.add(Instruction::Set(*value));
+			}
+			IrInstruction::Copy { src, .. } => self.load_main(*src)?,
+			IrInstruction::Binary {
+				op: op @ (BinaryOp::Div | BinaryOp::Rem), lhs, rhs, ..
+			} => {
+				// The division writes the remainder into the operand register,
+				// so it always works on a copy.
+				self.load_main(*rhs)?;
+				self.add(Instruction::Swap(SCRATCH_OPERAND));
+				self.load_main(*lhs)?;
+				self.add(Instruction::Div(SCRATCH_OPERAND));
+				if *op == BinaryOp::Rem {
+					self.add(Instruction::Swap(SCRATCH_OPERAND));
+				}
+			}
+			IrInstruction::Binary { op, lhs, rhs, .. } => {
+				let rhs = self.operand(*rhs, SCRATCH_OPERAND)?;
+				self.load_main(*lhs)?;
+				self.add(match op {
+					BinaryOp::Add => Instruction::Add(rhs),
+					BinaryOp::Sub => Instruction::Sub(rhs),
+					_ => Instruction::Mul(rhs),
+				});
+			}
+			IrInstruction::Compare { condition, lhs, rhs, .. } => {
+				let label = format!("{}.cmp.{}", self.function.name, self.program.len());
+				self.compare(*lhs, *rhs)?;
+				self.add(Instruction::Set(1));
+				self.jump_if(*condition, &label);
+				self.add(Instruction::Set(0));
+				self.program.add_label(label)?;
+			}
+			IrInstruction::Load8 { addr, .. } => {
+				let addr = self.operand(*addr, SCRATCH_ADDR)?;
+				self.add(Instruction::Deref8(addr));
+			}
+			IrInstruction::Load32 { addr, .. } => {
+				let addr = self.operand(*addr, SCRATCH_ADDR)?;
+				self.add(Instruction::Deref32(addr));
+			}
+			IrInstruction::Store8 { addr, value } => {
+				let addr = self.operand(*addr, SCRATCH_ADDR)?;
+				self.load_main(*value)?;
+				self.add(Instruction::Write8(addr));
+			}
+			IrInstruction::Store32 { addr, value } => {
+				let addr = self.operand(*addr, SCRATCH_ADDR)?;
+				self.load_main(*value)?;
+				self.add(Instruction::Write32(addr));
+			}
+			IrInstruction::Call { function, args, .. } => {
+				for arg in args {
+					match self.location(*arg) {
+						Location::Register(register) => {
+							self.program.push_args(&[register]);
+						}
+						Location::Slot(slot) => {
+							self.program.load_local(slot)?;
+							self.add(Instruction::Push);
+						}
+					}
+				}
+				self.program.call_function(function);
+			}
+			IrInstruction::Syscall { index, arg, .. } => {
+				if let Some(arg) = arg {
+					self.load_main(*arg)?;
+				}
+				self.program.add_syscall(*index);
+			}
+		}
+		if let Some(dst) = dst {
+			self.store_main(self.location(dst))?;
+		}
+		Ok(())
+	}
+
+	/// Emit a terminator, followed by the block `next` in the layout.
+	fn terminator(&mut self, terminator: &Terminator, next: BlockId) -> anyhow::Result<()> {
+		match terminator {
+			Terminator::Jump(target) => {
+				if *target != next {
+					self.program.add_jump_label(&self.label(*target));
+				}
+			}
+			Terminator::Branch { condition, lhs, rhs, then, otherwise } => {
+				self.compare(*lhs, *rhs)?;
+				self.jump_if(*condition, &self.label(*then));
+				if *otherwise != next {
+					self.program.add_jump_label(&self.label(*otherwise));
+				}
+			}
+			Terminator::Return(value) => {
+				match value {
+					Some(value) => self.load_main(*value)?,
+					None => {
+						self.add(Instruction::Set(0));
+					}
+				}
+				// Restore the saved side registers, keeping the result.
+				let saved = &self.allocation.saved;
+				if !saved.is_empty() {
+					self.add(Instruction::Swap(SCRATCH_OPERAND));
+					for (index, register) in saved.iter().enumerate() {
+						self.program.load_local(self.allocation.slots + index)?;
+						self.add(Instruction::Swap(*register));
+					}
+					self.add(Instruction::Swap(SCRATCH_OPERAND));
+				}
+				self.program.add_function_return()?;
+			}
+			Terminator::Halt => {
+				self.program.add_halt();
+			}
+		}
+		Ok(())
+	}
+}
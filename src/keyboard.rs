@@ -0,0 +1,132 @@
+//! Keyboard input device: a ring buffer of key-press events that the host
+//! feeds and guest programs poll with syscall 5.
+
+use std::{
+	collections::VecDeque,
+	io::{self, Read},
+	sync::{Arc, Mutex},
+	thread,
+};
+
+use crate::VmPtr;
+
+/// Key of a key-press event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+	/// Character key, including control characters like `'\n'` for enter,
+	/// `'\x1b'` for escape and `'\x08'` for backspace.
+	Char(char),
+	/// Arrow up.
+	Up,
+	/// Arrow down.
+	Down,
+	/// Arrow left.
+	Left,
+	/// Arrow right.
+	Right,
+}
+
+impl Key {
+	/// Code of the key as seen by guest programs: the Unicode code point for
+	/// characters and codes from `0x110000` on, beyond all code points, for
+	/// the arrow keys in the order up, down, left, right.
+	pub fn code(self) -> VmPtr {
+		match self {
+			Self::Char(c) => VmPtr::from(c),
+			Self::Up => 0x11_0000,
+			Self::Down => 0x11_0001,
+			Self::Left => 0x11_0002,
+			Self::Right => 0x11_0003,
+		}
+	}
+}
+
+/// Handle to a keyboard event buffer. Clones share the same buffer, so the
+/// host can keep a clone to push events from another thread while the
+/// machine is running.
+#[derive(Debug, Clone)]
+pub struct Keyboard {
+	/// Buffered key codes, oldest first.
+	events: Arc<Mutex<VecDeque<VmPtr>>>,
+	/// Maximum number of buffered events.
+	capacity: usize,
+}
+
+impl Keyboard {
+	/// Default number of events the buffer holds.
+	pub const DEFAULT_CAPACITY: usize = 64;
+
+	/// Create an empty keyboard buffer with the default capacity.
+	pub fn new() -> Self {
+		Self::with_capacity(Self::DEFAULT_CAPACITY)
+	}
+
+	/// Create an empty keyboard buffer holding up to `capacity` events.
+	pub fn with_capacity(capacity: usize) -> Self {
+		Self { events: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))), capacity }
+	}
+
+	/// Add a key-press event. Like a hardware buffer, the event is dropped if
+	/// the buffer is full. Return whether it was stored.
+	pub fn push(&self, key: Key) -> bool {
+		let mut events = self.events.lock().expect("keyboard lock poisoned");
+		if events.len() >= self.capacity {
+			return false;
+		}
+		events.push_back(key.code());
+		true
+	}
+
+	/// Take the oldest event's key code, if any.
+	pub fn pop(&self) -> Option<VmPtr> {
+		self.events.lock().expect("keyboard lock poisoned").pop_front()
+	}
+
+	/// Number of buffered events.
+	pub fn len(&self) -> usize {
+		self.events.lock().expect("keyboard lock poisoned").len()
+	}
+
+	/// Whether no events are buffered.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Feed the keyboard from the bytes of stdin in a background thread.
+	/// Bytes are Latin-1 characters and the ANSI escape sequences of the arrow
+	/// keys are translated. The terminal delivers the input line by line
+	/// unless it is in raw mode.
+	pub fn feed_from_stdin(&self) {
+		let keyboard = self.clone();
+		thread::spawn(move || {
+			let mut bytes = io::stdin().lock().bytes().map_while(Result::ok).peekable();
+			while let Some(byte) = bytes.next() {
+				let key = if byte == 0x1b && bytes.next_if_eq(&b'[').is_some() {
+					match bytes.next() {
+						Some(b'A') => Key::Up,
+						Some(b'B') => Key::Down,
+						Some(b'C') => Key::Right,
+						Some(b'D') => Key::Left,
+						_ => continue,
+					}
+				} else {
+					Key::Char(char::from(byte))
+				};
+				keyboard.push(key);
+			}
+		});
+	}
+}
+
+impl Default for Keyboard {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl PartialEq for Keyboard {
+	/// Keyboards are equal if they share the same buffer.
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.events, &other.events)
+	}
+}
@@ -1,24 +1,112 @@
+mod analysis;
+mod assembler;
+mod audio;
+mod benchmark;
+mod block_device;
+mod brainfuck;
+mod calling;
+mod compiler;
+mod coverage;
+mod debug_info;
+mod debugger;
+mod dump;
+mod executable;
+mod formatter;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+mod golden;
 mod instruction;
+pub mod ir;
+mod keyboard;
+mod linker;
+mod lint;
+mod mailbox;
+mod opcode;
+mod profile;
 mod program;
+mod repl;
+mod serial;
+mod stdlib;
+mod symbols;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+mod timer;
+mod trace;
+#[cfg(feature = "tui")]
+pub mod tui;
 mod util;
 
-use std::{cmp::Ordering, mem::size_of};
+use std::{
+	cmp::Ordering,
+	collections::{BTreeMap, BTreeSet, VecDeque},
+	io::{self, Read, Write},
+	mem::size_of,
+	ops::Range,
+	path::Path,
+};
 
 use anyhow::Context;
+use timer::Timer;
 use util::{
-	native_ptr, read_cstr, read_u16, read_u32, read_u8, read_vm_ptr, vm_ptr, write_u16, write_u32,
-	write_u8, write_vm_ptr,
+	native_ptr, read_cstr, read_str, read_str_lossy, read_u16, read_u32, read_u8, read_vm_ptr,
+	vm_ptr, write_u16, write_u32, write_u8, write_vm_ptr,
 };
 
-pub use crate::{instruction::Instruction, program::Program};
+pub use crate::{
+	analysis::Analysis,
+	assembler::Diagnostic,
+	audio::{Audio, Sound},
+	benchmark::Throughput,
+	block_device::BlockDevice,
+	brainfuck::{compile_brainfuck, BRAINFUCK_TAPE_SIZE},
+	calling::FRAME_POINTER,
+	compiler::compile,
+	coverage::Coverage,
+	debug_info::DebugInfo,
+	debugger::Debugger,
+	executable::{Executable, FORMAT_VERSION, MAGIC},
+	formatter::format_asm,
+	golden::{GoldenOutcome, GoldenTest},
+	instruction::Instruction,
+	keyboard::{Key, Keyboard},
+	linker::{Linker, Object, OBJECT_MAGIC, OBJECT_VERSION},
+	lint::Lint,
+	mailbox::{Mailbox, MailboxEndpoint},
+	opcode::{Opcode, ISA_VERSION},
+	profile::Profile,
+	program::{Program, SourceLine, MAX_ALIGNMENT},
+	repl::Repl,
+	serial::Serial,
+	stdlib::STDLIB,
+	symbols::SymbolTable,
+	timer::TimerPeriod,
+	trace::Tracer,
+};
 
 /// VM pointer size.
 pub type VmPtr = u32;
 
+/// Memory size used for executables that do not specify one.
+pub const DEFAULT_MEMORY_SIZE: VmPtr = 4096;
+
+/// Maximum length in bytes of strings read by syscalls, unless configured
+/// otherwise.
+pub const DEFAULT_MAX_STRING_LENGTH: VmPtr = 1024;
+
+/// Number of side registers of a [`Machine`] unless specified otherwise. The
+/// standard library clobbers registers 0-3, so programs calling it need at
+/// least this many; the calling convention additionally uses register 4 as
+/// frame pointer.
+pub const DEFAULT_SIDE_REGISTERS: usize = 4;
+
 /// Virtual machine for my custom binary assembler language.
 #[derive(Debug, PartialEq, Clone)]
-pub struct Machine<const SIDE_REGS: usize = 4> {
+pub struct Machine<const SIDE_REGS: usize = DEFAULT_SIDE_REGISTERS> {
 	program: Box<[u8]>,
+	data_segments: Vec<Range<VmPtr>>,
+	data_execution: bool,
+	lossy_strings: bool,
+	max_string_length: VmPtr,
 	memory: Box<[u8]>,
 	instruction_pointer: VmPtr,
 	stack_pointer: VmPtr,
@@ -26,14 +114,40 @@ pub struct Machine<const SIDE_REGS: usize = 4> {
 	side_registers: [VmPtr; SIDE_REGS],
 	flag_zero: bool,
 	flag_comparison: Ordering,
+	debug_info: Option<DebugInfo>,
+	fuel: Option<u64>,
+	breakpoints: BTreeSet<VmPtr>,
+	captured_output: Option<Vec<u8>>,
+	input: Option<VecDeque<u8>>,
+	keyboard: Option<Keyboard>,
+	block_device: Option<BlockDevice>,
+	timer: Option<Timer>,
+	audio: Option<Audio>,
+	serial: Option<Serial>,
+	mailbox: Option<MailboxEndpoint>,
+	execution_counts: Option<BTreeMap<VmPtr, u64>>,
+}
+
+/// Reason why the machine stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+	/// The machine executed a halt instruction.
+	Halted,
+	/// The next instruction is at a breakpoint.
+	Breakpoint(VmPtr),
 }
 
 impl<const SIDE_REGS: usize> Machine<SIDE_REGS> {
 	/// Create a new virtual machine with the given program and memory size.
 	/// Stack pointer is initally at the end of the memory.
 	pub fn new(program: impl Into<Box<[u8]>>, memory_size: VmPtr) -> Self {
+		let program = program.into();
 		Self {
-			program: program.into(),
+			data_segments: data_segments(&program),
+			data_execution: false,
+			lossy_strings: false,
+			max_string_length: DEFAULT_MAX_STRING_LENGTH,
+			program,
 			memory: vec![0; native_ptr(memory_size)].into(),
 			instruction_pointer: 0,
 			stack_pointer: memory_size,
@@ -41,6 +155,334 @@ impl<const SIDE_REGS: usize> Machine<SIDE_REGS> {
 			side_registers: [0; SIDE_REGS],
 			flag_zero: true,
 			flag_comparison: Ordering::Equal,
+			debug_info: None,
+			fuel: None,
+			breakpoints: BTreeSet::new(),
+			captured_output: None,
+			input: None,
+			keyboard: None,
+			block_device: None,
+			timer: None,
+			audio: None,
+			serial: None,
+			mailbox: None,
+			execution_counts: None,
+		}
+	}
+
+	/// Create a new virtual machine from an executable. Uses the memory size
+	/// hint of the executable or [`DEFAULT_MEMORY_SIZE`] and loads its entry
+	/// point, data section and debug information.
+	pub fn from_executable(executable: Executable) -> anyhow::Result<Self> {
+		if usize::from(executable.side_registers) > SIDE_REGS {
+			anyhow::bail!(
+				"Executable requires {} side registers, but the machine only has {SIDE_REGS}",
+				executable.side_registers
+			);
+		}
+		let memory_size = executable.memory_size.unwrap_or(DEFAULT_MEMORY_SIZE);
+		let mut machine =
+			Self::new(executable.code, memory_size).with_entry_point(executable.entry_point);
+		machine.load_data(executable.data_base, &executable.data)?;
+		machine.debug_info = executable.debug_info;
+		if let (None, Some(symbols)) = (&machine.debug_info, executable.symbols) {
+			machine = machine.with_symbols(symbols);
+		}
+		Ok(machine)
+	}
+
+	/// Load an executable file and create a new virtual machine from it, see
+	/// [`Self::from_executable`].
+	pub fn load_executable(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+		Self::from_executable(Executable::load(path)?)
+	}
+
+	/// Copy static data into memory at the given base address, e.g. the data
+	/// section of a [`Program`].
+	pub fn load_data(&mut self, base: VmPtr, data: &[u8]) -> anyhow::Result<()> {
+		let start = native_ptr(base);
+		self.memory
+			.get_mut(start..start + data.len())
+			.with_context(|| format!("Data section at {base} does not fit into memory"))?
+			.copy_from_slice(data);
+		Ok(())
+	}
+
+	/// Start execution at the given code address instead of the beginning of
+	/// the program, e.g. at [`Program::entry_point`].
+	pub fn with_entry_point(mut self, entry_point: VmPtr) -> Self {
+		self.instruction_pointer = entry_point;
+		self
+	}
+
+	/// Load debug information, which is used to describe the location and the
+	/// instruction of runtime errors.
+	pub fn with_debug_info(mut self, debug_info: DebugInfo) -> Self {
+		self.debug_info = Some(debug_info);
+		self
+	}
+
+	/// Load the labels of the program into the debug information, creating it
+	/// without source lines if there is none.
+	pub fn with_symbols(mut self, symbols: SymbolTable) -> Self {
+		self.debug_info.get_or_insert_with(|| DebugInfo::new("<unknown>")).set_symbols(symbols);
+		self
+	}
+
+	/// Limit the number of instructions the machine executes. Executing more
+	/// instructions is a runtime error, which protects against endless loops.
+	pub fn with_fuel(mut self, fuel: u64) -> Self {
+		self.fuel = Some(fuel);
+		self
+	}
+
+	/// Capture the output of print syscalls instead of writing it to stdout.
+	/// Retrieve it using [`Self::take_output`].
+	pub fn with_captured_output(mut self) -> Self {
+		self.captured_output = Some(Vec::new());
+		self
+	}
+
+	/// Take the output captured so far, see [`Self::with_captured_output`].
+	/// Bytes that are not valid UTF-8 are replaced, use
+	/// [`Self::take_output_bytes`] to get them as they are.
+	pub fn take_output(&mut self) -> String {
+		String::from_utf8_lossy(&self.take_output_bytes()).into_owned()
+	}
+
+	/// Take the raw bytes of the output captured so far, see
+	/// [`Self::with_captured_output`].
+	pub fn take_output_bytes(&mut self) -> Vec<u8> {
+		self.captured_output.as_mut().map(std::mem::take).unwrap_or_default()
+	}
+
+	/// Read the input of the read syscall from the given bytes instead of
+	/// stdin. The end of the bytes is the end of the input.
+	pub fn with_input(mut self, input: impl Into<Vec<u8>>) -> Self {
+		self.input = Some(input.into().into());
+		self
+	}
+
+	/// Allow executing data segments in the code as before, where the data
+	/// instruction is skipped and jumps into its payload execute the bytes as
+	/// instructions. By default, this is a runtime error.
+	pub fn with_data_execution(mut self, allowed: bool) -> Self {
+		self.data_execution = allowed;
+		self
+	}
+
+	/// Replace invalid UTF-8 in printed strings with U+FFFD instead of failing.
+	pub fn with_lossy_strings(mut self, lossy: bool) -> Self {
+		self.lossy_strings = lossy;
+		self
+	}
+
+	/// Set the maximum length in bytes of strings read by syscalls, which is
+	/// [`DEFAULT_MAX_STRING_LENGTH`] by default. Longer strings and nul
+	/// terminated strings without terminator within the limit are runtime
+	/// errors.
+	pub fn with_max_string_length(mut self, max_len: VmPtr) -> Self {
+		self.max_string_length = max_len;
+		self
+	}
+
+	/// Attach a keyboard, whose key-press events are polled with syscall 5.
+	/// Keep a clone of the keyboard to push events while the machine runs.
+	pub fn with_keyboard(mut self, keyboard: Keyboard) -> Self {
+		self.keyboard = Some(keyboard);
+		self
+	}
+
+	/// Attach a block device, whose sectors are read and written with syscalls
+	/// 6 and 7.
+	pub fn with_block_device(mut self, device: BlockDevice) -> Self {
+		self.block_device = Some(device);
+		self
+	}
+
+	/// Attach a timer raising an interrupt with the given period. Interrupts
+	/// are only taken once the program registers a handler with syscall 9.
+	pub fn with_timer(mut self, period: TimerPeriod) -> anyhow::Result<Self> {
+		self.timer = Some(Timer::new(period)?);
+		Ok(self)
+	}
+
+	/// Attach an audio device, which plays tones and samples with syscalls 12
+	/// and 13.
+	pub fn with_audio(mut self, audio: Audio) -> Self {
+		self.audio = Some(audio);
+		self
+	}
+
+	/// The attached audio device, if any.
+	pub fn audio(&self) -> Option<&Audio> {
+		self.audio.as_ref()
+	}
+
+	/// Attach a serial device, which transmits and receives bytes with
+	/// syscalls 14 and 15.
+	pub fn with_serial(mut self, serial: Serial) -> Self {
+		self.serial = Some(serial);
+		self
+	}
+
+	/// Attach a mailbox endpoint, which sends and receives messages with
+	/// syscalls 16-19.
+	pub fn with_mailbox(mut self, endpoint: MailboxEndpoint) -> Self {
+		self.mailbox = Some(endpoint);
+		self
+	}
+
+	/// Count how often the instruction at each code address is executed, e.g.
+	/// for profiling. Retrieve the counts using [`Self::execution_counts`].
+	pub fn with_execution_counts(mut self) -> Self {
+		self.execution_counts = Some(BTreeMap::new());
+		self
+	}
+
+	/// Number of executions by code address, if enabled with
+	/// [`Self::with_execution_counts`].
+	pub fn execution_counts(&self) -> Option<&BTreeMap<VmPtr, u64>> {
+		self.execution_counts.as_ref()
+	}
+
+	/// Number of instructions the machine may still execute, if limited.
+	pub fn remaining_fuel(&self) -> Option<u64> {
+		self.fuel
+	}
+
+	/// Pass arguments to the program. The arguments are stored as nul
+	/// terminated strings at the end of memory, followed by the argument vector
+	/// of pointers to them. The stack starts below, with the address of the
+	/// argument vector and then the argument count pushed onto it, so the
+	/// program can `pop` the count and then the vector.
+	pub fn with_args<S: AsRef<str>>(
+		mut self,
+		args: impl IntoIterator<Item = S>,
+	) -> anyhow::Result<Self> {
+		let args = args.into_iter().collect::<Vec<_>>();
+		let mut addr = self.stack_pointer;
+		let mut pointers = Vec::with_capacity(args.len());
+		for arg in args.iter().rev() {
+			let arg = arg.as_ref();
+			anyhow::ensure!(!arg.contains('\0'), "Program arguments cannot contain nul bytes");
+			addr = addr
+				.checked_sub(vm_ptr(arg.len() + 1))
+				.context("Program arguments do not fit into memory")?;
+			let mem = self.memory_mut(addr)?;
+			mem[..arg.len()].copy_from_slice(arg.as_bytes());
+			mem[arg.len()] = 0;
+			pointers.push(addr);
+		}
+		pointers.reverse();
+		let argv = addr
+			.checked_sub(vm_ptr(pointers.len() * size_of::<VmPtr>()))
+			.context("Program arguments do not fit into memory")?;
+		for (index, pointer) in pointers.iter().enumerate() {
+			let mem = self.memory_mut(argv + vm_ptr(index * size_of::<VmPtr>()))?;
+			write_vm_ptr(mem, *pointer)?;
+		}
+		self.stack_pointer = argv;
+		for value in [argv, vm_ptr(args.len())] {
+			self.stack_pointer = self
+				.stack_pointer
+				.checked_sub(vm_ptr(size_of::<VmPtr>()))
+				.context("Program arguments do not fit into memory")?;
+			let mem = self.memory_mut(self.stack_pointer)?;
+			write_vm_ptr(mem, value)?;
+		}
+		Ok(self)
+	}
+
+	/// Code address of the next instruction to execute.
+	pub fn instruction_pointer(&self) -> VmPtr {
+		self.instruction_pointer
+	}
+
+	/// Size of the machine memory in bytes.
+	pub fn memory_size(&self) -> VmPtr {
+		vm_ptr(self.memory.len())
+	}
+
+	/// Current stack pointer.
+	pub fn stack_pointer(&self) -> VmPtr {
+		self.stack_pointer
+	}
+
+	/// Value of the main register.
+	pub fn main_register(&self) -> VmPtr {
+		self.main_register
+	}
+
+	/// Values of the side registers.
+	pub fn side_registers(&self) -> &[VmPtr; SIDE_REGS] {
+		&self.side_registers
+	}
+
+	/// Zero flag, set by increments and decrements.
+	pub fn flag_zero(&self) -> bool {
+		self.flag_zero
+	}
+
+	/// Result of the last comparison.
+	pub fn flag_comparison(&self) -> Ordering {
+		self.flag_comparison
+	}
+
+	/// Program code the machine executes.
+	pub fn code(&self) -> &[u8] {
+		&self.program
+	}
+
+	/// Read `len` bytes of memory at the given address.
+	pub fn read_memory(&self, addr: VmPtr, len: usize) -> anyhow::Result<&[u8]> {
+		self.memory(addr)?
+			.get(..len)
+			.with_context(|| format!("Out of memory access occured at {addr} with length {len}"))
+	}
+
+	/// Stop before executing the instruction at the given code address when
+	/// running with [`Self::run_until_break`].
+	pub fn add_breakpoint(&mut self, addr: VmPtr) {
+		self.breakpoints.insert(addr);
+	}
+
+	/// Remove a breakpoint. Return whether it existed.
+	pub fn remove_breakpoint(&mut self, addr: VmPtr) -> bool {
+		self.breakpoints.remove(&addr)
+	}
+
+	/// Code addresses of all breakpoints.
+	pub fn breakpoints(&self) -> impl Iterator<Item = VmPtr> + '_ {
+		self.breakpoints.iter().copied()
+	}
+
+	/// Debug information of the loaded program, if available.
+	pub fn debug_info(&self) -> Option<&DebugInfo> {
+		self.debug_info.as_ref()
+	}
+
+	/// Describe the given code address, using the debug information if
+	/// available.
+	fn describe_location(&self, addr: VmPtr) -> String {
+		match &self.debug_info {
+			Some(debug_info) => debug_info.describe(addr),
+			None => format!("code address {addr}"),
+		}
+	}
+
+	/// Describe the location of a runtime error at the given code address.
+	/// With debug information, the decoded instruction is included as well.
+	fn describe_error_location(&self, addr: VmPtr) -> String {
+		let location = self.describe_location(addr);
+		let instruction = self
+			.debug_info
+			.as_ref()
+			.and_then(|_| self.program.get(native_ptr(addr)..))
+			.and_then(|code| Instruction::parse(code).ok());
+		match instruction {
+			Some(instruction) => format!("{location} in `{instruction}`"),
+			None => location,
 		}
 	}
 
@@ -75,32 +517,213 @@ impl<const SIDE_REGS: usize> Machine<SIDE_REGS> {
 			.with_context(|| format!("Side register {reg} out of bounds"))
 	}
 
+	/// Print program output to stdout or to the capture buffer.
+	fn print(&mut self, output: &str) -> io::Result<()> {
+		self.write_output(output.as_bytes())
+	}
+
+	/// Write raw program output bytes to stdout or to the capture buffer.
+	fn write_output(&mut self, output: &[u8]) -> io::Result<()> {
+		match &mut self.captured_output {
+			Some(captured) => {
+				captured.extend_from_slice(output);
+				Ok(())
+			}
+			None => io::stdout().write_all(output),
+		}
+	}
+
 	/// Make a syscall at the current state.
 	///
 	/// Available syscalls:
 	/// - 0: Print line with the string referenced by the main register.
 	/// - 1: Print the number in the main register.
 	/// - 2: Print the string referenced by the main registern.
+	/// - 3: Read a byte from the input into the main register, or `VmPtr::MAX`
+	///   at the end of the input.
+	/// - 4: Write the lowest byte of the main register to the output as it is.
+	/// - 5: Poll the keyboard: take the code of the oldest key-press event into
+	///   the main register, or `VmPtr::MAX` if there is none (see
+	///   [`Key::code`]).
+	/// - 6: Read the block device sector given by the main register into the
+	///   memory at the address in side register 0.
+	/// - 7: Write the memory at the address in side register 0 to the block
+	///   device sector given by the main register.
+	/// - 8: Set the main register to the number of sectors of the block device.
+	/// - 9: Register the code address in the main register as timer interrupt
+	///   handler, `VmPtr::MAX` removes the handler.
+	/// - 10: Mask the timer interrupt if the main register is not 0, otherwise
+	///   unmask it. Interrupts raised while masked are delivered when unmasked.
+	/// - 11: Return from the interrupt handler, acknowledging the interrupt:
+	///   pop the flags and the instruction pointer pushed on entry.
+	/// - 12: Play a tone with the frequency in Hz in the main register and the
+	///   duration in milliseconds in side register 0.
+	/// - 13: Play the number of unsigned 8 bit PCM samples in side register 0
+	///   from the memory at the address in the main register.
+	/// - 14: Transmit the lowest byte of the main register on the serial
+	///   device.
+	/// - 15: Receive a byte from the serial device into the main register,
+	///   blocking until one is available, or `VmPtr::MAX` at the end of the
+	///   stream.
+	/// - 16: Send the message at the address in the main register with the
+	///   length in side register 0 to the mailbox endpoint in side register 1.
+	///   Set the main register to 0, or `VmPtr::MAX` if the queue is full.
+	/// - 17: Receive the oldest message into the buffer at the address in the
+	///   main register with the size in side register 0. Set the main register
+	///   to its length and side register 1 to the sender, or the main register
+	///   to `VmPtr::MAX` if there is none.
+	/// - 18: Set the main register to the length of the oldest message, or
+	///   `VmPtr::MAX` if there is none.
+	/// - 19: Set the main register to the id of the mailbox endpoint.
+	/// - 20: Print the string at the address in the main register with the
+	///   length in bytes in side register 0.
+	/// - 21: Print the length-prefixed string at the address in the main
+	///   register: a 32 bit length in bytes followed by the string.
+	///
+	/// Strings are UTF-8, see [`Self::with_lossy_strings`] for invalid UTF-8,
+	/// and limited by [`Self::with_max_string_length`].
 	fn syscall(&mut self, index: u8) -> anyhow::Result<()> {
 		match index {
 			0 => {
-				let mem = self.memory(self.main_register)?;
-				let cstr = read_cstr(mem)?;
-				let s = cstr.to_str().with_context(|| {
-					format!("Accessed invalid string at {}", self.main_register)
-				})?;
-				println!("{s}");
+				let output = format!("{}\n", self.cstr_at(self.main_register)?);
+				self.print(&output)?;
 			}
 			1 => {
-				print!("{}", self.main_register);
+				self.print(&self.main_register.to_string())?;
 			}
 			2 => {
-				let mem = self.memory(self.main_register)?;
-				let cstr = read_cstr(mem)?;
-				let s = cstr.to_str().with_context(|| {
-					format!("Accessed invalid string at {}", self.main_register)
-				})?;
-				print!("{s}");
+				let output = self.cstr_at(self.main_register)?;
+				self.print(&output)?;
+			}
+			3 => {
+				self.main_register = match &mut self.input {
+					Some(input) => input.pop_front().map_or(VmPtr::MAX, VmPtr::from),
+					None => {
+						io::stdout().flush()?;
+						let mut byte = [0];
+						match io::stdin().read(&mut byte)? {
+							0 => VmPtr::MAX,
+							_ => VmPtr::from(byte[0]),
+						}
+					}
+				};
+			}
+			4 => {
+				self.write_output(&[self.main_register as u8])?;
+			}
+			5 => {
+				self.main_register =
+					self.keyboard.as_ref().and_then(Keyboard::pop).unwrap_or(VmPtr::MAX);
+			}
+			6 | 7 => {
+				let device = self.block_device.clone().context("No block device attached")?;
+				let sector = self.main_register;
+				let buffer = self.side_register(0)?;
+				let size = native_ptr(device.sector_size());
+				let memory = self
+					.memory_mut(buffer)?
+					.get_mut(..size)
+					.with_context(|| format!("Sector buffer at {buffer} exceeds the memory"))?;
+				if index == 6 {
+					device.read_sector(sector, memory)?;
+				} else {
+					device.write_sector(sector, memory)?;
+				}
+			}
+			8 => {
+				let device = self.block_device.as_ref().context("No block device attached")?;
+				self.main_register = device.sectors();
+			}
+			9 => {
+				let timer = self.timer.as_mut().context("No timer attached")?;
+				timer.handler = (self.main_register != VmPtr::MAX).then_some(self.main_register);
+			}
+			10 => {
+				let timer = self.timer.as_mut().context("No timer attached")?;
+				timer.masked = self.main_register != 0;
+			}
+			11 => {
+				let timer = self.timer.as_mut().context("No timer attached")?;
+				anyhow::ensure!(timer.in_service, "Return from interrupt outside of a handler");
+				timer.in_service = false;
+				(self.flag_zero, self.flag_comparison) = timer::decode_flags(self.pop_value()?)?;
+				self.instruction_pointer = self.pop_value()?;
+			}
+			12 => {
+				let audio = self.audio.as_ref().context("No audio device attached")?;
+				let duration_ms = self.side_register(0)?;
+				audio.play(Sound::Tone { frequency: self.main_register, duration_ms });
+			}
+			13 => {
+				let audio = self.audio.as_ref().context("No audio device attached")?;
+				let samples = self
+					.read_memory(self.main_register, native_ptr(self.side_register(0)?))?
+					.to_vec();
+				audio.play(Sound::Samples(samples));
+			}
+			14 => {
+				let serial = self.serial.as_ref().context("No serial device attached")?;
+				serial.transmit(self.main_register as u8).context("Serial transmit failed")?;
+			}
+			15 => {
+				let serial = self.serial.as_ref().context("No serial device attached")?;
+				io::stdout().flush()?;
+				self.main_register = serial.receive().context("Serial receive failed")?;
+			}
+			16 => {
+				let endpoint = self.mailbox.as_ref().context("No mailbox attached")?;
+				let message =
+					self.read_memory(self.main_register, native_ptr(self.side_register(0)?))?;
+				let sent = endpoint.send(self.side_register(1)?, message)?;
+				self.main_register = if sent { 0 } else { VmPtr::MAX };
+			}
+			17 => {
+				let endpoint = self.mailbox.clone().context("No mailbox attached")?;
+				let buffer = self.main_register;
+				let max_len = native_ptr(self.side_register(0)?);
+				// Check the buffer and the sender register before taking the
+				// message, so that it stays queued if they are invalid. Without
+				// a message yet, the whole buffer is checked.
+				let len = endpoint.peek_len().unwrap_or(max_len).min(max_len);
+				self.memory(buffer)?
+					.get(..len)
+					.with_context(|| format!("Message buffer at {buffer} exceeds the memory"))?;
+				self.side_register(1)?;
+				match endpoint.receive(max_len)? {
+					Some((sender, message)) => {
+						self.memory_mut(buffer)?
+							.get_mut(..message.len())
+							.with_context(|| {
+								format!("Message buffer at {buffer} exceeds the memory")
+							})?
+							.copy_from_slice(&message);
+						self.main_register = vm_ptr(message.len());
+						*self.side_register_mut(1)? = sender;
+					}
+					None => self.main_register = VmPtr::MAX,
+				}
+			}
+			18 => {
+				let endpoint = self.mailbox.as_ref().context("No mailbox attached")?;
+				self.main_register = endpoint.peek_len().map_or(VmPtr::MAX, vm_ptr);
+			}
+			19 => {
+				let endpoint = self.mailbox.as_ref().context("No mailbox attached")?;
+				self.main_register = endpoint.id();
+			}
+			20 => {
+				let len = native_ptr(self.side_register(0)?);
+				let output = self.str_at(self.main_register, len)?;
+				self.print(&output)?;
+			}
+			21 => {
+				let len = native_ptr(read_vm_ptr(self.memory(self.main_register)?)?);
+				let addr = self
+					.main_register
+					.checked_add(vm_ptr(size_of::<VmPtr>()))
+					.context("String address overflow")?;
+				let output = self.str_at(addr, len)?;
+				self.print(&output)?;
 			}
 			_ => return Err(anyhow::format_err!("Unknown syscall {index}")),
 		}
@@ -109,14 +732,108 @@ impl<const SIDE_REGS: usize> Machine<SIDE_REGS> {
 
 	/// Run a step of the virtual machine. Return whether the execution should
 	/// continue.
-	#[allow(clippy::unnecessary_cast, clippy::useless_conversion)] // For future compatibility, when changing VmPtr.
 	pub fn step(&mut self) -> anyhow::Result<bool> {
+		if let Some(handler) = self.timer.as_mut().and_then(Timer::tick) {
+			self.interrupt(handler).with_context(|| {
+				format!("Cannot enter interrupt handler at {}", self.describe_location(handler))
+			})?;
+		}
+		let ip = self.instruction_pointer;
+		if let Some(fuel) = &mut self.fuel {
+			if *fuel == 0 {
+				anyhow::bail!("Out of fuel at {}", self.describe_location(ip));
+			}
+			*fuel -= 1;
+		}
+		if let Some(counts) = &mut self.execution_counts {
+			*counts.entry(ip).or_default() += 1;
+		}
+		self.execute_step()
+			.with_context(|| format!("Runtime error at {}", self.describe_error_location(ip)))
+	}
+
+	/// Read the nul terminated string at the address for printing.
+	fn cstr_at(&self, addr: VmPtr) -> anyhow::Result<String> {
+		let bytes = read_cstr(self.memory(addr)?, native_ptr(self.max_string_length))
+			.with_context(|| format!("Accessed invalid string at {addr}"))?
+			.to_bytes();
+		self.str_at(addr, bytes.len())
+	}
+
+	/// Read the string of the given length in bytes at the address for
+	/// printing.
+	fn str_at(&self, addr: VmPtr, len: usize) -> anyhow::Result<String> {
+		let max_len = native_ptr(self.max_string_length);
+		anyhow::ensure!(
+			len <= max_len,
+			"String of {len} bytes at {addr} exceeds the maximum length of {max_len} bytes"
+		);
+		let mem = self.memory(addr)?;
+		let string = if self.lossy_strings {
+			read_str_lossy(mem, len)?.into_owned()
+		} else {
+			read_str(mem, len)
+				.with_context(|| format!("Accessed invalid string at {addr}"))?
+				.to_owned()
+		};
+		Ok(string)
+	}
+
+	/// Enter the interrupt handler: push the instruction pointer and the flags
+	/// and continue at the handler.
+	fn interrupt(&mut self, handler: VmPtr) -> anyhow::Result<()> {
+		self.push_value(self.instruction_pointer)?;
+		self.push_value(timer::encode_flags(self.flag_zero, self.flag_comparison))?;
+		self.instruction_pointer = handler;
+		Ok(())
+	}
+
+	/// Push the value onto the stack.
+	fn push_value(&mut self, value: VmPtr) -> anyhow::Result<()> {
+		self.stack_pointer =
+			self.stack_pointer.checked_sub(vm_ptr(size_of::<VmPtr>())).context("Stack overflow")?;
+		let mem = self.memory_mut(self.stack_pointer)?;
+		write_vm_ptr(mem, value)
+	}
+
+	/// Pop a value from the stack.
+	fn pop_value(&mut self) -> anyhow::Result<VmPtr> {
+		let value = read_vm_ptr(self.memory(self.stack_pointer)?)?;
+		self.stack_pointer = self
+			.stack_pointer
+			.checked_add(vm_ptr(size_of::<VmPtr>()))
+			.context("Stack underflow")?;
+		Ok(value)
+	}
+
+	/// Execute the instruction at the instruction pointer. Return whether the
+	/// execution should continue.
+	fn execute_step(&mut self) -> anyhow::Result<bool> {
+		let ip = self.instruction_pointer;
+		if !self.data_execution {
+			let segment = self.data_segments.partition_point(|segment| segment.end <= ip);
+			if self.data_segments.get(segment).is_some_and(|segment| segment.contains(&ip)) {
+				anyhow::bail!("Executed data as code at {ip}");
+			}
+		}
 		let code = self
 			.program
 			.get(native_ptr(self.instruction_pointer)..)
 			.context("Instruction pointer is outside of program code")?;
 		let instruction = Instruction::parse(code).context("Failed parsing instruction")?;
-		self.instruction_pointer += vm_ptr(instruction.size());
+		self.execute_instruction(instruction)
+	}
+
+	/// Execute the given instruction as if it was located at the instruction
+	/// pointer, independent of the program code. This is useful to experiment
+	/// with instructions interactively. Return whether the execution should
+	/// continue.
+	#[allow(clippy::unnecessary_cast, clippy::useless_conversion)] // For future compatibility, when changing VmPtr.
+	pub fn execute_instruction(&mut self, instruction: Instruction) -> anyhow::Result<bool> {
+		self.instruction_pointer = self
+			.instruction_pointer
+			.checked_add(vm_ptr(instruction.size()))
+			.context("Instruction pointer overflow")?;
 		match instruction {
 			Instruction::Nop | Instruction::Data(_, _) => {}
 			Instruction::Halt => return Ok(false),
@@ -351,4 +1068,33 @@ impl<const SIDE_REGS: usize> Machine<SIDE_REGS> {
 		while self.step()? {}
 		Ok(())
 	}
+
+	/// Run the virtual machine until it halts (or errors) or the next
+	/// instruction is at a breakpoint. At least one instruction is executed,
+	/// so that execution can be continued from a breakpoint.
+	pub fn run_until_break(&mut self) -> anyhow::Result<StopReason> {
+		loop {
+			if !self.step()? {
+				return Ok(StopReason::Halted);
+			}
+			if self.breakpoints.contains(&self.instruction_pointer) {
+				return Ok(StopReason::Breakpoint(self.instruction_pointer));
+			}
+		}
+	}
+}
+
+/// Code address ranges of the data segments in the code, found by decoding it
+/// from the start. Decoding stops at the first invalid instruction.
+fn data_segments(code: &[u8]) -> Vec<Range<VmPtr>> {
+	let mut segments = Vec::new();
+	let mut addr = 0;
+	while let Some(instruction) = code.get(addr..).and_then(|code| Instruction::parse(code).ok()) {
+		let size = instruction.size();
+		if let Instruction::Data(..) = instruction {
+			segments.push(vm_ptr(addr)..vm_ptr(addr + size));
+		}
+		addr += size;
+	}
+	segments
 }
@@ -0,0 +1,267 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+	assembler::Assembler,
+	util::{native_ptr, read_bytes, read_u16, read_u8, read_vm_ptr, vm_ptr, write_vm_ptr},
+	Executable, SymbolTable, VmPtr,
+};
+
+/// Magic bytes at the start of every object file.
+pub const OBJECT_MAGIC: [u8; 4] = *b"MYVO";
+/// Current version of the object format.
+pub const OBJECT_VERSION: u16 = 1;
+
+/// Relocatable object: compiled code with all code addresses relative to the
+/// start of the object, plus the information to combine it with other objects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Object {
+	/// Number of side registers the code requires.
+	pub side_registers: u16,
+	/// Compiled code.
+	pub code: Vec<u8>,
+	/// Code address of the entry point, if the object defines one.
+	pub entry_point: Option<VmPtr>,
+	/// Labels defined in the object: code address and whether it is exported.
+	pub symbols: BTreeMap<String, (VmPtr, bool)>,
+	/// Code offsets of address operands that are relative to the object start.
+	pub relocations: Vec<VmPtr>,
+	/// Code offsets of address operands that refer to symbols of other
+	/// objects.
+	pub imports: Vec<(VmPtr, String)>,
+}
+
+impl Object {
+	/// Assemble text assembly into an object. Jump and call targets that are
+	/// not defined in the input become imports. Use `global <label>` to only
+	/// export specific labels, otherwise all labels are exported.
+	pub fn assemble(input: &str) -> anyhow::Result<Self> {
+		Assembler::assemble_object(input)
+	}
+
+	/// Serialize the object to bytes.
+	///
+	/// Layout (big endian): magic, version (u16), side registers (u16), entry
+	/// point (u32, `VmPtr::MAX` if none), code length (u32) and code, then the
+	/// symbols, relocations and imports, each as count (u32) followed by the
+	/// entries. Names are stored as length (u16) and UTF-8 bytes.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		bytes.extend_from_slice(&OBJECT_MAGIC);
+		bytes.extend_from_slice(&OBJECT_VERSION.to_be_bytes());
+		bytes.extend_from_slice(&self.side_registers.to_be_bytes());
+		bytes.extend_from_slice(&self.entry_point.unwrap_or(VmPtr::MAX).to_be_bytes());
+		bytes.extend_from_slice(&vm_ptr(self.code.len()).to_be_bytes());
+		bytes.extend_from_slice(&self.code);
+		bytes.extend_from_slice(&vm_ptr(self.symbols.len()).to_be_bytes());
+		for (name, (addr, global)) in &self.symbols {
+			bytes.extend_from_slice(&addr.to_be_bytes());
+			bytes.push(u8::from(*global));
+			push_name(&mut bytes, name);
+		}
+		bytes.extend_from_slice(&vm_ptr(self.relocations.len()).to_be_bytes());
+		for offset in &self.relocations {
+			bytes.extend_from_slice(&offset.to_be_bytes());
+		}
+		bytes.extend_from_slice(&vm_ptr(self.imports.len()).to_be_bytes());
+		for (offset, name) in &self.imports {
+			bytes.extend_from_slice(&offset.to_be_bytes());
+			push_name(&mut bytes, name);
+		}
+		bytes
+	}
+
+	/// Parse an object from bytes.
+	pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+		let mut reader = Reader { bytes };
+		if reader.bytes(OBJECT_MAGIC.len())? != OBJECT_MAGIC {
+			anyhow::bail!("Not an object: invalid magic bytes");
+		}
+		let version = reader.u16()?;
+		if version > OBJECT_VERSION {
+			anyhow::bail!("Unsupported object format version {version}");
+		}
+		let mut object = Self { side_registers: reader.u16()?, ..Self::default() };
+		object.entry_point = Some(reader.vm_ptr()?).filter(|entry| *entry != VmPtr::MAX);
+		let code_len = reader.vm_ptr()?;
+		object.code = reader.bytes(native_ptr(code_len))?.to_vec();
+		for _ in 0..reader.vm_ptr()? {
+			let addr = reader.vm_ptr()?;
+			let global = reader.u8()? != 0;
+			object.symbols.insert(reader.name()?, (addr, global));
+		}
+		for _ in 0..reader.vm_ptr()? {
+			object.relocations.push(reader.vm_ptr()?);
+		}
+		for _ in 0..reader.vm_ptr()? {
+			let offset = reader.vm_ptr()?;
+			object.imports.push((offset, reader.name()?));
+		}
+		Ok(object)
+	}
+
+	/// Write the object to a file.
+	pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+		let path = path.as_ref();
+		std::fs::write(path, self.to_bytes())
+			.with_context(|| format!("Cannot write object {}", path.display()))
+	}
+
+	/// Read an object from a file.
+	pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+		let path = path.as_ref();
+		let bytes = std::fs::read(path)
+			.with_context(|| format!("Cannot read object {}", path.display()))?;
+		Self::from_bytes(&bytes).with_context(|| format!("Invalid object {}", path.display()))
+	}
+}
+
+/// Append a length prefixed name to the buffer.
+fn push_name(bytes: &mut Vec<u8>, name: &str) {
+	let len = u16::try_from(name.len()).expect("symbol names are shorter than 64 KiB");
+	bytes.extend_from_slice(&len.to_be_bytes());
+	bytes.extend_from_slice(name.as_bytes());
+}
+
+/// Sequential reader over the object bytes.
+struct Reader<'a> {
+	/// Remaining bytes.
+	bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	/// Read the given amount of bytes.
+	fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+		let bytes = read_bytes(self.bytes, len).context("Object is truncated")?;
+		self.bytes = &self.bytes[len..];
+		Ok(bytes)
+	}
+
+	/// Read an u8.
+	fn u8(&mut self) -> anyhow::Result<u8> {
+		read_u8(self.bytes(1)?)
+	}
+
+	/// Read an u16.
+	fn u16(&mut self) -> anyhow::Result<u16> {
+		read_u16(self.bytes(2)?)
+	}
+
+	/// Read a VmPtr.
+	fn vm_ptr(&mut self) -> anyhow::Result<VmPtr> {
+		read_vm_ptr(self.bytes(4)?)
+	}
+
+	/// Read a length prefixed name.
+	fn name(&mut self) -> anyhow::Result<String> {
+		let len = self.u16()?;
+		let name = self.bytes(len.into())?;
+		String::from_utf8(name.to_vec()).context("Invalid symbol name")
+	}
+}
+
+/// Linker combining multiple objects into one executable. The objects are
+/// placed in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct Linker {
+	/// Objects to link.
+	objects: Vec<Object>,
+}
+
+impl Linker {
+	/// Create new linker without objects.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add an object to link.
+	pub fn add_object(&mut self, object: Object) -> &mut Self {
+		self.objects.push(object);
+		self
+	}
+
+	/// Link the objects: place them after each other, rebase their code
+	/// addresses and resolve imports against the exported symbols. The entry
+	/// point is taken from the single object that defines one, or the start of
+	/// the code otherwise. Local symbols are kept in the symbol table if their
+	/// names are unique.
+	pub fn link(&self) -> anyhow::Result<Executable> {
+		let mut bases = Vec::with_capacity(self.objects.len());
+		let mut code_size: VmPtr = 0;
+		for object in &self.objects {
+			bases.push(code_size);
+			code_size = code_size
+				.checked_add(vm_ptr(object.code.len()))
+				.context("Linked code exceeds the address space")?;
+		}
+
+		let mut globals = HashMap::new();
+		let mut locals = HashMap::<&str, Vec<VmPtr>>::new();
+		for (index, (object, base)) in self.objects.iter().zip(&bases).enumerate() {
+			for (name, (addr, global)) in &object.symbols {
+				let addr = base
+					.checked_add(*addr)
+					.with_context(|| format!("Symbol {name} exceeds the address space"))?;
+				if *global {
+					if let Some((other, _)) = globals.insert(name.as_str(), (index, addr)) {
+						anyhow::bail!("Symbol {name} is exported by objects {other} and {index}");
+					}
+				} else {
+					locals.entry(name.as_str()).or_default().push(addr);
+				}
+			}
+		}
+
+		let mut executable = Executable::default();
+		let mut entry_point = None;
+		for (index, (object, base)) in self.objects.iter().zip(&bases).enumerate() {
+			let start = executable.code.len();
+			executable.code.extend_from_slice(&object.code);
+			let code = &mut executable.code[start..];
+			for offset in &object.relocations {
+				let operand = code
+					.get_mut(native_ptr(*offset)..)
+					.with_context(|| format!("Invalid relocation in object {index}"))?;
+				let addr = read_vm_ptr(operand)?.checked_add(*base).with_context(|| {
+					format!("Relocation in object {index} exceeds the address space")
+				})?;
+				write_vm_ptr(operand, addr)?;
+			}
+			for (offset, name) in &object.imports {
+				let (_, addr) = globals.get(name.as_str()).with_context(|| {
+					format!("Undefined symbol {name} referenced by object {index}")
+				})?;
+				let operand = code
+					.get_mut(native_ptr(*offset)..)
+					.with_context(|| format!("Invalid import in object {index}"))?;
+				write_vm_ptr(operand, *addr)?;
+			}
+			if let Some(entry) = object.entry_point {
+				let entry = base.checked_add(entry).with_context(|| {
+					format!("Entry point of object {index} exceeds the address space")
+				})?;
+				if let Some((other, _)) = entry_point.replace((index, entry)) {
+					anyhow::bail!("Entry point is defined by objects {other} and {index}");
+				}
+			}
+			executable.side_registers = executable.side_registers.max(object.side_registers);
+		}
+		executable.entry_point = entry_point.map_or(0, |(_, entry)| entry);
+
+		let mut symbols = SymbolTable::new();
+		for (name, (_, addr)) in &globals {
+			symbols.insert(*name, *addr);
+		}
+		for (name, addrs) in locals {
+			if let ([addr], false) = (addrs.as_slice(), globals.contains_key(name)) {
+				symbols.insert(name, *addr);
+			}
+		}
+		executable.symbols = Some(symbols);
+		Ok(executable)
+	}
+}
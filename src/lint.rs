@@ -0,0 +1,173 @@
+//! Lint pass finding likely mistakes in programs.
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt,
+};
+
+use crate::{Instruction, Program, VmPtr};
+
+/// Warning about a likely mistake in a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+	/// Index of the instruction the warning is about.
+	pub index: usize,
+	/// Source line of the instruction, if it was parsed from text assembly.
+	pub line: Option<usize>,
+	/// Description of the problem.
+	pub message: String,
+}
+
+impl fmt::Display for Lint {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.line {
+			Some(line) => write!(f, "line {line}: {}", self.message),
+			None => write!(f, "instruction {}: {}", self.index, self.message),
+		}
+	}
+}
+
+impl Program {
+	/// Find likely mistakes: unused labels, unreachable code, execution
+	/// falling through into data segments, side registers beyond the declared
+	/// register count (if given) and functions with unbalanced pushes and
+	/// pops. Code from included files is not reported. The warnings are
+	/// sorted by instruction index.
+	pub fn lint(&self, side_registers: Option<usize>) -> Vec<Lint> {
+		let instructions = self.iter().collect::<Vec<_>>();
+		let index_of = instructions
+			.iter()
+			.enumerate()
+			.map(|(index, (addr, _))| (*addr, index))
+			.collect::<BTreeMap<_, _>>();
+		let lint = |index: usize, message: String| Lint {
+			index,
+			line: self.source(index).map(|source| source.line),
+			message,
+		};
+		let mut lints = Vec::new();
+
+		// Labels that are no target of any instruction, the entry point or
+		// exported. Copies refer to the payload of data segments, so the
+		// start of the instruction containing the address counts as used.
+		let mut used = instructions
+			.iter()
+			.filter_map(|(_, instruction)| instruction.code_address())
+			.filter_map(|addr| index_of.range(..=addr).next_back().map(|(start, _)| *start))
+			.collect::<BTreeSet<_>>();
+		used.insert(self.entry_point());
+		let symbols = self.symbols();
+		used.extend(self.globals().filter_map(|name| symbols.address(name)));
+		for (name, addr) in symbols.iter() {
+			if let Some(index) = index_of.get(&addr).filter(|_| !used.contains(&addr)) {
+				lints.push(lint(*index, format!("Label {name} is never used")));
+			}
+		}
+
+		// Unreachable code, reported once per block.
+		let unreachable = self
+			.unreachable_instructions()
+			.into_iter()
+			.filter(|index| !matches!(instructions[*index].1, Instruction::Data(_, _)))
+			.collect::<BTreeSet<_>>();
+		for index in &unreachable {
+			if *index == 0 || !unreachable.contains(&(index - 1)) {
+				lints.push(lint(*index, "Unreachable code".to_owned()));
+			}
+		}
+
+		for (index, pair) in instructions.windows(2).enumerate() {
+			let [(_, instruction), (_, next)] = pair else { continue };
+			let stops = matches!(
+				instruction,
+				Instruction::Jump(_)
+					| Instruction::Halt
+					| Instruction::Return
+					| Instruction::Data(_, _)
+			);
+			if !stops && matches!(next, Instruction::Data(_, _)) && !unreachable.contains(&index) {
+				lints.push(lint(index, "Execution falls through into a data segment".to_owned()));
+			}
+		}
+
+		if let Some(count) = side_registers {
+			for (index, (_, instruction)) in instructions.iter().enumerate() {
+				let Some(register) = instruction.side_register() else { continue };
+				if usize::from(register) >= count {
+					lints.push(lint(
+						index,
+						format!(
+							"Side register {register} is beyond the {count} declared registers"
+						),
+					));
+				}
+			}
+		}
+
+		let functions = instructions
+			.iter()
+			.filter_map(|(_, instruction)| match instruction {
+				Instruction::Call(addr) => index_of.get(addr).copied(),
+				_ => None,
+			})
+			.collect::<BTreeSet<_>>();
+		for function in functions {
+			let name = symbols.name_at(instructions[function].0).unwrap_or("<unnamed>");
+			if let Some((index, message)) = stack_balance(&instructions, &index_of, function) {
+				lints.push(lint(index, format!("Function {name}: {message}")));
+			}
+		}
+
+		// Included files, e.g. the standard library, are linted on their own.
+		lints.retain(|lint| self.source(lint.index).is_none_or(|source| source.file.is_none()));
+		lints.sort_by_key(|lint| lint.index);
+		lints
+	}
+}
+
+/// Check that every path through the function starting at the given index
+/// pops as many values as it pushes before returning. Return the index and
+/// description of the first problem.
+fn stack_balance(
+	instructions: &[(VmPtr, &Instruction)],
+	index_of: &BTreeMap<VmPtr, usize>,
+	function: usize,
+) -> Option<(usize, String)> {
+	let mut depths = BTreeMap::<usize, i64>::new();
+	let mut pending = vec![(function, 0_i64)];
+	while let Some((index, depth)) = pending.pop() {
+		let Some((_, instruction)) = instructions.get(index) else { continue };
+		match depths.insert(index, depth) {
+			Some(previous) if previous == depth => continue,
+			Some(_) => return Some((index, "stack depth differs between paths".to_owned())),
+			None => {}
+		}
+		let next = index + 1;
+		match instruction {
+			Instruction::Push | Instruction::PushRegister(_) => pending.push((next, depth + 1)),
+			Instruction::Pop | Instruction::PopRegister(_) => pending.push((next, depth - 1)),
+			Instruction::Return if depth > 0 => {
+				return Some((index, format!("{depth} more pushes than pops at return")));
+			}
+			Instruction::Return if depth < 0 => {
+				return Some((index, format!("{} more pops than pushes at return", -depth)));
+			}
+			// Stack pointer manipulation cannot be followed.
+			Instruction::Halt | Instruction::Return | Instruction::WriteStackPointer => {}
+			Instruction::Jump(addr) => pending.extend(index_of.get(addr).map(|&to| (to, depth))),
+			Instruction::JumpEqual(addr)
+			| Instruction::JumpNotEqual(addr)
+			| Instruction::JumpGreater(addr)
+			| Instruction::JumpLess(addr)
+			| Instruction::JumpGreaterEqual(addr)
+			| Instruction::JumpLessEqual(addr)
+			| Instruction::JumpZero(addr)
+			| Instruction::JumpNonzero(addr) => {
+				pending.extend(index_of.get(addr).map(|&to| (to, depth)));
+				pending.push((next, depth));
+			}
+			_ => pending.push((next, depth)),
+		}
+	}
+	None
+}
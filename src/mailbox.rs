@@ -0,0 +1,144 @@
+//! Mailbox device for messages between machines, accessed with syscalls
+//! 16-19.
+
+use std::{
+	collections::VecDeque,
+	sync::{Arc, Condvar, Mutex, MutexGuard},
+};
+
+use crate::{
+	util::{native_ptr, vm_ptr},
+	VmPtr,
+};
+
+/// Queued message: sender endpoint and bytes.
+type Message = (VmPtr, Vec<u8>);
+
+/// State shared by all endpoints of a mailbox.
+#[derive(Debug, Default)]
+struct Shared {
+	/// Incoming messages of every endpoint, indexed by endpoint id.
+	queues: Mutex<Vec<VecDeque<Message>>>,
+	/// Notified whenever a message is sent or received.
+	changed: Condvar,
+}
+
+/// Mailbox that machines attach to with their own [`MailboxEndpoint`]. Each
+/// endpoint has a queue of incoming messages with limited capacity. Clones
+/// share the same mailbox.
+#[derive(Debug, Clone)]
+pub struct Mailbox {
+	/// Queues shared by the endpoints.
+	shared: Arc<Shared>,
+	/// Maximum number of queued messages per endpoint.
+	capacity: usize,
+	/// Whether sending to a full queue and receiving from an empty queue
+	/// block instead of failing.
+	blocking: bool,
+}
+
+impl Mailbox {
+	/// Maximum size of a message in bytes.
+	pub const MAX_MESSAGE_SIZE: usize = 256;
+
+	/// Create a non-blocking mailbox where each endpoint can queue up to
+	/// `capacity` incoming messages.
+	pub fn new(capacity: usize) -> Self {
+		Self { shared: Arc::default(), capacity, blocking: false }
+	}
+
+	/// Make sending to a full queue and receiving from an empty queue wait
+	/// instead of failing. The machines then need to run in separate threads.
+	pub fn with_blocking(mut self, blocking: bool) -> Self {
+		self.blocking = blocking;
+		self
+	}
+
+	/// Create a new endpoint with the next id, starting at 0.
+	pub fn endpoint(&self) -> MailboxEndpoint {
+		let mut queues = self.queues();
+		queues.push(VecDeque::new());
+		MailboxEndpoint { mailbox: self.clone(), id: vm_ptr(queues.len() - 1) }
+	}
+
+	/// Lock the queues.
+	fn queues(&self) -> MutexGuard<'_, Vec<VecDeque<Message>>> {
+		self.shared.queues.lock().expect("mailbox lock poisoned")
+	}
+}
+
+/// Endpoint of a [`Mailbox`], identified by its id. Attach it to a machine or
+/// use it from the host to talk to machines.
+#[derive(Debug, Clone)]
+pub struct MailboxEndpoint {
+	/// Mailbox of the endpoint.
+	mailbox: Mailbox,
+	/// Id of the endpoint, the index of its queue.
+	id: VmPtr,
+}
+
+impl MailboxEndpoint {
+	/// Id of the endpoint that other endpoints send to.
+	pub fn id(&self) -> VmPtr {
+		self.id
+	}
+
+	/// Send a message to the endpoint with id `to`. Return whether it was
+	/// queued, which fails if the queue is full and the mailbox is
+	/// non-blocking.
+	pub fn send(&self, to: VmPtr, message: &[u8]) -> anyhow::Result<bool> {
+		anyhow::ensure!(
+			message.len() <= Mailbox::MAX_MESSAGE_SIZE,
+			"Message of {} bytes exceeds the maximum of {} bytes",
+			message.len(),
+			Mailbox::MAX_MESSAGE_SIZE
+		);
+		let mailbox = &self.mailbox;
+		let mut queues = mailbox.queues();
+		let index = usize::try_from(to).ok().filter(|index| *index < queues.len());
+		let index = index.ok_or_else(|| anyhow::format_err!("Unknown mailbox endpoint {to}"))?;
+		while queues[index].len() >= mailbox.capacity {
+			if !mailbox.blocking {
+				return Ok(false);
+			}
+			queues = mailbox.shared.changed.wait(queues).expect("mailbox lock poisoned");
+		}
+		queues[index].push_back((self.id, message.to_vec()));
+		mailbox.shared.changed.notify_all();
+		Ok(true)
+	}
+
+	/// Receive the oldest incoming message with its sender id. Return `None`
+	/// if there is none and the mailbox is non-blocking. Fails without
+	/// removing the message if it is longer than `max_len`.
+	pub fn receive(&self, max_len: usize) -> anyhow::Result<Option<(VmPtr, Vec<u8>)>> {
+		let mailbox = &self.mailbox;
+		let mut queues = mailbox.queues();
+		while mailbox.blocking && queues[native_ptr(self.id)].is_empty() {
+			queues = mailbox.shared.changed.wait(queues).expect("mailbox lock poisoned");
+		}
+		let queue = &mut queues[native_ptr(self.id)];
+		if let Some((_, message)) = queue.front() {
+			anyhow::ensure!(
+				message.len() <= max_len,
+				"Message of {} bytes does not fit into the buffer of {max_len} bytes",
+				message.len()
+			);
+		}
+		let message = queue.pop_front();
+		mailbox.shared.changed.notify_all();
+		Ok(message)
+	}
+
+	/// Length of the oldest incoming message, if any, without blocking.
+	pub fn peek_len(&self) -> Option<usize> {
+		self.mailbox.queues()[native_ptr(self.id)].front().map(|(_, message)| message.len())
+	}
+}
+
+impl PartialEq for MailboxEndpoint {
+	/// Endpoints are equal if they are the same endpoint of the same mailbox.
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.mailbox.shared, &other.mailbox.shared) && self.id == other.id
+	}
+}
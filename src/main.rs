@@ -1,12 +1,829 @@
+use std::{
+	fs::{self, File},
+	io::{self, BufWriter, Read, Write},
+	net::TcpStream,
+	path::{Path, PathBuf},
+	str::FromStr,
+	thread,
+	time::{Duration, SystemTime},
+};
+
 use anyhow::Context;
-use my_vm::{Machine, Program};
+use clap::{Args, Parser, Subcommand};
+use my_vm::{
+	compile, compile_brainfuck, format_asm, BlockDevice, Coverage, Debugger, Executable,
+	GoldenTest, Keyboard, Machine, Profile, Program, Repl, Serial, SymbolTable, Throughput,
+	TimerPeriod, Tracer, VmPtr, BRAINFUCK_TAPE_SIZE, DEFAULT_MAX_STRING_LENGTH,
+	DEFAULT_MEMORY_SIZE, MAGIC,
+};
+
+/// Number of side registers the CLI runs programs with by default. This is
+/// more than [`my_vm::DEFAULT_SIDE_REGISTERS`], so programs using the frame
+/// pointer of the calling convention or registers allocated by the IR lowering
+/// run without `--registers`.
+const CLI_SIDE_REGISTERS: usize = 8;
+
+/// Assembler and virtual machine for my custom assembly language.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+	/// Command to execute.
+	#[command(subcommand)]
+	command: Command,
+}
+
+/// Commands of the CLI.
+#[derive(Debug, Subcommand)]
+enum Command {
+	/// Run a program, either text assembly (`.asm`) or an executable.
+	Run {
+		/// Program file.
+		file: PathBuf,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+		#[command(flatten)]
+		machine: MachineArgs,
+		#[command(flatten)]
+		dump: DumpArgs,
+		#[command(flatten)]
+		trace: TraceArgs,
+		/// Watch the file and re-assemble and re-run the program whenever it
+		/// changes.
+		#[arg(long)]
+		watch: bool,
+	},
+	/// Assemble a program into an executable.
+	Build {
+		/// Text assembly file.
+		file: PathBuf,
+		/// Output file, defaults to the input file with the `.bin` extension.
+		#[arg(short, long)]
+		output: Option<PathBuf>,
+		/// Compress the data section.
+		#[cfg(feature = "compression")]
+		#[arg(long)]
+		compress: bool,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+	},
+	/// Compile a program of the high-level language into an executable. It
+	/// needs at least 2 side registers to run.
+	Compile {
+		/// Source file.
+		file: PathBuf,
+		/// Output file, defaults to the input file with the `.bin` or `.asm`
+		/// extension.
+		#[arg(short, long)]
+		output: Option<PathBuf>,
+		/// Write text assembly instead of an executable.
+		#[arg(long)]
+		asm: bool,
+	},
+	/// Run a Brainfuck program, reading its input from stdin.
+	#[command(alias = "bf")]
+	Brainfuck {
+		/// Brainfuck source file.
+		file: PathBuf,
+		/// Number of cells of the tape.
+		#[arg(long, default_value_t = BRAINFUCK_TAPE_SIZE)]
+		tape_size: VmPtr,
+		/// Maximum number of instructions to execute.
+		#[arg(long)]
+		fuel: Option<u64>,
+		/// Print the generated text assembly instead of running it.
+		#[arg(long)]
+		asm: bool,
+	},
+	/// Print a program as text assembly.
+	Disasm {
+		/// Program file.
+		file: PathBuf,
+		/// Print the listing with code addresses and encoded bytes instead.
+		#[arg(long)]
+		listing: bool,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+	},
+	/// Debug a program interactively: step, continue, set breakpoints and
+	/// inspect registers, memory and the stack.
+	Debug {
+		/// Program file.
+		file: PathBuf,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+		#[command(flatten)]
+		machine: MachineArgs,
+	},
+	/// Run a program and print the most executed instructions and labels.
+	Profile {
+		/// Program file.
+		file: PathBuf,
+		/// Number of instructions and labels to show.
+		#[arg(long, default_value_t = 10)]
+		top: usize,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+		#[command(flatten)]
+		machine: MachineArgs,
+	},
+	/// Run a program repeatedly with captured output and print the executed
+	/// instructions per second.
+	Bench {
+		/// Program file.
+		file: PathBuf,
+		/// Number of runs.
+		#[arg(long, default_value_t = 10)]
+		iterations: u64,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+		#[command(flatten)]
+		machine: MachineArgs,
+	},
+	/// Run a program and print its listing annotated with how often every
+	/// instruction was executed, followed by the uncovered labels.
+	Coverage {
+		/// Program file.
+		file: PathBuf,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+		#[command(flatten)]
+		machine: MachineArgs,
+	},
+	/// Run all `.asm` files in a directory and compare their output to the
+	/// adjacent `.expected` files or their `#expect:` comments.
+	Test {
+		/// Directory with the tests.
+		dir: PathBuf,
+		/// Number of side registers.
+		#[arg(long, default_value_t = CLI_SIDE_REGISTERS)]
+		registers: usize,
+		/// Maximum number of instructions each program may execute.
+		#[arg(long, default_value_t = 1_000_000)]
+		fuel: u64,
+	},
+	/// Format text assembly files in place.
+	Fmt {
+		/// Text assembly files.
+		#[arg(required = true)]
+		files: Vec<PathBuf>,
+		/// Only check whether the files are formatted, without changing them.
+		#[arg(long)]
+		check: bool,
+	},
+	/// Warn about likely mistakes in a text assembly program.
+	Lint {
+		/// Text assembly file.
+		file: PathBuf,
+		/// Number of side registers the program may use.
+		#[arg(long)]
+		registers: Option<usize>,
+	},
+	/// Estimate the maximum stack depth and the memory size a program needs.
+	Analyze {
+		/// Program file.
+		file: PathBuf,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+	},
+	/// Execute instructions interactively, printing the changes to registers,
+	/// flags and memory.
+	Repl {
+		/// Memory size in bytes.
+		#[arg(long, default_value_t = DEFAULT_MEMORY_SIZE)]
+		memory_size: VmPtr,
+		/// Number of side registers.
+		#[arg(long, default_value_t = CLI_SIDE_REGISTERS)]
+		registers: usize,
+	},
+	/// Debug a program in a full-screen terminal user interface.
+	#[cfg(feature = "tui")]
+	Tui {
+		/// Program file.
+		file: PathBuf,
+		#[command(flatten)]
+		assemble: AssembleArgs,
+		#[command(flatten)]
+		machine: MachineArgs,
+	},
+}
+
+/// Options for assembling text assembly.
+#[derive(Debug, Args)]
+struct AssembleArgs {
+	/// Merge identical data segments.
+	#[arg(long)]
+	pool_data: bool,
+	/// Remove unreachable code and unused data segments.
+	#[arg(long)]
+	remove_dead_code: bool,
+	/// Apply all optimizations, i.e. `--pool-data` and `--remove-dead-code`.
+	#[arg(short = 'O', long)]
+	optimize: bool,
+}
+
+/// Options for the virtual machine.
+#[derive(Debug, Args)]
+struct MachineArgs {
+	/// Memory size in bytes, defaults to the size suggested by the program or
+	/// 4096.
+	#[arg(long)]
+	memory_size: Option<VmPtr>,
+	/// Number of side registers.
+	#[arg(long, default_value_t = CLI_SIDE_REGISTERS)]
+	registers: usize,
+	/// Load the file as raw bytecode without executable header, starting
+	/// execution at code address 0.
+	#[arg(long)]
+	raw: bool,
+	/// Maximum number of instructions to execute.
+	#[arg(long)]
+	fuel: Option<u64>,
+	/// Skip data segments in the code when executing them instead of failing,
+	/// like older versions did.
+	#[arg(long)]
+	allow_data_execution: bool,
+	/// Replace invalid UTF-8 in printed strings instead of failing.
+	#[arg(long)]
+	lossy_strings: bool,
+	/// Maximum length in bytes of strings read by syscalls.
+	#[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_STRING_LENGTH)]
+	max_string_length: VmPtr,
+	/// Attach a keyboard fed with the bytes of stdin, polled with syscall 5.
+	#[arg(long)]
+	keyboard: bool,
+	/// Attach a block device backed by the file, accessed with syscalls 6-8.
+	#[arg(long, value_name = "FILE")]
+	disk: Option<PathBuf>,
+	/// Sector size of the block device in bytes.
+	#[arg(long, default_value_t = BlockDevice::DEFAULT_SECTOR_SIZE, requires = "disk")]
+	sector_size: VmPtr,
+	/// Create or resize the block device file to the number of sectors.
+	#[arg(long, value_name = "N", requires = "disk")]
+	disk_sectors: Option<VmPtr>,
+	/// Raise a timer interrupt every n executed instructions.
+	#[arg(long, value_name = "N", conflicts_with = "timer_ms")]
+	timer_instructions: Option<u64>,
+	/// Raise a timer interrupt every n milliseconds.
+	#[arg(long, value_name = "N")]
+	timer_ms: Option<u64>,
+	/// Attach a serial device (syscalls 14 and 15) connected to stdin and
+	/// stdout, or to the TCP address.
+	#[arg(long, value_name = "stdio|HOST:PORT")]
+	serial: Option<SerialTarget>,
+	/// Play the audio device (syscalls 12 and 13) on the default output device.
+	#[cfg(feature = "audio")]
+	#[arg(long)]
+	audio: bool,
+	/// Arguments passed to the program.
+	#[arg(last = true)]
+	args: Vec<String>,
+}
+
+/// Options for dumping the machine state after execution.
+#[derive(Debug, Args)]
+struct DumpArgs {
+	/// Print a hexdump of memory after execution, given as `<addr>..<len>`,
+	/// e.g. `0x100..64`. Can be given multiple times.
+	#[arg(long, value_name = "ADDR..LEN")]
+	dump_memory: Vec<MemoryRange>,
+	/// Print the registers and flags after execution.
+	#[arg(long)]
+	dump_registers: bool,
+	/// Print the full machine state after execution: registers, flags, the
+	/// next instruction, the stack and the non-zero memory.
+	#[arg(long)]
+	dump_state: bool,
+}
+
+/// Options for tracing the execution.
+#[derive(Debug, Args)]
+struct TraceArgs {
+	/// Write a JSON line per executed instruction to the file.
+	#[arg(long, value_name = "FILE")]
+	trace: Option<PathBuf>,
+	/// Only trace every n-th executed instruction.
+	#[arg(long, value_name = "N", default_value_t = 1, requires = "trace")]
+	trace_every: u64,
+	/// Maximum number of traced instructions.
+	#[arg(long, value_name = "N", requires = "trace")]
+	trace_limit: Option<u64>,
+}
+
+/// Memory range to dump.
+#[derive(Debug, Clone, Copy)]
+struct MemoryRange {
+	/// Start address.
+	addr: VmPtr,
+	/// Number of bytes.
+	len: VmPtr,
+}
+
+impl FromStr for MemoryRange {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (addr, len) = s.split_once("..").context("Expected `<addr>..<len>`")?;
+		Ok(Self { addr: parse_number(addr)?, len: parse_number(len)? })
+	}
+}
+
+/// Host streams the serial device is connected to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SerialTarget {
+	/// Stdin and stdout.
+	Stdio,
+	/// TCP connection to the address.
+	Tcp(String),
+}
+
+impl FromStr for SerialTarget {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"stdio" => Ok(Self::Stdio),
+			addr if addr.contains(':') => Ok(Self::Tcp(addr.to_owned())),
+			_ => anyhow::bail!("Expected `stdio` or `<host>:<port>`"),
+		}
+	}
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal number.
+fn parse_number(s: &str) -> anyhow::Result<VmPtr> {
+	let s = s.trim();
+	match s.strip_prefix("0x") {
+		Some(hex) => VmPtr::from_str_radix(hex, 16),
+		None => s.parse(),
+	}
+	.with_context(|| format!("Invalid number `{s}`"))
+}
+
+/// Call the function with the machine's number of side registers as const
+/// generic argument.
+macro_rules! with_side_registers {
+	($count:expr, $function:ident($($arg:expr),*)) => {
+		match $count {
+			0 => $function::<0>($($arg),*),
+			1 => $function::<1>($($arg),*),
+			2 => $function::<2>($($arg),*),
+			3 => $function::<3>($($arg),*),
+			4 => $function::<4>($($arg),*),
+			5 => $function::<5>($($arg),*),
+			6 => $function::<6>($($arg),*),
+			7 => $function::<7>($($arg),*),
+			8 => $function::<8>($($arg),*),
+			16 => $function::<16>($($arg),*),
+			32 => $function::<32>($($arg),*),
+			64 => $function::<64>($($arg),*),
+			128 => $function::<128>($($arg),*),
+			256 => $function::<256>($($arg),*),
+			count => Err(anyhow::format_err!(
+				"Unsupported number of side registers: {count} (supported are 0 to 8, 16, 32, 64, \
+				 128 and 256)"
+			)),
+		}
+	};
+}
 
 fn main() -> anyhow::Result<()> {
-	let asm = std::fs::read_to_string("./program.asm").context("Cannot read ./program.asm file")?;
-	let program = asm.parse::<Program>()?;
-	let executable = program.compile();
+	match Cli::parse().command {
+		Command::Run { file, assemble, machine, dump, trace, watch: true } => watch(&file, || {
+			let executable = load(&file, &assemble, machine.raw)?;
+			with_side_registers!(machine.registers, run(executable, &machine, &dump, &trace))
+		}),
+		Command::Run { file, assemble, machine, dump, trace, watch: false } => {
+			let executable = load(&file, &assemble, machine.raw)?;
+			with_side_registers!(machine.registers, run(executable, &machine, &dump, &trace))
+		}
+		Command::Build {
+			file,
+			output,
+			#[cfg(feature = "compression")]
+			compress,
+			assemble,
+		} => {
+			#[allow(unused_mut)] // Only modified with compression.
+			let mut executable = assemble_file(&file, &assemble)?.to_executable()?;
+			#[cfg(feature = "compression")]
+			{
+				executable.compress_data = compress;
+			}
+			save(&executable, &output.unwrap_or_else(|| file.with_extension("bin")))
+		}
+		Command::Brainfuck { file, tape_size, fuel, asm } => {
+			let source = fs::read_to_string(&file)
+				.with_context(|| format!("Cannot read {}", file.display()))?;
+			let program = compile_brainfuck(&source, tape_size)
+				.with_context(|| format!("In {}", file.display()))?;
+			if asm {
+				print!("{}", program.to_asm()?);
+				return Ok(());
+			}
+			let mut machine = Machine::<2>::from_executable(program.to_executable()?)?;
+			if let Some(fuel) = fuel {
+				machine = machine.with_fuel(fuel);
+			}
+			machine.run()
+		}
+		Command::Compile { file, output, asm } => {
+			let source = fs::read_to_string(&file)
+				.with_context(|| format!("Cannot read {}", file.display()))?;
+			let program = compile(&source).with_context(|| format!("In {}", file.display()))?;
+			if asm {
+				let output = output.unwrap_or_else(|| file.with_extension("asm"));
+				fs::write(&output, program.to_asm()?)
+					.with_context(|| format!("Cannot write {}", output.display()))?;
+				println!("Wrote {}", output.display());
+				Ok(())
+			} else {
+				save(
+					&program.to_executable()?,
+					&output.unwrap_or_else(|| file.with_extension("bin")),
+				)
+			}
+		}
+		Command::Disasm { file, listing, assemble } => {
+			let program = if is_assembly(&file)? {
+				assemble_file(&file, &assemble)?
+			} else {
+				Program::from_executable(&Executable::load(&file)?)?
+			};
+			if listing {
+				print!("{}", program.listing());
+			} else {
+				print!("{}", program.to_asm()?);
+			}
+			Ok(())
+		}
+		Command::Debug { file, assemble, machine } => {
+			let executable = load(&file, &assemble, machine.raw)?;
+			with_side_registers!(machine.registers, debug(executable, &machine))
+		}
+		Command::Profile { file, top, assemble, machine } => {
+			let executable = load(&file, &assemble, machine.raw)?;
+			with_side_registers!(machine.registers, profile(executable, &machine, top))
+		}
+		Command::Bench { file, iterations, assemble, machine } => {
+			let executable = load(&file, &assemble, machine.raw)?;
+			with_side_registers!(machine.registers, bench(executable, &machine, iterations))
+		}
+		Command::Coverage { file, assemble, machine } => {
+			let (program, executable) = if machine.raw {
+				let executable = load(&file, &assemble, true)?;
+				(Program::disassemble(&executable.code)?, executable)
+			} else if is_assembly(&file)? {
+				let program = assemble_file(&file, &assemble)?;
+				let executable = program.to_executable()?;
+				(program, executable)
+			} else {
+				let executable = Executable::load(&file)?;
+				(Program::from_executable(&executable)?, executable)
+			};
+			with_side_registers!(machine.registers, coverage(&program, executable, &machine))
+		}
+		Command::Test { dir, registers, fuel } => {
+			with_side_registers!(registers, golden_tests(&dir, fuel))
+		}
+		Command::Fmt { files, check } => {
+			let mut unformatted = 0;
+			for file in &files {
+				let source = fs::read_to_string(file)
+					.with_context(|| format!("Cannot read {}", file.display()))?;
+				let formatted = format_asm(&source);
+				if formatted == source {
+					continue;
+				}
+				if check {
+					println!("{} is not formatted", file.display());
+					unformatted += 1;
+				} else {
+					fs::write(file, formatted)
+						.with_context(|| format!("Cannot write {}", file.display()))?;
+				}
+			}
+			anyhow::ensure!(unformatted == 0, "{unformatted} files are not formatted");
+			Ok(())
+		}
+		Command::Lint { file, registers } => {
+			let lints = Program::from_file(&file)?.lint(registers);
+			for lint in &lints {
+				match lint.line {
+					Some(line) => println!("{}:{line}: {}", file.display(), lint.message),
+					None => println!("{}: {lint}", file.display()),
+				}
+			}
+			anyhow::ensure!(lints.is_empty(), "{} warnings", lints.len());
+			Ok(())
+		}
+		Command::Analyze { file, assemble } => {
+			let program = if is_assembly(&file)? {
+				assemble_file(&file, &assemble)?
+			} else {
+				Program::from_executable(&Executable::load(&file)?)?
+			};
+			println!("{}", program.analyze());
+			Ok(())
+		}
+		Command::Repl { memory_size, registers } => {
+			with_side_registers!(registers, repl(memory_size))
+		}
+		#[cfg(feature = "tui")]
+		Command::Tui { file, assemble, machine } => {
+			let executable = load(&file, &assemble, machine.raw)?;
+			with_side_registers!(machine.registers, tui(executable, &machine))
+		}
+	}
+}
+
+/// Save the executable and print its size.
+fn save(executable: &Executable, output: &Path) -> anyhow::Result<()> {
+	executable.save(output)?;
+	println!(
+		"Wrote {}: {} bytes code, {} bytes data, {} symbols, {} bytes total",
+		output.display(),
+		executable.code.len(),
+		executable.data.len(),
+		executable.symbols.as_ref().map_or(0, SymbolTable::len),
+		executable.to_bytes().len()
+	);
+	Ok(())
+}
+
+/// Whether the file is text assembly instead of an executable, detected by
+/// the magic bytes of executables.
+fn is_assembly(file: &Path) -> anyhow::Result<bool> {
+	let mut magic = Vec::with_capacity(MAGIC.len());
+	File::open(file)
+		.and_then(|file| file.take(MAGIC.len() as u64).read_to_end(&mut magic))
+		.with_context(|| format!("Cannot read {}", file.display()))?;
+	Ok(!Executable::is_executable(&magic))
+}
+
+/// Read and assemble a text assembly file.
+fn assemble_file(file: &Path, options: &AssembleArgs) -> anyhow::Result<Program> {
+	let mut program = Program::from_file(file)?;
+	if options.pool_data || options.optimize {
+		program.pool_data();
+	}
+	if options.remove_dead_code || options.optimize {
+		program.remove_dead_code();
+	}
+	Ok(program)
+}
+
+/// Load a program file as executable, assembling it if necessary.
+fn load(file: &Path, options: &AssembleArgs, raw: bool) -> anyhow::Result<Executable> {
+	if raw {
+		let code = fs::read(file).with_context(|| format!("Cannot read {}", file.display()))?;
+		Ok(Executable::from_code(code))
+	} else if is_assembly(file)? {
+		assemble_file(file, options)?.to_executable()
+	} else {
+		Executable::load(file)
+	}
+}
+
+/// Create a machine with the given number of side registers for the
+/// executable.
+fn machine<const SIDE_REGS: usize>(
+	mut executable: Executable,
+	options: &MachineArgs,
+) -> anyhow::Result<Machine<SIDE_REGS>> {
+	anyhow::ensure!(
+		!options.keyboard || options.serial != Some(SerialTarget::Stdio),
+		"The keyboard and the serial device cannot both read stdin"
+	);
+	if options.memory_size.is_some() {
+		executable.memory_size = options.memory_size;
+	}
+	let mut machine =
+		Machine::<SIDE_REGS>::from_executable(executable)?.with_args(&options.args)?;
+	if let Some(fuel) = options.fuel {
+		machine = machine.with_fuel(fuel);
+	}
+	machine = machine
+		.with_data_execution(options.allow_data_execution)
+		.with_lossy_strings(options.lossy_strings)
+		.with_max_string_length(options.max_string_length);
+	if options.keyboard {
+		let keyboard = Keyboard::new();
+		keyboard.feed_from_stdin();
+		machine = machine.with_keyboard(keyboard);
+	}
+	if let Some(disk) = &options.disk {
+		let device = match options.disk_sectors {
+			Some(sectors) => BlockDevice::create(disk, options.sector_size, sectors)?,
+			None => BlockDevice::open(disk, options.sector_size)?,
+		};
+		machine = machine.with_block_device(device);
+	}
+	if let Some(n) = options.timer_instructions {
+		machine = machine.with_timer(TimerPeriod::Instructions(n))?;
+	} else if let Some(ms) = options.timer_ms {
+		machine = machine.with_timer(TimerPeriod::Milliseconds(ms))?;
+	}
+	match &options.serial {
+		Some(SerialTarget::Stdio) => machine = machine.with_serial(Serial::stdio()),
+		Some(SerialTarget::Tcp(addr)) => {
+			let stream =
+				TcpStream::connect(addr).with_context(|| format!("Cannot connect to {addr}"))?;
+			machine = machine.with_serial(Serial::tcp(stream)?);
+		}
+		None => {}
+	}
+	#[cfg(feature = "audio")]
+	if options.audio {
+		machine = machine.with_audio(my_vm::Audio::playback()?);
+	}
+	Ok(machine)
+}
+
+/// Run the executable on a machine with the given number of side registers.
+/// Afterwards, dump the requested machine state, even if execution failed.
+fn run<const SIDE_REGS: usize>(
+	executable: Executable,
+	options: &MachineArgs,
+	dump: &DumpArgs,
+	trace: &TraceArgs,
+) -> anyhow::Result<()> {
+	let mut machine = machine::<SIDE_REGS>(executable, options)?;
+	let result = match &trace.trace {
+		Some(path) => {
+			let file = File::create(path)
+				.with_context(|| format!("Cannot create trace file {}", path.display()))?;
+			let mut tracer = Tracer::new(BufWriter::new(file)).with_sampling(trace.trace_every);
+			if let Some(limit) = trace.trace_limit {
+				tracer = tracer.with_limit(limit);
+			}
+			tracer.run(&mut machine)
+		}
+		None => machine.run(),
+	};
+	if let Some(audio) = machine.audio() {
+		audio.wait();
+	}
+	if dump.dump_state {
+		eprintln!("{machine}");
+	}
+	let debugger = Debugger::new(machine);
+	if dump.dump_registers {
+		eprintln!("{}", debugger.registers());
+	}
+	for range in &dump.dump_memory {
+		match debugger.hexdump(range.addr, range.len) {
+			Ok(hexdump) => eprintln!("{hexdump}"),
+			Err(err) => eprintln!("Cannot dump memory: {err:#}"),
+		}
+	}
+	result
+}
+
+/// Call `action` now and again every time the file or one of the files it
+/// includes is modified, until the process is interrupted. Errors are printed
+/// instead of ending the loop.
+fn watch(file: &Path, mut action: impl FnMut() -> anyhow::Result<()>) -> anyhow::Result<()> {
+	for run in 1.. {
+		let mut files = vec![file.to_path_buf()];
+		if is_assembly(file).unwrap_or(false) {
+			if let Ok(program) = Program::from_file(file) {
+				files.extend_from_slice(program.included_files());
+			}
+		}
+		let modified = || -> Vec<Option<SystemTime>> {
+			files
+				.iter()
+				.map(|file| fs::metadata(file).and_then(|meta| meta.modified()).ok())
+				.collect()
+		};
+		let last = modified();
 
-	let mut machine = Machine::<8>::new(executable, 4096);
+		eprintln!("=== Run {run} ===");
+		let result = action();
+		io::stdout().flush()?;
+		match result {
+			Ok(()) => eprintln!("\n=== Finished, waiting for changes ==="),
+			Err(err) => eprintln!("\nError: {err:#}\n=== Failed, waiting for changes ==="),
+		}
+		while modified() == last {
+			thread::sleep(Duration::from_millis(200));
+		}
+		// Give editors time to finish writing the file.
+		thread::sleep(Duration::from_millis(100));
+	}
+	Ok(())
+}
+
+/// Run the executable counting executions, then print the hotspot report.
+fn profile<const SIDE_REGS: usize>(
+	executable: Executable,
+	options: &MachineArgs,
+	top: usize,
+) -> anyhow::Result<()> {
+	let mut machine = machine::<SIDE_REGS>(executable, options)?.with_execution_counts();
 	machine.run()?;
+	println!("\n{}", Profile::from_machine(&machine)?.report(top));
 	Ok(())
 }
+
+/// Run the executable repeatedly and print its throughput.
+fn bench<const SIDE_REGS: usize>(
+	executable: Executable,
+	options: &MachineArgs,
+	iterations: u64,
+) -> anyhow::Result<()> {
+	let machine = machine::<SIDE_REGS>(executable, options)?.with_captured_output();
+	println!("{}", Throughput::measure(&machine, iterations)?);
+	Ok(())
+}
+
+/// Run the executable counting executions, then print the coverage report of
+/// the program. The report is printed even if execution failed.
+fn coverage<const SIDE_REGS: usize>(
+	program: &Program,
+	executable: Executable,
+	options: &MachineArgs,
+) -> anyhow::Result<()> {
+	let mut machine = machine::<SIDE_REGS>(executable, options)?.with_execution_counts();
+	let result = machine.run();
+	println!("\n{}", Coverage::from_machine(&machine)?.report(program));
+	result
+}
+
+/// Run the golden tests in the directory, report failures with diffs and fail
+/// if any test failed.
+fn golden_tests<const SIDE_REGS: usize>(dir: &Path, fuel: u64) -> anyhow::Result<()> {
+	let tests = GoldenTest::discover(dir)?;
+	let mut failed = 0;
+	for test in &tests {
+		let outcome = test.run::<SIDE_REGS>(fuel);
+		if outcome.passed() {
+			println!("ok     {}", outcome.path.display());
+			continue;
+		}
+		failed += 1;
+		println!("FAILED {}", outcome.path.display());
+		if let Some(error) = &outcome.error {
+			println!("  Error: {error}");
+		}
+		if outcome.output != outcome.expected {
+			for line in outcome.diff().lines() {
+				println!("  {line}");
+			}
+		}
+	}
+	println!("\n{} passed, {failed} failed", tests.len() - failed);
+	anyhow::ensure!(failed == 0, "{failed} of {} tests failed", tests.len());
+	Ok(())
+}
+
+/// Debug the executable interactively, reading debugger commands from stdin.
+fn debug<const SIDE_REGS: usize>(
+	executable: Executable,
+	options: &MachineArgs,
+) -> anyhow::Result<()> {
+	let mut debugger = Debugger::new(machine::<SIDE_REGS>(executable, options)?);
+	println!("{}", debugger.execute("where")?);
+	prompt("(my_vm) ", |command| debugger.execute(command))
+}
+
+/// Execute instructions interactively on a fresh machine.
+fn repl<const SIDE_REGS: usize>(memory_size: VmPtr) -> anyhow::Result<()> {
+	let mut repl = Repl::<SIDE_REGS>::new(memory_size);
+	println!("Enter instructions to execute them, see `.help` for commands.");
+	prompt("> ", |line| repl.execute(line))
+}
+
+/// Read lines from stdin and print the output of executing them, until
+/// `quit` or the end of input.
+fn prompt(
+	prompt: &str,
+	mut execute: impl FnMut(&str) -> anyhow::Result<String>,
+) -> anyhow::Result<()> {
+	let mut line = String::new();
+	loop {
+		print!("{prompt}");
+		io::stdout().flush()?;
+		line.clear();
+		if io::stdin().read_line(&mut line)? == 0 {
+			return Ok(());
+		}
+		match line.trim() {
+			"quit" | "q" | ".quit" => return Ok(()),
+			command => match execute(command) {
+				Ok(output) if output.is_empty() => {}
+				Ok(output) => println!("{output}"),
+				Err(err) => println!("Error: {err:#}"),
+			},
+		}
+	}
+}
+
+/// Debug the executable in the full-screen terminal user interface.
+#[cfg(feature = "tui")]
+fn tui<const SIDE_REGS: usize>(
+	executable: Executable,
+	options: &MachineArgs,
+) -> anyhow::Result<()> {
+	let machine = machine::<SIDE_REGS>(executable, options)?.with_captured_output();
+	my_vm::tui::run(&mut Debugger::new(machine))
+}
@@ -0,0 +1,203 @@
+/// Current version of the instruction set. It is stored in executables and
+/// must be increased whenever opcodes change, so that older code can still be
+/// decoded by [`Opcode::from_versioned`].
+pub const ISA_VERSION: u16 = 1;
+
+/// Opcode of an [`Instruction`](crate::Instruction), the first byte of its
+/// encoding. The numbering is stable, new opcodes are only ever appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Opcode {
+	/// See [`Instruction::Nop`](crate::Instruction::Nop).
+	Nop = 0,
+	/// See [`Instruction::Halt`](crate::Instruction::Halt).
+	Halt = 1,
+	/// See [`Instruction::Load8`](crate::Instruction::Load8).
+	Load8 = 2,
+	/// See [`Instruction::Store8`](crate::Instruction::Store8).
+	Store8 = 3,
+	/// See [`Instruction::Load16`](crate::Instruction::Load16).
+	Load16 = 4,
+	/// See [`Instruction::Store16`](crate::Instruction::Store16).
+	Store16 = 5,
+	/// See [`Instruction::Load32`](crate::Instruction::Load32).
+	Load32 = 6,
+	/// See [`Instruction::Store32`](crate::Instruction::Store32).
+	Store32 = 7,
+	/// See [`Instruction::Set`](crate::Instruction::Set).
+	Set = 8,
+	/// See [`Instruction::Deref8`](crate::Instruction::Deref8).
+	Deref8 = 9,
+	/// See [`Instruction::Deref16`](crate::Instruction::Deref16).
+	Deref16 = 10,
+	/// See [`Instruction::Deref32`](crate::Instruction::Deref32).
+	Deref32 = 11,
+	/// See [`Instruction::Syscall`](crate::Instruction::Syscall).
+	Syscall = 12,
+	/// See [`Instruction::CopyCodeMemory`](crate::Instruction::CopyCodeMemory).
+	CopyCodeMemory = 13,
+	/// See [`Instruction::Data`](crate::Instruction::Data).
+	Data = 14,
+	/// See [`Instruction::Swap`](crate::Instruction::Swap).
+	Swap = 15,
+	/// See [`Instruction::Write8`](crate::Instruction::Write8).
+	Write8 = 16,
+	/// See [`Instruction::Write16`](crate::Instruction::Write16).
+	Write16 = 17,
+	/// See [`Instruction::Write32`](crate::Instruction::Write32).
+	Write32 = 18,
+	/// See [`Instruction::ReadStackPointer`](crate::Instruction::ReadStackPointer).
+	ReadStackPointer = 19,
+	/// See [`Instruction::WriteStackPointer`](crate::Instruction::WriteStackPointer).
+	WriteStackPointer = 20,
+	/// See [`Instruction::Jump`](crate::Instruction::Jump).
+	Jump = 21,
+	/// See [`Instruction::Call`](crate::Instruction::Call).
+	Call = 22,
+	/// See [`Instruction::Return`](crate::Instruction::Return).
+	Return = 23,
+	/// See [`Instruction::Increment`](crate::Instruction::Increment).
+	Increment = 24,
+	/// See [`Instruction::Decrement`](crate::Instruction::Decrement).
+	Decrement = 25,
+	/// See [`Instruction::Add`](crate::Instruction::Add).
+	Add = 26,
+	/// See [`Instruction::Sub`](crate::Instruction::Sub).
+	Sub = 27,
+	/// See [`Instruction::Compare`](crate::Instruction::Compare).
+	Compare = 28,
+	/// See [`Instruction::JumpEqual`](crate::Instruction::JumpEqual).
+	JumpEqual = 29,
+	/// See [`Instruction::JumpNotEqual`](crate::Instruction::JumpNotEqual).
+	JumpNotEqual = 30,
+	/// See [`Instruction::JumpGreater`](crate::Instruction::JumpGreater).
+	JumpGreater = 31,
+	/// See [`Instruction::JumpLess`](crate::Instruction::JumpLess).
+	JumpLess = 32,
+	/// See [`Instruction::JumpGreaterEqual`](crate::Instruction::JumpGreaterEqual).
+	JumpGreaterEqual = 33,
+	/// See [`Instruction::JumpLessEqual`](crate::Instruction::JumpLessEqual).
+	JumpLessEqual = 34,
+	/// See [`Instruction::JumpZero`](crate::Instruction::JumpZero).
+	JumpZero = 35,
+	/// See [`Instruction::JumpNonzero`](crate::Instruction::JumpNonzero).
+	JumpNonzero = 36,
+	/// See [`Instruction::Push`](crate::Instruction::Push).
+	Push = 37,
+	/// See [`Instruction::Pop`](crate::Instruction::Pop).
+	Pop = 38,
+	/// See [`Instruction::PushRegister`](crate::Instruction::PushRegister).
+	PushRegister = 39,
+	/// See [`Instruction::PopRegister`](crate::Instruction::PopRegister).
+	PopRegister = 40,
+	/// See [`Instruction::Mul`](crate::Instruction::Mul).
+	Mul = 41,
+	/// See [`Instruction::Div`](crate::Instruction::Div).
+	Div = 42,
+	/// See [`Instruction::IncrementRegister`](crate::Instruction::IncrementRegister).
+	IncrementRegister = 43,
+	/// See [`Instruction::DecrementRegister`](crate::Instruction::DecrementRegister).
+	DecrementRegister = 44,
+	/// See [`Instruction::SetRegister`](crate::Instruction::SetRegister).
+	SetRegister = 45,
+}
+
+impl Opcode {
+	/// Highest assigned opcode.
+	pub const LAST: Self = Self::SetRegister;
+
+	/// All opcodes, indexed by their numbering. Decoding looks opcodes up
+	/// here, the compile time check below keeps it in sync with the
+	/// discriminants.
+	pub const ALL: [Self; Self::LAST as usize + 1] = [
+		Self::Nop,
+		Self::Halt,
+		Self::Load8,
+		Self::Store8,
+		Self::Load16,
+		Self::Store16,
+		Self::Load32,
+		Self::Store32,
+		Self::Set,
+		Self::Deref8,
+		Self::Deref16,
+		Self::Deref32,
+		Self::Syscall,
+		Self::CopyCodeMemory,
+		Self::Data,
+		Self::Swap,
+		Self::Write8,
+		Self::Write16,
+		Self::Write32,
+		Self::ReadStackPointer,
+		Self::WriteStackPointer,
+		Self::Jump,
+		Self::Call,
+		Self::Return,
+		Self::Increment,
+		Self::Decrement,
+		Self::Add,
+		Self::Sub,
+		Self::Compare,
+		Self::JumpEqual,
+		Self::JumpNotEqual,
+		Self::JumpGreater,
+		Self::JumpLess,
+		Self::JumpGreaterEqual,
+		Self::JumpLessEqual,
+		Self::JumpZero,
+		Self::JumpNonzero,
+		Self::Push,
+		Self::Pop,
+		Self::PushRegister,
+		Self::PopRegister,
+		Self::Mul,
+		Self::Div,
+		Self::IncrementRegister,
+		Self::DecrementRegister,
+		Self::SetRegister,
+	];
+
+	/// Decode an opcode of code that was compiled for the given instruction set
+	/// version, mapping opcodes of older versions to the current numbering.
+	/// Opcodes that no longer exist and newer versions are rejected.
+	pub fn from_versioned(opcode: u8, isa_version: u16) -> anyhow::Result<Self> {
+		match isa_version {
+			0 => anyhow::bail!("Invalid instruction set version 0"),
+			// Mappings of older versions go here once the numbering changes, e.g.
+			// `(1, 46) => Ok(Self::Foo)` or `(1, 47) => bail!(...)` for removed
+			// instructions.
+			ISA_VERSION => Self::try_from(opcode),
+			_ => anyhow::bail!(
+				"Code uses instruction set version {isa_version}, but only up to \
+				 {ISA_VERSION} is supported"
+			),
+		}
+	}
+}
+
+// Every opcode is at the index of its discriminant.
+const _: () = {
+	let mut index = 0;
+	while index < Opcode::ALL.len() {
+		assert!(Opcode::ALL[index] as usize == index, "Opcode::ALL is out of order");
+		index += 1;
+	}
+};
+
+impl TryFrom<u8> for Opcode {
+	type Error = anyhow::Error;
+
+	fn try_from(opcode: u8) -> Result<Self, Self::Error> {
+		Self::ALL
+			.get(usize::from(opcode))
+			.copied()
+			.ok_or_else(|| anyhow::format_err!("Unrecognized instruction: {opcode}"))
+	}
+}
+
+impl From<Opcode> for u8 {
+	fn from(opcode: Opcode) -> Self {
+		opcode as u8
+	}
+}
@@ -0,0 +1,102 @@
+//! Hotspot report from the execution counts of a machine.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::Context;
+
+use crate::{util::native_ptr, DebugInfo, Instruction, Machine, VmPtr};
+
+/// Profile of a program run: how often each instruction was executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+	/// Number of executions by code address.
+	counts: BTreeMap<VmPtr, u64>,
+	/// Program code, to show the instructions.
+	code: Box<[u8]>,
+	/// Debug information, to group instructions by labels.
+	debug_info: Option<DebugInfo>,
+}
+
+impl Profile {
+	/// Create the profile from a machine that counted executions, see
+	/// [`Machine::with_execution_counts`].
+	pub fn from_machine<const SIDE_REGS: usize>(
+		machine: &Machine<SIDE_REGS>,
+	) -> anyhow::Result<Self> {
+		let counts = machine
+			.execution_counts()
+			.context("Execution counts are not enabled on the machine")?
+			.clone();
+		Ok(Self { counts, code: machine.code().into(), debug_info: machine.debug_info().cloned() })
+	}
+
+	/// Total number of executed instructions.
+	pub fn total(&self) -> u64 {
+		self.counts.values().sum()
+	}
+
+	/// Executed code addresses and their execution counts, most executed
+	/// first.
+	pub fn hottest_instructions(&self) -> Vec<(VmPtr, u64)> {
+		let mut instructions =
+			self.counts.iter().map(|(addr, count)| (*addr, *count)).collect::<Vec<_>>();
+		instructions.sort_by(|(_, a), (_, b)| b.cmp(a));
+		instructions
+	}
+
+	/// Execution counts grouped by the closest label at or before each
+	/// instruction, most executed first. Instructions before any label are
+	/// grouped as `<unknown>`.
+	pub fn hottest_labels(&self) -> Vec<(String, u64)> {
+		let mut labels = BTreeMap::<String, u64>::new();
+		for (addr, count) in &self.counts {
+			let label = self
+				.debug_info
+				.as_ref()
+				.and_then(|debug_info| debug_info.symbols().lookup(*addr))
+				.map_or("<unknown>", |(label, _offset)| label);
+			*labels.entry(label.to_owned()).or_default() += count;
+		}
+		let mut labels = labels.into_iter().collect::<Vec<_>>();
+		labels.sort_by(|(_, a), (_, b)| b.cmp(a));
+		labels
+	}
+
+	/// Human readable report of the `top` hottest instructions and labels,
+	/// with counts and percentages of all executed instructions.
+	pub fn report(&self, top: usize) -> String {
+		let total = self.total();
+		let percent = |count: u64| count as f64 * 100.0 / total.max(1) as f64;
+		let mut report = format!("Executed {total} instructions\n\nHottest instructions:\n");
+		writeln!(
+			report,
+			"{:>12} {:>7}  {:<10}  {:<24}  location",
+			"count", "%", "address", "instruction"
+		)
+		.expect("writing to string");
+		for (addr, count) in self.hottest_instructions().into_iter().take(top) {
+			let instruction = self
+				.code
+				.get(native_ptr(addr)..)
+				.and_then(|code| Instruction::parse(code).ok())
+				.map_or_else(|| "<invalid>".to_owned(), |instruction| instruction.to_string());
+			let location = self
+				.debug_info
+				.as_ref()
+				.map_or_else(String::new, |debug_info| debug_info.describe(addr));
+			writeln!(
+				report,
+				"{count:>12} {:>6.2}%  {addr:#010x}  {instruction:<24}  {location}",
+				percent(count)
+			)
+			.expect("writing to string");
+		}
+		writeln!(report, "\nHottest labels:\n{:>12} {:>7}  label", "count", "%")
+			.expect("writing to string");
+		for (label, count) in self.hottest_labels().into_iter().take(top) {
+			writeln!(report, "{count:>12} {:>6.2}%  {label}", percent(count))
+				.expect("writing to string");
+		}
+		report
+	}
+}
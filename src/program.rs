@@ -1,14 +1,79 @@
-use std::{collections::HashMap, ffi::CString, mem::size_of, str::FromStr};
+use std::{
+	cmp::Ordering,
+	collections::{BTreeMap, BTreeSet, HashMap},
+	fmt::{self, Write},
+	mem::size_of,
+	path::{Path, PathBuf},
+	str::FromStr,
+};
 
 use anyhow::Context;
 
-use crate::{instruction::Instruction, util::vm_ptr, VmPtr};
+use crate::{
+	assembler::{Assembler, Diagnostic},
+	calling::Frame,
+	instruction::Instruction,
+	linker::Object,
+	util::{closest_match, native_ptr, vm_ptr},
+	DebugInfo, Executable, SymbolTable, VmPtr,
+};
+
+/// Largest alignment accepted when padding code or static data.
+pub const MAX_ALIGNMENT: VmPtr = 4096;
 
 /// A full programm. Just a helper to create programs, the VM uses actual byte
 /// code.
 #[derive(Debug, Clone, Default)]
 pub struct Program {
 	instructions: Vec<Instruction>,
+	/// Cached code addresses of the instructions.
+	addresses: Vec<VmPtr>,
+	/// Source lines of the instructions, if they were parsed from assembly.
+	sources: Vec<Option<SourceLine>>,
+	/// Instruction index of the entry point, if it is not the first
+	/// instruction.
+	entry: Option<usize>,
+	/// Instruction indices of named labels.
+	labels: BTreeMap<String, usize>,
+	/// Name of the source file, if the program was read from a file.
+	source_file: Option<String>,
+	/// Suggested memory size to run the program with.
+	memory_size: Option<VmPtr>,
+	/// Static data section, which is loaded into machine memory at the data
+	/// base address before execution.
+	data: Vec<u8>,
+	/// Memory address the data section is loaded to.
+	data_base: VmPtr,
+	/// Labels that are exported when the program is used as an object.
+	globals: BTreeSet<String>,
+	/// Jumps or calls to labels that are not defined (yet): instruction index
+	/// and label. They are resolved when the label is added, or when linking.
+	imports: Vec<(usize, String)>,
+	/// Files that were included by the assembled source, excluding the
+	/// standard library.
+	included_files: Vec<PathBuf>,
+	/// Function that is being built with the calling convention helpers.
+	pub(crate) frame: Option<Frame>,
+}
+
+/// Source line of an instruction that was parsed from text assembly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLine {
+	/// Included file the line is in, or `None` for the assembled source itself.
+	pub file: Option<String>,
+	/// Line number, starting at 1.
+	pub line: usize,
+	/// Source text of the statement.
+	pub text: String,
+}
+
+impl fmt::Display for SourceLine {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.file {
+			Some(file) => write!(f, "{file}:{}: {}", self.line, self.text),
+			None => write!(f, "{:>5}: {}", self.line, self.text),
+		}
+	}
 }
 
 impl Program {
@@ -17,18 +82,398 @@ impl Program {
 		Self::default()
 	}
 
-	/// Compile the program to continuous bytes.
-	pub fn compile(&self) -> Vec<u8> {
+	/// Parse a program from text assembly with predefined constants, which can
+	/// be used in operands and conditional sections like `.define`d ones.
+	pub fn from_str_with_defines<'a>(
+		input: &str,
+		defines: impl IntoIterator<Item = (&'a str, VmPtr)>,
+	) -> anyhow::Result<Self> {
+		let defines = defines.into_iter().map(|(name, value)| (name.to_owned(), value)).collect();
+		Assembler::assemble_with_defines(input, defines)
+	}
+
+	/// Parse a program from text assembly like [`str::parse`], but return all
+	/// errors as diagnostics with their source lines instead of one combined
+	/// error.
+	pub fn parse_with_diagnostics(input: &str) -> Result<Self, Vec<Diagnostic>> {
+		Assembler::assemble_with_diagnostics(input)
+	}
+
+	/// Read and assemble a program from a text assembly file. The file name is
+	/// remembered for debug information. Relative includes are resolved
+	/// against the directory of the file.
+	pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+		let path = path.as_ref();
+		let input = std::fs::read_to_string(path)
+			.with_context(|| format!("Cannot read {}", path.display()))?;
+		let mut program = Assembler::assemble_in_dir(&input, path.parent())?;
+		program.source_file = Some(path.display().to_string());
+		Ok(program)
+	}
+
+	/// Decode compiled bytes back into a program, instruction by instruction.
+	pub fn disassemble(code: &[u8]) -> anyhow::Result<Self> {
+		let mut program = Self::new();
+		let mut addr = 0;
+		while addr < code.len() {
+			let instruction = Instruction::parse(&code[addr..])
+				.with_context(|| format!("Failed decoding instruction at code address {addr}"))?;
+			addr += instruction.size();
+			program.add_instruction(instruction);
+		}
+		Ok(program)
+	}
+
+	/// Decode an executable back into a program, including its entry point,
+	/// data section and the labels of its symbol table that point to
+	/// instructions.
+	pub fn from_executable(executable: &Executable) -> anyhow::Result<Self> {
+		let mut program = Self::disassemble(&executable.code)?;
+		let layout = program.layout();
+		if let Ok(index) = layout.binary_search(&executable.entry_point) {
+			program.entry = Some(index);
+		}
+		for (name, addr) in executable.symbols.iter().flat_map(SymbolTable::iter) {
+			if let Ok(index) = layout.binary_search(&addr) {
+				program.labels.insert(name.to_owned(), index);
+			}
+		}
+		program.memory_size = executable.memory_size;
+		program.data_base = executable.data_base;
+		program.data = executable.data.clone();
+		Ok(program)
+	}
+
+	/// Compile the program to continuous bytes. Fails if jumps or calls refer
+	/// to labels that were never added.
+	pub fn compile(&self) -> anyhow::Result<Vec<u8>> {
+		if let Some((index, label)) = self.imports.first() {
+			let mut message = format!("Undefined label {label} used by instruction {index}");
+			if let Some(suggestion) = closest_match(label, self.labels.keys().map(String::as_str)) {
+				write!(message, ", did you mean {suggestion}?")
+					.expect("writing to String cannot fail");
+			}
+			anyhow::bail!(message);
+		}
+		Ok(self.code())
+	}
+
+	/// Concatenated bytes of all instructions, without checking for unresolved
+	/// labels.
+	fn code(&self) -> Vec<u8> {
 		self.instructions.iter().flat_map(|i| i.bytes()).collect()
 	}
 
+	/// Set the indexed instruction as the entry point of the program, where
+	/// execution starts.
+	pub fn set_entry(&mut self, index: usize) -> anyhow::Result<()> {
+		self.resolve(index).context("Invalid entry index")?;
+		self.entry = Some(index);
+		Ok(())
+	}
+
+	/// Get the code address of the entry point, where execution starts. This
+	/// is the first instruction if no other entry point was set.
+	pub fn entry_point(&self) -> VmPtr {
+		self.entry.and_then(|index| self.resolve(index)).map_or(0, |(addr, _)| addr)
+	}
+
+	/// Compile the program to continuous bytes and return the symbol table of
+	/// its labels alongside.
+	pub fn compile_with_symbols(&self) -> anyhow::Result<(Vec<u8>, SymbolTable)> {
+		Ok((self.compile()?, self.symbols()))
+	}
+
+	/// Set the suggested memory size to run the program with, which is stored
+	/// in the executable.
+	pub fn set_memory_size(&mut self, memory_size: VmPtr) {
+		self.memory_size = Some(memory_size);
+	}
+
+	/// Number of side registers the program requires, i.e. the highest used
+	/// side register plus one.
+	pub fn required_side_registers(&self) -> u16 {
+		self.instructions
+			.iter()
+			.filter_map(Instruction::side_register)
+			.map(|reg| u16::from(reg) + 1)
+			.max()
+			.unwrap_or(0)
+	}
+
+	/// Compile the program into the executable container format, including
+	/// symbols and debug information.
+	pub fn to_executable(&self) -> anyhow::Result<Executable> {
+		let file = self.source_file.as_deref().unwrap_or("<unknown>");
+		Ok(Executable {
+			side_registers: self.required_side_registers(),
+			memory_size: self.memory_size,
+			entry_point: self.entry_point(),
+			code: self.compile()?,
+			data_base: self.data_base,
+			data: self.data.clone(),
+			compress_data: false,
+			symbols: Some(self.symbols()),
+			debug_info: Some(self.debug_info(file)),
+		})
+	}
+
+	/// Convert the program to a relocatable object, that can be linked with
+	/// other objects using the [`Linker`](crate::Linker). Jumps or calls to
+	/// undefined labels become imports. All labels are exported, unless some
+	/// are explicitly declared `global`. Data sections are not supported in
+	/// objects.
+	pub fn to_object(&self) -> anyhow::Result<Object> {
+		anyhow::ensure!(self.data.is_empty(), "Objects cannot have a data section");
+		let layout = self.layout();
+		let imports = self.imports.iter().map(|(index, _)| *index).collect::<BTreeSet<_>>();
+		let mut object = Object {
+			side_registers: self.required_side_registers(),
+			code: self.code(),
+			entry_point: self.entry.map(|index| layout[index]),
+			..Object::default()
+		};
+		for (index, instruction) in self.instructions.iter().enumerate() {
+			let Some(addr) = instruction.code_address() else { continue };
+			// The code address operand directly follows the opcode.
+			let offset = layout[index] + 1;
+			if imports.contains(&index) {
+				continue;
+			}
+			anyhow::ensure!(
+				addr != VmPtr::MAX,
+				"Instruction {index} still has a dummy address and cannot be relocated"
+			);
+			object.relocations.push(offset);
+		}
+		for (index, label) in &self.imports {
+			object.imports.push((layout[*index] + 1, label.clone()));
+		}
+		for (name, index) in &self.labels {
+			let global = self.globals.is_empty() || self.globals.contains(name);
+			object.symbols.insert(name.clone(), (layout[*index], global));
+		}
+		Ok(object)
+	}
+
+	/// Compile the program and write it to a file in the executable container
+	/// format.
+	pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+		self.to_executable()?.save(path)
+	}
+
+	/// Get the symbol table mapping the program's labels to code addresses.
+	pub fn symbols(&self) -> SymbolTable {
+		let layout = self.layout();
+		let mut symbols = SymbolTable::new();
+		for (name, index) in &self.labels {
+			symbols.insert(name.as_str(), layout[*index]);
+		}
+		symbols
+	}
+
+	/// Get the debug information of the program, mapping code addresses to the
+	/// source lines in the given file or the files it included, and to labels.
+	pub fn debug_info(&self, file: impl Into<String>) -> DebugInfo {
+		let mut debug_info = DebugInfo::new(file);
+		for (addr, source) in self.layout().into_iter().zip(&self.sources) {
+			match source {
+				Some(SourceLine { file: None, line, .. }) => debug_info.insert_line(addr, *line),
+				Some(SourceLine { file: Some(file), line, .. }) => {
+					debug_info.insert_included_line(addr, file.as_str(), *line)
+				}
+				None => debug_info.insert_unknown(addr),
+			}
+		}
+		debug_info.set_symbols(self.symbols());
+		debug_info
+	}
+
+	/// Convert the program back to text assembly, which can be parsed again.
+	/// Labels are generated for all jump, call and copy data targets that do
+	/// not have a label yet.
+	pub fn to_asm(&self) -> anyhow::Result<String> {
+		let layout = self.layout();
+		let mut labels = BTreeMap::<usize, Vec<String>>::new();
+		for (name, index) in &self.labels {
+			labels.entry(*index).or_default().push(name.clone());
+		}
+		let mut label_for = |index: usize| -> String {
+			let names = labels.entry(index).or_default();
+			if names.is_empty() {
+				names.push(format!("addr_{}", layout[index]));
+			}
+			names[0].clone()
+		};
+
+		let mut statements = Vec::with_capacity(self.instructions.len());
+		for (index, instruction) in self.instructions.iter().enumerate() {
+			let statement = match instruction {
+				Instruction::CopyCodeMemory(src, size) => {
+					let target = layout
+						.iter()
+						.position(|addr| *addr + 1 + vm_ptr(size_of::<VmPtr>()) == *src)
+						.filter(|target| {
+							matches!(self.instructions.get(*target), Some(Instruction::Data(len, _)) if len == size)
+						})
+						.with_context(|| {
+							format!("Instruction {index} does not copy a whole data segment")
+						})?;
+					format!("copyCodeMemory {}", label_for(target))
+				}
+				_ => match instruction.code_address() {
+					Some(addr) => {
+						let target = layout.binary_search(&addr).map_err(|_| {
+							anyhow::format_err!(
+								"Instruction {index} refers to code address {addr}, which is not an \
+								 instruction"
+							)
+						})?;
+						let text = instruction.to_string();
+						let mnemonic = text.split_whitespace().next().unwrap_or_default();
+						format!("{mnemonic} {}", label_for(target))
+					}
+					None => instruction.to_string(),
+				},
+			};
+			statements.push(statement);
+		}
+
+		let mut asm = String::new();
+		if !self.data.is_empty() {
+			writeln!(asm, ".data {}", self.data_base).expect("writing to String cannot fail");
+			writeln!(asm, "{}", Instruction::Data(vm_ptr(self.data.len()), self.data.clone()))
+				.expect("writing to String cannot fail");
+			writeln!(asm, ".code").expect("writing to String cannot fail");
+		}
+		if let Some(entry) = self.entry {
+			writeln!(asm, "entry {}", label_for(entry)).expect("writing to String cannot fail");
+		}
+		for (index, statement) in statements.iter().enumerate() {
+			for name in labels.get(&index).into_iter().flatten() {
+				writeln!(asm, "label {name}").expect("writing to String cannot fail");
+			}
+			writeln!(asm, "{statement}").expect("writing to String cannot fail");
+		}
+		for name in labels.get(&self.instructions.len()).into_iter().flatten() {
+			writeln!(asm, "label {name}").expect("writing to String cannot fail");
+		}
+		Ok(asm)
+	}
+
+	/// Produce a human readable listing of the program with the code address,
+	/// the encoded bytes and the source line of every instruction. Instructions
+	/// that were not parsed from assembly show their reconstruction instead.
+	pub fn listing(&self) -> String {
+		const BYTES_PER_ROW: usize = 8;
+		let mut listing = String::new();
+		let mut addr: VmPtr = 0;
+		for (instruction, source) in self.instructions.iter().zip(&self.sources) {
+			let bytes = instruction.bytes();
+			let text = match source {
+				Some(source) => source.to_string(),
+				None => format!("       {instruction}"),
+			};
+			for (row, chunk) in bytes.chunks(BYTES_PER_ROW).enumerate() {
+				let hex = chunk.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ");
+				let row_addr = addr + vm_ptr(row * BYTES_PER_ROW);
+				let text = if row == 0 { text.as_str() } else { "" };
+				let row =
+					format!("{row_addr:08x}  {hex:<width$}  {text}", width = BYTES_PER_ROW * 3 - 1);
+				writeln!(listing, "{}", row.trim_end()).expect("writing to String cannot fail");
+			}
+			addr += vm_ptr(bytes.len());
+		}
+		if !self.data.is_empty() {
+			writeln!(listing, "\ndata section at memory address {:08x}:", self.data_base)
+				.expect("writing to String cannot fail");
+			for (row, chunk) in self.data.chunks(BYTES_PER_ROW).enumerate() {
+				let hex = chunk.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ");
+				let row_addr = self.data_base + vm_ptr(row * BYTES_PER_ROW);
+				writeln!(listing, "{row_addr:08x}  {hex}").expect("writing to String cannot fail");
+			}
+		}
+		listing
+	}
+
 	/// Add an instruction to the program. Return the index of this instruction
 	/// to be used by jumps or calls.
 	pub fn add_instruction(&mut self, instruction: Instruction) -> usize {
+		self.addresses.push(self.code_size());
 		self.instructions.push(instruction);
+		self.sources.push(None);
 		self.instructions.len() - 1
 	}
 
+	/// Number of instructions in the program.
+	pub fn len(&self) -> usize {
+		self.instructions.len()
+	}
+
+	/// Whether the program has no instructions.
+	pub fn is_empty(&self) -> bool {
+		self.instructions.is_empty()
+	}
+
+	/// Iterate over the instructions together with their code addresses.
+	pub fn iter(&self) -> impl Iterator<Item = (VmPtr, &Instruction)> + '_ {
+		self.addresses.iter().copied().zip(&self.instructions)
+	}
+
+	/// Code address of the indexed instruction. The index equal to the number
+	/// of instructions gives the end of the program.
+	pub fn address_of(&self, index: usize) -> Option<VmPtr> {
+		match index.cmp(&self.instructions.len()) {
+			Ordering::Less => Some(self.addresses[index]),
+			Ordering::Equal => Some(self.code_size()),
+			Ordering::Greater => None,
+		}
+	}
+
+	/// Name the instruction at the given index, which can also be the end of
+	/// the program.
+	pub(crate) fn insert_label(&mut self, name: String, index: usize) {
+		self.labels.insert(name, index);
+	}
+
+	/// Export the label when the program is used as an object.
+	pub(crate) fn insert_global(&mut self, name: String) {
+		self.globals.insert(name);
+	}
+
+	/// Labels that are exported when the program is used as an object.
+	pub(crate) fn globals(&self) -> impl Iterator<Item = &str> {
+		self.globals.iter().map(String::as_str)
+	}
+
+	/// Record that the indexed dummy jump or call refers to a label outside
+	/// of the program.
+	pub(crate) fn add_import(&mut self, index: usize, label: String) {
+		self.imports.push((index, label));
+	}
+
+	/// Files that were included by the assembled source, excluding the
+	/// standard library. Changes to them change the program.
+	pub fn included_files(&self) -> &[PathBuf] {
+		&self.included_files
+	}
+
+	/// Record that the file was included.
+	pub(crate) fn add_included_file(&mut self, path: PathBuf) {
+		self.included_files.push(path);
+	}
+
+	/// Source line of the instruction at the given index, if it was parsed
+	/// from text assembly.
+	pub fn source(&self, index: usize) -> Option<&SourceLine> {
+		self.sources.get(index)?.as_ref()
+	}
+
+	/// Set the source line of the indexed instruction.
+	pub(crate) fn set_source(&mut self, index: usize, source: SourceLine) {
+		self.sources[index] = Some(source);
+	}
+
 	/// Add NOP instruction to the program. Return the index of this instruction
 	/// to be used by jumps or calls.
 	pub fn add_nop(&mut self) -> usize {
@@ -54,12 +499,393 @@ impl Program {
 		self.add_instruction(Instruction::Data(vm_ptr(data.len()), data))
 	}
 
+	/// Pad the program with NOP instructions, so that the next instruction
+	/// starts at a code address that is a multiple of `alignment`. Return the
+	/// number of added padding instructions. The alignment must not exceed
+	/// [`MAX_ALIGNMENT`].
+	pub fn add_align(&mut self, alignment: VmPtr) -> anyhow::Result<usize> {
+		check_alignment(alignment)?;
+		let padding = (alignment - self.code_size() % alignment) % alignment;
+		for _ in 0..padding {
+			self.add_nop();
+		}
+		Ok(native_ptr(padding))
+	}
+
+	/// Pool identical data segments: every data segment that duplicates an
+	/// earlier one is removed and all copy data instructions referencing it are
+	/// rewritten to the earlier segment. All other code addresses are fixed up
+	/// accordingly. Return the number of removed data segments.
+	pub fn pool_data(&mut self) -> usize {
+		let mut first_occurrence = HashMap::new();
+		let mut redirects = HashMap::new();
+		for (index, instruction) in self.instructions.iter().enumerate() {
+			if let Instruction::Data(_, data) = instruction {
+				let first = *first_occurrence.entry(data.as_slice()).or_insert(index);
+				if first != index {
+					redirects.insert(index, first);
+				}
+			}
+		}
+		let removed = redirects.len();
+		if removed > 0 {
+			self.remove_with_fixup(&redirects);
+		}
+		removed
+	}
+
+	/// Check the program for mistakes that are easy to make with the builder
+	/// API: dummy addresses that were never replaced, jump or call targets
+	/// outside of the program or in the middle of an instruction, and copy
+	/// data instructions that do not copy from within a data segment. All
+	/// problems are reported at once.
+	pub fn validate(&self) -> anyhow::Result<()> {
+		let layout = self.layout();
+		let code_end = layout.last().copied().unwrap_or_default();
+		let imports = self.imports.iter().map(|(index, _)| *index).collect::<BTreeSet<_>>();
+		let mut problems = Vec::new();
+		for (index, instruction) in self.instructions.iter().enumerate() {
+			let Some(addr) = instruction.code_address() else { continue };
+			let location = format!("Instruction {index} at {:08x}", layout[index]);
+			if imports.contains(&index) {
+				continue;
+			}
+			if addr == VmPtr::MAX {
+				problems.push(format!("{location} still has a dummy address"));
+				continue;
+			}
+			if let Instruction::CopyCodeMemory(_, size) = instruction {
+				let source = layout.partition_point(|start| *start <= addr).saturating_sub(1);
+				let payload = match self.instructions.get(source) {
+					Some(Instruction::Data(len, _)) => {
+						let start = layout[source] + 1 + vm_ptr(size_of::<VmPtr>());
+						start..start + len
+					}
+					_ => 0..0,
+				};
+				let end = addr.checked_add(*size);
+				if !payload.contains(&addr) || end.is_none_or(|end| end > payload.end) {
+					problems.push(format!(
+						"{location} copies {size} bytes from {addr:08x}, which is not within a data \
+						 segment"
+					));
+				}
+			} else if addr >= code_end {
+				problems.push(format!("{location} targets {addr:08x} outside of the program"));
+			} else if layout.binary_search(&addr).is_err() {
+				problems
+					.push(format!("{location} targets {addr:08x} in the middle of an instruction"));
+			}
+		}
+		for (index, label) in &self.imports {
+			problems.push(format!("Instruction {index} refers to undefined label {label}"));
+		}
+		if problems.is_empty() {
+			return Ok(());
+		}
+		anyhow::bail!("Program has {} problem(s):\n{}", problems.len(), problems.join("\n"));
+	}
+
+	/// Indices of the instructions that are neither reachable from the entry
+	/// point (or labels declared `global`) nor read as data by a reachable copy
+	/// data instruction. Only direct jumps and calls are followed, so code that
+	/// is only reached via computed return addresses counts as unreachable.
+	pub fn unreachable_instructions(&self) -> Vec<usize> {
+		let layout = self.layout();
+		let code_end = layout.last().copied().unwrap_or_default();
+		let index_at = |addr: VmPtr| {
+			(addr < code_end).then(|| layout.partition_point(|start| *start <= addr) - 1)
+		};
+
+		let mut visited = vec![false; self.instructions.len()];
+		let mut live = vec![false; self.instructions.len()];
+		let mut pending = vec![self.entry.unwrap_or(0)];
+		pending.extend(self.globals.iter().filter_map(|name| self.labels.get(name)));
+		while let Some(index) = pending.pop() {
+			let Some(instruction) = self.instructions.get(index) else { continue };
+			if visited[index] {
+				continue;
+			}
+			visited[index] = true;
+			// Data segments are never executed, they are only live if they are
+			// copied with `CopyCodeMemory`.
+			live[index] = !matches!(instruction, Instruction::Data(_, _));
+			match instruction {
+				Instruction::Halt | Instruction::Return => {}
+				Instruction::Jump(addr) => pending.extend(index_at(*addr)),
+				Instruction::CopyCodeMemory(addr, size) => {
+					let end = addr.saturating_add(*size);
+					if let Some(first) = index_at(*addr) {
+						for source in first..self.instructions.len() {
+							if layout[source] >= end && source != first {
+								break;
+							}
+							live[source] = true;
+						}
+					}
+					pending.push(index + 1);
+				}
+				_ => {
+					pending.extend(instruction.code_address().and_then(index_at));
+					pending.push(index + 1);
+				}
+			}
+		}
+		live.iter().enumerate().filter(|(_, live)| !**live).map(|(index, _)| index).collect()
+	}
+
+	/// Remove all unreachable instructions and unreferenced data segments (see
+	/// [`Self::unreachable_instructions`]) and fix up all code addresses.
+	/// Labels of removed instructions are removed as well. Return the number
+	/// of removed instructions.
+	pub fn remove_dead_code(&mut self) -> usize {
+		let dead = self.unreachable_instructions();
+		if dead.is_empty() {
+			return 0;
+		}
+		let redirects: HashMap<usize, usize> =
+			dead.iter().map(|index| (*index, index + 1)).collect();
+		self.labels.retain(|_, index| !redirects.contains_key(index));
+		self.imports.retain(|(index, _)| !redirects.contains_key(index));
+		self.remove_with_fixup(&redirects);
+		dead.len()
+	}
+
+	/// Insert an instruction before the indexed instruction (or at the end of
+	/// the program) and fix up all code addresses, labels and the entry point.
+	/// References to the indexed instruction keep pointing to it, i.e. they now
+	/// point behind the inserted instruction. A code address operand of the
+	/// inserted instruction is taken as it is.
+	pub fn insert_instruction(
+		&mut self,
+		index: usize,
+		instruction: Instruction,
+	) -> anyhow::Result<()> {
+		anyhow::ensure!(index <= self.instructions.len(), "Invalid instruction index");
+		let target = instruction.code_address();
+		let old_layout = self.layout();
+		let index_map: Vec<usize> =
+			(0..=self.instructions.len()).map(|i| if i < index { i } else { i + 1 }).collect();
+		self.instructions.insert(index, instruction);
+		self.sources.insert(index, None);
+		self.update_addresses();
+		self.relocate(&old_layout, &index_map);
+		if let (Some(target), Some(addr)) = (target, self.instructions[index].code_address_mut()) {
+			*addr = target;
+		}
+		Ok(())
+	}
+
+	/// Remove the indexed instruction and fix up all code addresses, labels
+	/// and the entry point. References to the removed instruction are
+	/// redirected to the following instruction. Return the removed
+	/// instruction.
+	pub fn remove_instruction(&mut self, index: usize) -> anyhow::Result<Instruction> {
+		let instruction =
+			self.instructions.get(index).context("Invalid instruction index")?.clone();
+		self.imports.retain(|(import, _)| *import != index);
+		self.remove_with_fixup(&HashMap::from([(index, index + 1)]));
+		Ok(instruction)
+	}
+
+	/// Shift all code addresses the instructions refer to by `offset`, e.g.
+	/// when the code is placed behind other code. Dummy addresses are kept as
+	/// they are.
+	pub fn rebase(&mut self, offset: VmPtr) -> anyhow::Result<()> {
+		for (index, instruction) in self.instructions.iter_mut().enumerate() {
+			let Some(addr) = instruction.code_address_mut() else { continue };
+			if *addr == VmPtr::MAX {
+				continue;
+			}
+			*addr = addr
+				.checked_add(offset)
+				.filter(|addr| *addr != VmPtr::MAX)
+				.with_context(|| format!("Rebased address of instruction {index} overflows"))?;
+		}
+		Ok(())
+	}
+
+	/// Append the other program to this one, rebasing its code addresses. The
+	/// labels of both programs are merged, and jumps or calls to labels defined
+	/// in the other program are resolved. At most one of the programs may have
+	/// an entry point, and their data sections must be adjacent if both have
+	/// one. Return the index of the first appended instruction, which is the
+	/// offset to add to the other program's instruction indices.
+	pub fn append(&mut self, mut other: Program) -> anyhow::Result<usize> {
+		if let Some(name) = other.labels.keys().find(|name| self.labels.contains_key(*name)) {
+			anyhow::bail!("Label {name} is defined in both programs");
+		}
+		anyhow::ensure!(
+			self.entry.is_none() || other.entry.is_none(),
+			"Both programs have an entry point"
+		);
+		if !self.data.is_empty() && !other.data.is_empty() {
+			anyhow::ensure!(
+				other.data_base == self.data_end()?,
+				"Data section of the appended program does not follow the existing one"
+			);
+		}
+		other.rebase(self.code_size())?;
+
+		let offset = self.instructions.len();
+		self.instructions.append(&mut other.instructions);
+		self.sources.append(&mut other.sources);
+		self.update_addresses();
+		if let Some(entry) = other.entry {
+			self.entry = Some(entry + offset);
+		}
+		self.labels.extend(other.labels.into_iter().map(|(name, index)| (name, index + offset)));
+		self.globals.append(&mut other.globals);
+		self.imports
+			.extend(other.imports.into_iter().map(|(index, label)| (index + offset, label)));
+		self.resolve_imports();
+		if self.data.is_empty() {
+			self.data_base = other.data_base;
+		}
+		self.data.append(&mut other.data);
+		self.data_end()?;
+		self.memory_size = self.memory_size.max(other.memory_size);
+		Ok(offset)
+	}
+
+	/// Remove the instructions that are keys in `redirects` and fix up all code
+	/// addresses. References to a removed instruction are redirected to the
+	/// instruction given as value.
+	fn remove_with_fixup(&mut self, redirects: &HashMap<usize, usize>) {
+		let mut index_map = Vec::with_capacity(self.instructions.len() + 1);
+		let mut kept = 0;
+		for index in 0..self.instructions.len() {
+			index_map.push(kept);
+			if !redirects.contains_key(&index) {
+				kept += 1;
+			}
+		}
+		index_map.push(kept);
+		for (&index, &target) in redirects {
+			index_map[index] = index_map[target];
+		}
+
+		let old_layout = self.layout();
+		let mut index = 0;
+		self.instructions.retain(|_| {
+			index += 1;
+			!redirects.contains_key(&(index - 1))
+		});
+		let mut index = 0;
+		self.sources.retain(|_| {
+			index += 1;
+			!redirects.contains_key(&(index - 1))
+		});
+		self.update_addresses();
+		self.relocate(&old_layout, &index_map);
+	}
+
+	/// Fix up all code addresses after the instructions were rearranged.
+	/// `old_layout` is the layout before the change and `index_map` maps every
+	/// old instruction index (including the end of the program) to its new
+	/// index.
+	fn relocate(&mut self, old_layout: &[VmPtr], index_map: &[usize]) {
+		let new_layout = self.layout();
+		let code_end = old_layout.last().copied().unwrap_or_default();
+		for instruction in &mut self.instructions {
+			let Some(addr) = instruction.code_address_mut() else { continue };
+			if *addr > code_end {
+				// Dummy or invalid addresses are kept as they are.
+				continue;
+			}
+			let old_index = old_layout.partition_point(|start| *start <= *addr) - 1;
+			let offset = *addr - old_layout[old_index];
+			*addr = new_layout[index_map[old_index]] + offset;
+		}
+		self.entry = self.entry.map(|index| index_map[index]);
+		for index in self.labels.values_mut() {
+			*index = index_map[*index];
+		}
+		for (index, _) in &mut self.imports {
+			*index = index_map[*index];
+		}
+	}
+
+	/// Recompute the cached code addresses after instructions were inserted or
+	/// removed.
+	fn update_addresses(&mut self) {
+		self.addresses.clear();
+		let mut addr = 0;
+		for instruction in &self.instructions {
+			self.addresses.push(addr);
+			addr += vm_ptr(instruction.size());
+		}
+	}
+
+	/// Code addresses of all instructions, followed by the end of the program.
+	fn layout(&self) -> Vec<VmPtr> {
+		let mut layout = Vec::with_capacity(self.instructions.len() + 1);
+		layout.extend_from_slice(&self.addresses);
+		layout.push(self.code_size());
+		layout
+	}
+
+	/// Size of the compiled program in bytes.
+	fn code_size(&self) -> VmPtr {
+		self.addresses
+			.last()
+			.zip(self.instructions.last())
+			.map_or(0, |(addr, instruction)| addr + vm_ptr(instruction.size()))
+	}
+
+	/// Set the memory address the data section is loaded to. Must be set before
+	/// adding static data, as the returned addresses depend on it.
+	pub fn set_data_base(&mut self, base: VmPtr) -> anyhow::Result<()> {
+		anyhow::ensure!(
+			self.data.is_empty(),
+			"Data base address must be set before adding static data"
+		);
+		self.data_base = base;
+		Ok(())
+	}
+
+	/// Memory address the data section is loaded to.
+	pub fn data_base(&self) -> VmPtr {
+		self.data_base
+	}
+
+	/// Static data section of the program.
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Add static data to the data section. Return the memory address the data
+	/// will be at when the program is loaded.
+	pub fn add_static_data(&mut self, data: impl AsRef<[u8]>) -> anyhow::Result<VmPtr> {
+		let addr = self.data_end()?;
+		self.data.extend_from_slice(data.as_ref());
+		self.data_end()?;
+		Ok(addr)
+	}
+
+	/// Pad the data section with zeros, so that the next static data starts at
+	/// a memory address that is a multiple of `alignment`. The alignment must
+	/// not exceed [`MAX_ALIGNMENT`].
+	pub fn align_static_data(&mut self, alignment: VmPtr) -> anyhow::Result<()> {
+		check_alignment(alignment)?;
+		let padding = (alignment - self.data_end()? % alignment) % alignment;
+		self.data.resize(self.data.len() + native_ptr(padding), 0);
+		Ok(())
+	}
+
+	/// Memory address after the end of the data section.
+	pub(crate) fn data_end(&self) -> anyhow::Result<VmPtr> {
+		VmPtr::try_from(self.data.len())
+			.ok()
+			.and_then(|len| self.data_base.checked_add(len))
+			.context("Data section exceeds the address space")
+	}
+
 	/// Resolve the instruction index to a code memory address and its
 	/// instruction.
 	fn resolve(&self, index: usize) -> Option<(VmPtr, &Instruction)> {
-		let addr = self.instructions.iter().take(index).map(|i| vm_ptr(i.size())).sum();
 		let instruction = self.instructions.get(index)?;
-		Some((addr, instruction))
+		Some((self.addresses[index], instruction))
 	}
 
 	/// Add an instruction to the program that copies the data from the indexed
@@ -267,6 +1093,123 @@ impl Program {
 		self.add_instruction(Instruction::JumpNonzero(VmPtr::MAX))
 	}
 
+	/// Name the position of the next added instruction, so that jumps and
+	/// calls can refer to it by label. Earlier references to the label are
+	/// resolved now, later ones right away. Return the index of the next
+	/// instruction.
+	pub fn add_label(&mut self, name: impl Into<String>) -> anyhow::Result<usize> {
+		let name = name.into();
+		anyhow::ensure!(!self.labels.contains_key(&name), "Label {name} is defined twice");
+		let index = self.instructions.len();
+		self.labels.insert(name, index);
+		self.resolve_imports();
+		Ok(index)
+	}
+
+	/// Resolve all jumps or calls to undefined labels whose labels are defined
+	/// now.
+	fn resolve_imports(&mut self) {
+		let mut imports = std::mem::take(&mut self.imports);
+		imports.retain(|(reference, label)| {
+			let Some(addr) = self.labels.get(label).and_then(|index| self.address_of(*index))
+			else {
+				return true;
+			};
+			if let Some(target) = self.instructions[*reference].code_address_mut() {
+				*target = addr;
+			}
+			false
+		});
+		self.imports = imports;
+	}
+
+	/// Add an instruction that refers to the label with its code address
+	/// operand. The address is resolved now if the label exists, otherwise when
+	/// it is added. Return the index of the instruction.
+	fn add_label_reference(&mut self, instruction: Instruction, label: &str) -> usize {
+		let target = self.labels.get(label).copied();
+		let index = self.add_instruction(instruction);
+		match target {
+			Some(target) => {
+				let addr = self.address_of(target).expect("labels point into the program");
+				if let Some(operand) = self.instructions[index].code_address_mut() {
+					*operand = addr;
+				}
+			}
+			None => self.imports.push((index, label.to_owned())),
+		}
+		index
+	}
+
+	/// Add an instruction to the program that jumps to the labelled
+	/// instruction. Return the index of this instruction to be used by jumps
+	/// or calls.
+	pub fn add_jump_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::Jump(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that calls the labelled instruction.
+	/// Return the index of this instruction to be used by jumps or calls.
+	pub fn add_call_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::Call(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that jumps to the labelled instruction
+	/// if the last comparison was equal. Return the index of this instruction
+	/// to be used by jumps or calls.
+	pub fn add_jump_equal_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::JumpEqual(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that jumps to the labelled instruction
+	/// if the last comparison was not equal. Return the index of this
+	/// instruction to be used by jumps or calls.
+	pub fn add_jump_not_equal_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::JumpNotEqual(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that jumps to the labelled instruction
+	/// if the last comparison was greater. Return the index of this instruction
+	/// to be used by jumps or calls.
+	pub fn add_jump_greater_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::JumpGreater(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that jumps to the labelled instruction
+	/// if the last comparison was less. Return the index of this instruction
+	/// to be used by jumps or calls.
+	pub fn add_jump_less_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::JumpLess(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that jumps to the labelled instruction
+	/// if the last comparison was greater or equal. Return the index of this
+	/// instruction to be used by jumps or calls.
+	pub fn add_jump_greater_equal_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::JumpGreaterEqual(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that jumps to the labelled instruction
+	/// if the last comparison was less or equal. Return the index of this
+	/// instruction to be used by jumps or calls.
+	pub fn add_jump_less_equal_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::JumpLessEqual(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that jumps to the labelled instruction
+	/// if the last increment/decrement resulted in zero. Return the index of
+	/// this instruction to be used by jumps or calls.
+	pub fn add_jump_zero_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::JumpZero(VmPtr::MAX), label)
+	}
+
+	/// Add an instruction to the program that jumps to the labelled instruction
+	/// if the last increment/decrement resulted in nonzero. Return the index of
+	/// this instruction to be used by jumps or calls.
+	pub fn add_jump_nonzero_label(&mut self, label: &str) -> usize {
+		self.add_label_reference(Instruction::JumpNonzero(VmPtr::MAX), label)
+	}
+
 	/// Replace a dummy jump/call address with a real address. This is useful
 	/// when the code that we want to jump to does not exist yet in the
 	/// program.
@@ -294,318 +1237,76 @@ impl Program {
 	}
 }
 
-impl FromStr for Program {
-	type Err = anyhow::Error;
+/// Check that the alignment is between 1 and [`MAX_ALIGNMENT`].
+fn check_alignment(alignment: VmPtr) -> anyhow::Result<()> {
+	anyhow::ensure!(alignment > 0, "Alignment must be greater than 0");
+	anyhow::ensure!(
+		alignment <= MAX_ALIGNMENT,
+		"Alignment {alignment} exceeds the maximum of {MAX_ALIGNMENT}"
+	);
+	Ok(())
+}
 
-	fn from_str(input: &str) -> Result<Self, Self::Err> {
-		let mut program = Program::new();
-		let mut next_index: usize = 0;
-		let mut label_index = HashMap::new();
-		let mut dummy_jumps = Vec::new();
-		let mut dummy_copy_data = Vec::new();
-
-		// Parse lines into instructions, making dummies at references to labels.
-		for line in input.lines().map(str::trim).filter(|s| !s.is_empty()) {
-			let parts = line.split_whitespace().collect::<Vec<_>>();
-			match parts[0].to_lowercase().as_str() {
-				// Comments.
-				"#" | "//" => continue,
-				// Label <name>
-				"label" if parts.len() == 2 => {
-					let prev = label_index.insert(parts[1], next_index);
-					if prev.is_some() {
-						anyhow::bail!("Label {} is defined multiple times", parts[1]);
-					}
-				}
-				// Nop
-				"nop" if parts.len() == 1 => {
-					program.add_nop();
-					next_index += 1;
-				}
-				// Halt
-				"halt" if parts.len() == 1 => {
-					program.add_instruction(Instruction::Halt);
-					next_index += 1;
-				}
-				// Load8 <ptr>
-				"load8" if parts.len() == 2 => {
-					let ptr = parts[1].parse()?;
-					program.add_instruction(Instruction::Load8(ptr));
-					next_index += 1;
-				}
-				// Load16 <ptr>
-				"store8" if parts.len() == 2 => {
-					let ptr = parts[1].parse()?;
-					program.add_instruction(Instruction::Store8(ptr));
-					next_index += 1;
-				}
-				// Load16 <ptr>
-				"load16" if parts.len() == 2 => {
-					let ptr = parts[1].parse()?;
-					program.add_instruction(Instruction::Load16(ptr));
-					next_index += 1;
-				}
-				// Store16 <ptr>
-				"store16" if parts.len() == 2 => {
-					let ptr = parts[1].parse()?;
-					program.add_instruction(Instruction::Store16(ptr));
-					next_index += 1;
-				}
-				// Load32 <ptr>
-				"load32" if parts.len() == 2 => {
-					let ptr = parts[1].parse()?;
-					program.add_instruction(Instruction::Load32(ptr));
-					next_index += 1;
-				}
-				// Store32 <ptr>
-				"store32" if parts.len() == 2 => {
-					let ptr = parts[1].parse()?;
-					program.add_instruction(Instruction::Store32(ptr));
-					next_index += 1;
-				}
-				// Set <value>
-				"set" if parts.len() == 2 => {
-					let value = parts[1].parse()?;
-					program.add_instruction(Instruction::Set(value));
-					next_index += 1;
-				}
-				// Deref8 <register>
-				"deref8" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Deref8(register));
-					next_index += 1;
-				}
-				// Deref16 <register>
-				"deref16" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Deref16(register));
-					next_index += 1;
-				}
-				// Deref32 <register>
-				"deref32" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Deref32(register));
-					next_index += 1;
-				}
-				// Syscall <id>
-				"syscall" if parts.len() == 2 => {
-					let id = parts[1].parse()?;
-					program.add_syscall(id);
-					next_index += 1;
-				}
-				// CopyCodeMemory <target_data_label>
-				"copycodememory" if parts.len() == 2 => {
-					let index = program.add_dummy_copy_data();
-					dummy_copy_data.push((index, parts[1]));
-					next_index += 1;
-				}
-				// DataString <str>
-				"datastring" => {
-					let cstr = CString::new(line.split_at(10).1.trim())?;
-					program.add_data(cstr.into_bytes_with_nul());
-					next_index += 1;
-				}
-				// Swap <register>
-				"swap" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Swap(register));
-					next_index += 1;
-				}
-				// Write8 <register>
-				"write8" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Write8(register));
-					next_index += 1;
-				}
-				// Write16 <register>
-				"write16" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Write16(register));
-					next_index += 1;
-				}
-				// Write32 <register>
-				"write32" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Write32(register));
-					next_index += 1;
-				}
-				// ReadStackPointer
-				"readstackpointer" if parts.len() == 1 => {
-					program.add_instruction(Instruction::ReadStackPointer);
-					next_index += 1;
-				}
-				// WriteStackPointer
-				"writestackpointer" if parts.len() == 1 => {
-					program.add_instruction(Instruction::WriteStackPointer);
-					next_index += 1;
-				}
-				// Jump <label>
-				"jump" if parts.len() == 2 => {
-					let index = program.add_dummy_jump();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// Call <label>
-				"call" if parts.len() == 2 => {
-					let index = program.add_dummy_call();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// Return
-				"return" if parts.len() == 1 => {
-					program.add_instruction(Instruction::Return);
-					next_index += 1;
-				}
-				// Increment
-				"increment" if parts.len() == 1 => {
-					program.add_instruction(Instruction::Increment);
-					next_index += 1;
-				}
-				// Decrement
-				"decrement" if parts.len() == 1 => {
-					program.add_instruction(Instruction::Decrement);
-					next_index += 1;
-				}
-				// Add <register>
-				"add" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Add(register));
-					next_index += 1;
-				}
-				// Sub <register>
-				"sub" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Sub(register));
-					next_index += 1;
-				}
-				// Compare <register>
-				"compare" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Compare(register));
-					next_index += 1;
-				}
-				// JumpEqual <label>
-				"jumpequal" if parts.len() == 2 => {
-					let index = program.add_dummy_jump_equal();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// JumpNotEqual <label>
-				"jumpnotequal" if parts.len() == 2 => {
-					let index = program.add_dummy_jump_not_equal();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// JumpGreater <label>
-				"jumpgreater" if parts.len() == 2 => {
-					let index = program.add_dummy_jump_greater();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// JumpLess <label>
-				"jumpless" if parts.len() == 2 => {
-					let index = program.add_dummy_jump_less();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// JumpGreaterEqual <label>
-				"jumpgreaterequal" if parts.len() == 2 => {
-					let index = program.add_dummy_jump_greater_equal();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// JumpLessEqual <label>
-				"jumplessequal" if parts.len() == 2 => {
-					let index = program.add_dummy_jump_less_equal();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// JumpZero <label>
-				"jumpzero" if parts.len() == 2 => {
-					let index = program.add_dummy_jump_zero();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// JumpNonzero <label>
-				"jumpnonzero" if parts.len() == 2 => {
-					let index = program.add_dummy_jump_nonzero();
-					dummy_jumps.push((index, parts[1]));
-					next_index += 1;
-				}
-				// Push
-				"push" if parts.len() == 1 => {
-					program.add_instruction(Instruction::Push);
-					next_index += 1;
-				}
-				// Pop
-				"pop" if parts.len() == 1 => {
-					program.add_instruction(Instruction::Pop);
-					next_index += 1;
-				}
-				// PushRegister <register>
-				"pushregister" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::PushRegister(register));
-					next_index += 1;
-				}
-				// PopRegister <register>
-				"popregister" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::PopRegister(register));
-					next_index += 1;
-				}
-				// Mul <register>
-				"mul" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Mul(register));
-					next_index += 1;
-				}
-				// Div <register>
-				"div" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::Div(register));
-					next_index += 1;
-				}
-				// IncrementRegister <register>
-				"incrementregister" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::IncrementRegister(register));
-					next_index += 1;
-				}
-				// DecrementRegister <register>
-				"decrementregister" if parts.len() == 2 => {
-					let register = parts[1].parse()?;
-					program.add_instruction(Instruction::DecrementRegister(register));
-					next_index += 1;
-				}
-				// SetRegister <register> <value>
-				"setregister" if parts.len() == 3 => {
-					let register = parts[1].parse()?;
-					let value = parts[2].parse()?;
-					program.add_instruction(Instruction::SetRegister(register, value));
-					next_index += 1;
-				}
-				// Unknown command.
-				cmd => {
-					return Err(anyhow::format_err!(
-						"Unknown command or wrong number of arguments: {cmd}"
-					))
-				}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Program {
+	/// Generate a small random program that passes [`Program::validate`]:
+	/// jumps and calls target instruction starts, and copy data instructions
+	/// copy from within data segments.
+	fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+		let len = u.int_in_range(1..=64)?;
+		let mut instructions = (0..len)
+			.map(|_| u.arbitrary::<Instruction>())
+			.collect::<arbitrary::Result<Vec<_>>>()?;
+		// Copy data instructions need a non-empty data segment to copy from.
+		let data_indices = instructions
+			.iter()
+			.enumerate()
+			.filter(|(_, instruction)| matches!(instruction, Instruction::Data(len, _) if *len > 0))
+			.map(|(index, _)| index)
+			.collect::<Vec<_>>();
+		for instruction in &mut instructions {
+			if matches!(instruction, Instruction::CopyCodeMemory(_, _)) && data_indices.is_empty() {
+				*instruction = Instruction::Nop;
 			}
 		}
 
-		// Resolve dummies to their labels.
-		for (index, label) in dummy_jumps {
-			let target =
-				*label_index.get(&label).with_context(|| format!("Unresolved label: {label}"))?;
-			program.replace_dummy_address(index, target)?;
+		let mut program = Self::new();
+		for instruction in instructions {
+			program.add_instruction(instruction);
 		}
-		for (index, label) in dummy_copy_data {
-			let target =
-				*label_index.get(&label).with_context(|| format!("Unresolved label: {label}"))?;
-			program.replace_dummy_copy_data(index, target)?;
+		let layout = program.layout();
+		for index in 0..len {
+			let resolved = match &program.instructions[index] {
+				Instruction::CopyCodeMemory(_, _) => {
+					let data = *u.choose(&data_indices)?;
+					let Instruction::Data(data_len, _) = program.instructions[data] else {
+						unreachable!("data indices point to data segments");
+					};
+					let offset = u.int_in_range(0..=data_len - 1)?;
+					let size = u.int_in_range(0..=data_len - offset)?;
+					let payload = layout[data] + 1 + vm_ptr(size_of::<VmPtr>());
+					Instruction::CopyCodeMemory(payload + offset, size)
+				}
+				instruction if instruction.code_address().is_some() => {
+					let mut instruction = instruction.clone();
+					let target = u.choose_index(len)?;
+					*instruction.code_address_mut().expect("checked above") = layout[target];
+					instruction
+				}
+				_ => continue,
+			};
+			program.instructions[index] = resolved;
 		}
-
+		let entry = u.choose_index(len)?;
+		program.set_entry(entry).expect("entry index is in range");
 		Ok(program)
 	}
 }
+
+impl FromStr for Program {
+	type Err = anyhow::Error;
+
+	fn from_str(input: &str) -> Result<Self, Self::Err> {
+		Assembler::assemble(input)
+	}
+}
@@ -0,0 +1,135 @@
+//! Interactive assembly REPL, executing one instruction at a time.
+
+use std::fmt::Write;
+
+use anyhow::bail;
+
+use crate::{util::parse_value, Debugger, Instruction, Machine, VmPtr};
+
+/// Help text listing the REPL commands.
+const HELP: &str = "\
+<instruction>          execute the instruction, e.g. `set 5` or `addRegister 0`
+.registers             print registers and flags
+.memory <addr> [len]   print len bytes of memory (default 64)
+.stack [n]             print the top n words of the stack (default 8)
+.reset                 reset the machine to its initial state
+.help                  print this help";
+
+/// REPL that executes entered instructions against a persistent machine and
+/// reports the resulting changes to registers, flags and memory.
+#[derive(Debug)]
+pub struct Repl<const SIDE_REGS: usize> {
+	/// Debugger holding the machine, for inspection.
+	debugger: Debugger<SIDE_REGS>,
+	/// Memory size of the machine, for resetting.
+	memory_size: VmPtr,
+}
+
+impl<const SIDE_REGS: usize> Repl<SIDE_REGS> {
+	/// Create a REPL with a fresh machine with the given memory size.
+	pub fn new(memory_size: VmPtr) -> Self {
+		Self { debugger: Debugger::new(Machine::new(Vec::new(), memory_size)), memory_size }
+	}
+
+	/// The machine instructions are executed on.
+	pub fn machine(&self) -> &Machine<SIDE_REGS> {
+		self.debugger.machine()
+	}
+
+	/// Execute a line of input: either a command starting with `.` or an
+	/// instruction. Return the output, for instructions a description of the
+	/// changes. Empty lines and comments do nothing.
+	pub fn execute(&mut self, line: &str) -> anyhow::Result<String> {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+			return Ok(String::new());
+		}
+		let Some(command) = line.strip_prefix('.') else {
+			return self.execute_instruction(line.parse()?);
+		};
+		let mut words = command.split_whitespace();
+		let name = words.next().unwrap_or_default();
+		let arguments: Vec<&str> = words.collect();
+		match (name, arguments.as_slice()) {
+			("registers" | "r", []) => Ok(self.debugger.registers()),
+			("memory" | "m", [addr]) => self.debugger.hexdump(parse_value(addr)?, 64),
+			("memory" | "m", [addr, len]) => {
+				self.debugger.hexdump(parse_value(addr)?, parse_value(len)?)
+			}
+			("stack" | "s", []) => Ok(self.debugger.stack(8)),
+			("stack" | "s", [count]) => Ok(self.debugger.stack(parse_value(count)?)),
+			("reset", []) => {
+				*self = Self::new(self.memory_size);
+				Ok("Machine reset".to_owned())
+			}
+			("help" | "h", []) => Ok(HELP.to_owned()),
+			_ => bail!("Invalid command `{line}`, see `.help`"),
+		}
+	}
+
+	/// Execute an instruction and describe the changes it made.
+	fn execute_instruction(&mut self, instruction: Instruction) -> anyhow::Result<String> {
+		let before = self.machine().clone();
+		let running = self.debugger.machine_mut().execute_instruction(instruction)?;
+		let after = self.machine();
+
+		let mut changes = Vec::new();
+		let mut compare = |name: &str, before: String, after: String| {
+			if before != after {
+				changes.push(format!("{name}: {before} -> {after}"));
+			}
+		};
+		compare("sp", hex(before.stack_pointer()), hex(after.stack_pointer()));
+		compare("main", hex(before.main_register()), hex(after.main_register()));
+		for (index, (before, after)) in
+			before.side_registers().iter().zip(after.side_registers()).enumerate()
+		{
+			compare(&format!("r{index}"), hex(*before), hex(*after));
+		}
+		compare("zero", before.flag_zero().to_string(), after.flag_zero().to_string());
+		compare(
+			"cmp",
+			format!("{:?}", before.flag_comparison()),
+			format!("{:?}", after.flag_comparison()),
+		);
+		changes.extend(memory_changes(&before, after));
+		if !running {
+			changes.push("halt (ignored)".to_owned());
+		}
+
+		let mut output = changes.join("\n");
+		if output.is_empty() {
+			write!(output, "no changes").expect("writing to string");
+		}
+		Ok(output)
+	}
+}
+
+/// Format a value as hexadecimal number.
+fn hex(value: VmPtr) -> String {
+	format!("{value:#x}")
+}
+
+/// Describe the ranges of memory that differ between the machines.
+fn memory_changes<const SIDE_REGS: usize>(
+	before: &Machine<SIDE_REGS>,
+	after: &Machine<SIDE_REGS>,
+) -> Vec<String> {
+	let mut changes = Vec::new();
+	let mut differences = before.memory.iter().zip(after.memory.iter()).enumerate().peekable();
+	while let Some((start, _)) = differences.find(|(_, (before, after))| before != after) {
+		let mut end = start + 1;
+		while differences.next_if(|(_, (before, after))| before != after).is_some() {
+			end += 1;
+		}
+		let bytes = |memory: &[u8]| {
+			memory[start..end].iter().map(|byte| format!("{byte:02x}")).collect::<String>()
+		};
+		changes.push(format!(
+			"memory[{start:#x}..{end:#x}]: {} -> {}",
+			bytes(&before.memory),
+			bytes(&after.memory)
+		));
+	}
+	changes
+}
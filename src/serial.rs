@@ -0,0 +1,71 @@
+//! UART-style serial device: a byte-oriented TX/RX pair wired to host streams
+//! and accessed with syscalls 14 and 15.
+
+use std::{
+	fmt,
+	io::{self, Read, Write},
+	net::TcpStream,
+	sync::{Arc, Mutex},
+};
+
+use crate::VmPtr;
+
+/// Handle to a serial device. Clones share the same streams.
+#[derive(Clone)]
+pub struct Serial {
+	/// Stream the received bytes are read from.
+	rx: Arc<Mutex<dyn Read + Send>>,
+	/// Stream the transmitted bytes are written to.
+	tx: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl Serial {
+	/// Create a serial device receiving from `rx` and transmitting to `tx`.
+	pub fn new(rx: impl Read + Send + 'static, tx: impl Write + Send + 'static) -> Self {
+		Self { rx: Arc::new(Mutex::new(rx)), tx: Arc::new(Mutex::new(tx)) }
+	}
+
+	/// Create a serial device on stdin and stdout.
+	pub fn stdio() -> Self {
+		Self::new(io::stdin(), io::stdout())
+	}
+
+	/// Create a serial device on both directions of a TCP connection.
+	pub fn tcp(stream: TcpStream) -> io::Result<Self> {
+		Ok(Self::new(stream.try_clone()?, stream))
+	}
+
+	/// Transmit a byte, flushing it to the stream right away.
+	pub fn transmit(&self, byte: u8) -> io::Result<()> {
+		let mut tx = self.tx.lock().expect("serial lock poisoned");
+		tx.write_all(&[byte])?;
+		tx.flush()
+	}
+
+	/// Receive a byte, blocking until one is available. Return `VmPtr::MAX`
+	/// at the end of the stream.
+	pub fn receive(&self) -> io::Result<VmPtr> {
+		let mut byte = [0];
+		loop {
+			match self.rx.lock().expect("serial lock poisoned").read(&mut byte) {
+				Ok(0) => return Ok(VmPtr::MAX),
+				Ok(_) => return Ok(VmPtr::from(byte[0])),
+				Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+				Err(err) => return Err(err),
+			}
+		}
+	}
+}
+
+impl fmt::Debug for Serial {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Serial").finish_non_exhaustive()
+	}
+}
+
+impl PartialEq for Serial {
+	/// Serial devices are equal if they share the same streams.
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.rx, &other.rx) && Arc::ptr_eq(&self.tx, &other.tx)
+	}
+}
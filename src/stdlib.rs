@@ -0,0 +1,22 @@
+//! Bundled standard library of assembly routines, included with
+//! `include "std/<module>.asm"`.
+
+/// Modules of the standard library: include path and text assembly.
+///
+/// All routines follow the same calling convention: arguments are passed in
+/// the side registers r0, r1 and r2, the result is returned in the main
+/// register. The routines clobber r0-r3 and preserve all other side registers
+/// and the stack, so programs using them need at least 4 side registers.
+///
+/// - `std/string.asm`: `strlen`, `strcpy`, `strcmp`, `memcpy`, `memset` and
+///   `memcmp`.
+/// - `std/convert.asm`: `itoa` and `atoi` for decimal numbers.
+pub const STDLIB: [(&str, &str); 2] = [
+	("std/string.asm", include_str!("stdlib/string.asm")),
+	("std/convert.asm", include_str!("stdlib/convert.asm")),
+];
+
+/// Source of the standard library module with the given include path.
+pub(crate) fn module(path: &str) -> Option<&'static str> {
+	STDLIB.iter().find(|(name, _)| *name == path).map(|(_, source)| *source)
+}
@@ -0,0 +1,59 @@
+# Standard library: conversion between numbers and decimal strings.
+# Calling convention: arguments in r0, r1, r2, result in the main register.
+# The routines clobber r0-r3 and preserve all other side registers.
+
+# itoa: Write the number in r0 as nul terminated decimal string to r1.
+# Returns the number of digits, i.e. the length without the nul.
+label itoa
+    pushRegister 1
+    swap 0
+    setRegister 2 0
+# Push the digits from the lowest to the highest.
+label itoa.divide
+    setRegister 3 10
+    div 3
+    pushRegister 3
+    incrementRegister 2
+    increment
+    decrement
+    jumpNonzero itoa.divide
+    # Pop them into the string from the highest to the lowest.
+    setRegister 3 48
+label itoa.write
+    pop
+    add 3
+    write8 1
+    incrementRegister 1
+    decrementRegister 2
+    jumpNonzero itoa.write
+    set 0
+    write8 1
+    swap 1
+    popRegister 1
+    sub 1
+    return
+
+# atoi: Parse the decimal number at the start of the string at r0. Parsing
+# stops at the first byte that is no digit. Returns the number.
+label atoi
+    setRegister 1 0
+label atoi.loop
+    deref8 0
+    setRegister 2 48
+    compare 2
+    jumpLess atoi.end
+    setRegister 2 57
+    compare 2
+    jumpGreater atoi.end
+    setRegister 2 48
+    sub 2
+    swap 1
+    setRegister 2 10
+    mul 2
+    add 1
+    swap 1
+    incrementRegister 0
+    jump atoi.loop
+label atoi.end
+    swap 1
+    return
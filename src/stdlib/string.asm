@@ -0,0 +1,130 @@
+# Standard library: string and memory routines.
+# Calling convention: arguments in r0, r1, r2, result in the main register.
+# The routines clobber r0-r3 and preserve all other side registers.
+
+# strlen: Length of the nul terminated string at r0, without the nul.
+label strlen
+    pushRegister 0
+    setRegister 1 0
+label strlen.loop
+    deref8 0
+    compare 1
+    jumpEqual strlen.end
+    incrementRegister 0
+    jump strlen.loop
+label strlen.end
+    popRegister 1
+    swap 0
+    sub 1
+    return
+
+# strcpy: Copy the nul terminated string from r1 to r0, including the nul.
+# Returns the length of the string, without the nul.
+label strcpy
+    pushRegister 0
+    setRegister 2 0
+label strcpy.loop
+    deref8 1
+    write8 0
+    compare 2
+    jumpEqual strcpy.end
+    incrementRegister 0
+    incrementRegister 1
+    jump strcpy.loop
+label strcpy.end
+    popRegister 1
+    swap 0
+    sub 1
+    return
+
+# strcmp: Compare the nul terminated strings at r0 and r1 bytewise.
+# Returns 0 if they are equal, 1 if the first differing byte is greater in the
+# first string and -1 if it is less.
+label strcmp
+    deref8 1
+    swap 3
+    deref8 0
+    compare 3
+    jumpGreater strcmp.greater
+    jumpLess strcmp.less
+    increment
+    decrement
+    jumpZero strcmp.equal
+    incrementRegister 0
+    incrementRegister 1
+    jump strcmp
+label strcmp.equal
+    return
+label strcmp.greater
+    set 1
+    return
+label strcmp.less
+    set -1
+    return
+
+# memcpy: Copy r2 bytes from r1 to r0. Returns the target address.
+label memcpy
+    pushRegister 0
+    swap 2
+label memcpy.loop
+    increment
+    decrement
+    jumpZero memcpy.end
+    swap 3
+    deref8 1
+    write8 0
+    incrementRegister 0
+    incrementRegister 1
+    swap 3
+    decrement
+    jump memcpy.loop
+label memcpy.end
+    pop
+    return
+
+# memset: Fill r2 bytes at r0 with the byte in r1. Returns the target address.
+label memset
+    pushRegister 0
+    swap 2
+label memset.loop
+    increment
+    decrement
+    jumpZero memset.end
+    swap 1
+    write8 0
+    swap 1
+    incrementRegister 0
+    decrement
+    jump memset.loop
+label memset.end
+    pop
+    return
+
+# memcmp: Compare r2 bytes at r0 and r1. Returns 0 if they are equal, 1 if the
+# first differing byte is greater at r0 and -1 if it is less.
+label memcmp
+    swap 2
+label memcmp.loop
+    increment
+    decrement
+    jumpZero memcmp.equal
+    swap 2
+    deref8 1
+    swap 3
+    deref8 0
+    compare 3
+    jumpGreater memcmp.greater
+    jumpLess memcmp.less
+    incrementRegister 0
+    incrementRegister 1
+    swap 2
+    decrement
+    jump memcmp.loop
+label memcmp.equal
+    return
+label memcmp.greater
+    set 1
+    return
+label memcmp.less
+    set -1
+    return
@@ -0,0 +1,121 @@
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::Write,
+};
+
+use anyhow::Context;
+
+use crate::VmPtr;
+
+/// Mapping between label names and code addresses, with lookup in both
+/// directions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+	/// Code address by label name.
+	by_name: BTreeMap<String, VmPtr>,
+	/// Label names by code address.
+	by_address: BTreeMap<VmPtr, BTreeSet<String>>,
+}
+
+impl SymbolTable {
+	/// Create new empty symbol table.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Insert a symbol. Replaces the address of an existing symbol with the
+	/// same name.
+	pub fn insert(&mut self, name: impl Into<String>, addr: VmPtr) {
+		let name = name.into();
+		if let Some(previous) = self.by_name.insert(name.clone(), addr) {
+			self.remove_address_entry(previous, &name);
+		}
+		self.by_address.entry(addr).or_default().insert(name);
+	}
+
+	/// Remove a symbol. Return its address if it existed.
+	pub fn remove(&mut self, name: &str) -> Option<VmPtr> {
+		let addr = self.by_name.remove(name)?;
+		self.remove_address_entry(addr, name);
+		Some(addr)
+	}
+
+	/// Remove the name from the reverse lookup of the address.
+	fn remove_address_entry(&mut self, addr: VmPtr, name: &str) {
+		if let Some(names) = self.by_address.get_mut(&addr) {
+			names.remove(name);
+			if names.is_empty() {
+				self.by_address.remove(&addr);
+			}
+		}
+	}
+
+	/// Get the code address of the symbol with the given name.
+	pub fn address(&self, name: &str) -> Option<VmPtr> {
+		self.by_name.get(name).copied()
+	}
+
+	/// Get all symbol names at exactly the given code address, sorted by name.
+	pub fn names_at(&self, addr: VmPtr) -> impl Iterator<Item = &str> {
+		self.by_address.get(&addr).into_iter().flatten().map(String::as_str)
+	}
+
+	/// Get the first symbol name at exactly the given code address.
+	pub fn name_at(&self, addr: VmPtr) -> Option<&str> {
+		self.names_at(addr).next()
+	}
+
+	/// Find the closest symbol at or before the given code address. Return its
+	/// name and the offset of the address from the symbol.
+	pub fn lookup(&self, addr: VmPtr) -> Option<(&str, VmPtr)> {
+		let (symbol_addr, names) = self.by_address.range(..=addr).next_back()?;
+		let name = names.first()?;
+		Some((name, addr - symbol_addr))
+	}
+
+	/// Iterate over all symbols and their addresses, sorted by address.
+	pub fn iter(&self) -> impl Iterator<Item = (&str, VmPtr)> {
+		self.by_address
+			.iter()
+			.flat_map(|(addr, names)| names.iter().map(|name| (name.as_str(), *addr)))
+	}
+
+	/// Number of symbols.
+	pub fn len(&self) -> usize {
+		self.by_name.len()
+	}
+
+	/// Whether there are no symbols.
+	pub fn is_empty(&self) -> bool {
+		self.by_name.is_empty()
+	}
+
+	/// Serialize to the sidecar format: one symbol per line, with the
+	/// hexadecimal address and the name separated by a space.
+	pub fn to_sidecar(&self) -> String {
+		let mut output = String::new();
+		for (name, addr) in self.iter() {
+			writeln!(output, "{addr:08x} {name}").expect("writing to String cannot fail");
+		}
+		output
+	}
+
+	/// Parse the sidecar format, see [`Self::to_sidecar`]. Empty lines are
+	/// ignored.
+	pub fn from_sidecar(input: &str) -> anyhow::Result<Self> {
+		let mut symbols = Self::new();
+		for (line_number, line) in input.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() {
+				continue;
+			}
+			let (addr, name) = line
+				.split_once(' ')
+				.with_context(|| format!("Invalid symbol at line {}", line_number + 1))?;
+			let addr = VmPtr::from_str_radix(addr, 16)
+				.with_context(|| format!("Invalid symbol address at line {}", line_number + 1))?;
+			symbols.insert(name.trim(), addr);
+		}
+		Ok(symbols)
+	}
+}
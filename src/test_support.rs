@@ -0,0 +1,108 @@
+//! Helpers for test suites of tools building on the VM: round-trip assertions
+//! for the encodings and diffing of machine states.
+
+use std::fmt::Write;
+
+use crate::{Instruction, Machine, Program, VmPtr};
+
+/// Maximum number of bytes shown per differing memory range.
+const MAX_SHOWN_BYTES: usize = 16;
+
+/// Assert that the instruction survives encoding to bytes and decoding again,
+/// and that its size matches its encoding.
+#[track_caller]
+pub fn assert_instruction_roundtrip(instruction: &Instruction) {
+	let bytes = instruction.bytes();
+	assert_eq!(bytes.len(), instruction.size(), "Encoded size of {instruction:?} is wrong");
+	let decoded = Instruction::parse(&bytes)
+		.unwrap_or_else(|err| panic!("Cannot decode {instruction:?} from {bytes:?}: {err:#}"));
+	assert_eq!(&decoded, instruction, "Decoding the bytes {bytes:?} gives a different instruction");
+}
+
+/// Assert that the program survives conversion to text assembly and assembling
+/// again, i.e. it compiles to the same code with the same entry point and data
+/// section.
+#[track_caller]
+pub fn assert_program_roundtrip(program: &Program) {
+	let asm = program.to_asm().unwrap_or_else(|err| panic!("Cannot convert to asm: {err:#}"));
+	let reassembled: Program =
+		asm.parse().unwrap_or_else(|err| panic!("Cannot assemble\n{asm}\n{err:#}"));
+	let code = program.compile().unwrap_or_else(|err| panic!("Cannot compile: {err:#}"));
+	let reassembled_code =
+		reassembled.compile().unwrap_or_else(|err| panic!("Cannot compile\n{asm}\n{err:#}"));
+	assert!(code == reassembled_code, "Reassembled code differs for\n{asm}");
+	assert_eq!(program.entry_point(), reassembled.entry_point(), "Entry point differs for\n{asm}");
+	assert_eq!(program.data_base(), reassembled.data_base(), "Data base differs for\n{asm}");
+	assert!(program.data() == reassembled.data(), "Data section differs for\n{asm}");
+}
+
+/// Describe all differences between the states of two machines, e.g. after
+/// running the same program in two different ways. Returns an empty list if
+/// the states are equal. Debug information is not compared.
+pub fn diff_machines<const SIDE_REGS: usize>(
+	left: &Machine<SIDE_REGS>,
+	right: &Machine<SIDE_REGS>,
+) -> Vec<String> {
+	let mut differences = Vec::new();
+	let mut compare = |name: &str, left: &dyn std::fmt::Debug, right: &dyn std::fmt::Debug| {
+		let (left, right) = (format!("{left:?}"), format!("{right:?}"));
+		if left != right {
+			differences.push(format!("{name}: {left} != {right}"));
+		}
+	};
+	compare("instruction pointer", &left.instruction_pointer, &right.instruction_pointer);
+	compare("stack pointer", &left.stack_pointer, &right.stack_pointer);
+	compare("main register", &left.main_register, &right.main_register);
+	for (reg, (l, r)) in left.side_registers.iter().zip(&right.side_registers).enumerate() {
+		compare(&format!("side register {reg}"), l, r);
+	}
+	compare("zero flag", &left.flag_zero, &right.flag_zero);
+	compare("comparison flag", &left.flag_comparison, &right.flag_comparison);
+	compare("memory size", &left.memory.len(), &right.memory.len());
+	if left.program != right.program {
+		differences.push("program code differs".to_owned());
+	}
+	differences.extend(diff_memory(&left.memory, &right.memory));
+	differences
+}
+
+/// Assert that the states of two machines are equal, listing all differences
+/// otherwise. See [`diff_machines`].
+#[track_caller]
+pub fn assert_machines_equal<const SIDE_REGS: usize>(
+	left: &Machine<SIDE_REGS>,
+	right: &Machine<SIDE_REGS>,
+) {
+	let differences = diff_machines(left, right);
+	assert!(differences.is_empty(), "Machine states differ:\n{}", differences.join("\n"));
+}
+
+/// Describe the ranges of differing bytes in the common part of two memories.
+fn diff_memory(left: &[u8], right: &[u8]) -> Vec<String> {
+	let mut differences = Vec::new();
+	let len = left.len().min(right.len());
+	let mut addr = 0;
+	while addr < len {
+		if left[addr] == right[addr] {
+			addr += 1;
+			continue;
+		}
+		let start = addr;
+		while addr < len && left[addr] != right[addr] {
+			addr += 1;
+		}
+		let shown = start..addr.min(start + MAX_SHOWN_BYTES);
+		let mut difference = format!(
+			"memory {:08x}..{:08x}: {:?} != {:?}",
+			start as VmPtr,
+			addr as VmPtr,
+			&left[shown.clone()],
+			&right[shown]
+		);
+		if addr - start > MAX_SHOWN_BYTES {
+			write!(difference, " (truncated)").expect("writing to String cannot fail");
+		}
+		differences.push(difference);
+	}
+	differences
+}
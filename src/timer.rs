@@ -0,0 +1,101 @@
+//! Periodic timer raising interrupts, handled by guest code registered with
+//! syscall 9.
+
+use std::{
+	cmp::Ordering,
+	time::{Duration, Instant},
+};
+
+use crate::VmPtr;
+
+/// Period of the timer interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerPeriod {
+	/// Raise the interrupt every n executed instructions.
+	Instructions(u64),
+	/// Raise the interrupt every n milliseconds of wall-clock time, checked
+	/// between instructions.
+	Milliseconds(u64),
+}
+
+/// State of the timer and its interrupt.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Timer {
+	/// Period of the interrupt.
+	period: TimerPeriod,
+	/// Code address of the guest handler, no interrupts are raised without.
+	pub handler: Option<VmPtr>,
+	/// Whether the guest masked the interrupt.
+	pub masked: bool,
+	/// Whether the handler is running and has not returned yet.
+	pub in_service: bool,
+	/// Whether the timer expired while the interrupt could not be taken.
+	pending: bool,
+	/// Instructions executed since the timer last expired.
+	instructions: u64,
+	/// Time the timer last expired.
+	last: Instant,
+}
+
+impl Timer {
+	/// Create a timer with the given period, without handler.
+	pub fn new(period: TimerPeriod) -> anyhow::Result<Self> {
+		let (TimerPeriod::Instructions(n) | TimerPeriod::Milliseconds(n)) = period;
+		anyhow::ensure!(n > 0, "Timer period must not be 0");
+		Ok(Self {
+			period,
+			handler: None,
+			masked: false,
+			in_service: false,
+			pending: false,
+			instructions: 0,
+			last: Instant::now(),
+		})
+	}
+
+	/// Advance the timer by an instruction. Return the handler address if the
+	/// interrupt is to be taken now.
+	pub fn tick(&mut self) -> Option<VmPtr> {
+		let expired = match self.period {
+			TimerPeriod::Instructions(n) => {
+				self.instructions += 1;
+				self.instructions >= n
+			}
+			TimerPeriod::Milliseconds(ms) => self.last.elapsed() >= Duration::from_millis(ms),
+		};
+		if expired {
+			self.instructions = 0;
+			self.last = Instant::now();
+			// Interrupts without handler are lost.
+			self.pending = self.handler.is_some();
+		}
+		if !self.pending || self.masked || self.in_service {
+			return None;
+		}
+		self.pending = false;
+		self.in_service = true;
+		self.handler
+	}
+}
+
+/// Encode the flags as pushed on interrupt entry: the zero flag in bit 0 and
+/// the comparison in bits 1-2 as 0 for less, 1 for equal and 2 for greater.
+pub(crate) fn encode_flags(zero: bool, comparison: Ordering) -> VmPtr {
+	let comparison = match comparison {
+		Ordering::Less => 0,
+		Ordering::Equal => 1,
+		Ordering::Greater => 2,
+	};
+	VmPtr::from(zero) | comparison << 1
+}
+
+/// Decode flags encoded by [`encode_flags`].
+pub(crate) fn decode_flags(flags: VmPtr) -> anyhow::Result<(bool, Ordering)> {
+	let comparison = match flags >> 1 {
+		0 => Ordering::Less,
+		1 => Ordering::Equal,
+		2 => Ordering::Greater,
+		_ => anyhow::bail!("Invalid interrupt flags {flags:#x}"),
+	};
+	Ok((flags & 1 == 1, comparison))
+}
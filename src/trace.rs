@@ -0,0 +1,163 @@
+//! Machine-readable execution trace in the JSON lines format.
+
+use std::{io::Write, mem::size_of};
+
+use anyhow::Context;
+
+use crate::{util::native_ptr, Instruction, Machine, VmPtr};
+
+/// Writes one JSON object per executed instruction, e.g.
+///
+/// ```json
+/// {"step":3,"address":10,"mnemonic":"push","operands":[],"ip":11,"sp":4092,"main":42,"side":[0,0],"zero":false,"cmp":"equal","writes":[{"address":4092,"bytes":"0000002a"}]}
+/// ```
+///
+/// `step` counts executed instructions starting at 0, `address` is the code
+/// address of the instruction and the registers and flags are the values
+/// after executing it. `writes` lists the memory written by the instruction
+/// with the written bytes in hexadecimal. To keep traces of long runs
+/// manageable, only every n-th instruction can be traced and the number of
+/// traced instructions can be limited.
+#[derive(Debug)]
+pub struct Tracer<W> {
+	/// Output of the trace.
+	writer: W,
+	/// Only trace every n-th instruction.
+	every: u64,
+	/// Maximum number of traced instructions.
+	limit: Option<u64>,
+	/// Number of executed instructions.
+	executed: u64,
+	/// Number of traced instructions.
+	traced: u64,
+}
+
+impl<W: Write> Tracer<W> {
+	/// Create a tracer writing every executed instruction to the writer.
+	pub fn new(writer: W) -> Self {
+		Self { writer, every: 1, limit: None, executed: 0, traced: 0 }
+	}
+
+	/// Only trace every n-th executed instruction, starting with the first.
+	pub fn with_sampling(mut self, every: u64) -> Self {
+		self.every = every.max(1);
+		self
+	}
+
+	/// Stop tracing after the given number of traced instructions. Execution
+	/// continues untraced.
+	pub fn with_limit(mut self, limit: u64) -> Self {
+		self.limit = Some(limit);
+		self
+	}
+
+	/// Number of instructions written to the trace.
+	pub fn traced(&self) -> u64 {
+		self.traced
+	}
+
+	/// Run a step of the machine and trace it. Return whether the execution
+	/// should continue.
+	pub fn step<const SIDE_REGS: usize>(
+		&mut self,
+		machine: &mut Machine<SIDE_REGS>,
+	) -> anyhow::Result<bool> {
+		let sampled = self.executed.is_multiple_of(self.every)
+			&& self.limit.is_none_or(|limit| self.traced < limit);
+		self.executed += 1;
+		if !sampled {
+			return machine.step();
+		}
+
+		let address = machine.instruction_pointer();
+		let instruction = machine
+			.code()
+			.get(native_ptr(address)..)
+			.and_then(|code| Instruction::parse(code).ok());
+		let write = instruction.as_ref().and_then(|instruction| memory_write(machine, instruction));
+		let running = machine.step()?;
+		let instruction =
+			instruction.context("Executed instruction could not be decoded for the trace")?;
+
+		let text = instruction.to_string();
+		let mut words = text.split_whitespace();
+		let mnemonic = words.next().unwrap_or_default();
+		let operands = words
+			.map(|operand| match operand.parse::<i64>() {
+				Ok(_) => operand.to_owned(),
+				Err(_) => json_string(operand),
+			})
+			.collect::<Vec<_>>();
+		let side = machine.side_registers().map(|value| value.to_string());
+		let writes = write
+			.and_then(|(addr, len)| {
+				let bytes = machine.read_memory(addr, native_ptr(len)).ok()?;
+				let hex = bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+				Some(format!(r#"{{"address":{addr},"bytes":"{hex}"}}"#))
+			})
+			.unwrap_or_default();
+		writeln!(
+			self.writer,
+			r#"{{"step":{},"address":{address},"mnemonic":{},"operands":[{}],"ip":{},"sp":{},"main":{},"side":[{}],"zero":{},"cmp":"{}","writes":[{writes}]}}"#,
+			self.executed - 1,
+			json_string(mnemonic),
+			operands.join(","),
+			machine.instruction_pointer(),
+			machine.stack_pointer(),
+			machine.main_register(),
+			side.join(","),
+			machine.flag_zero(),
+			format!("{:?}", machine.flag_comparison()).to_lowercase(),
+		)
+		.context("Failed writing trace")?;
+		self.traced += 1;
+		Ok(running)
+	}
+
+	/// Run the machine until it halts (or errors), tracing it.
+	pub fn run<const SIDE_REGS: usize>(
+		&mut self,
+		machine: &mut Machine<SIDE_REGS>,
+	) -> anyhow::Result<()> {
+		while self.step(machine)? {}
+		self.writer.flush().context("Failed writing trace")
+	}
+}
+
+/// Memory address and length the instruction is going to write to when
+/// executed on the machine in its current state.
+fn memory_write<const SIDE_REGS: usize>(
+	machine: &Machine<SIDE_REGS>,
+	instruction: &Instruction,
+) -> Option<(VmPtr, VmPtr)> {
+	let word = size_of::<VmPtr>() as VmPtr;
+	let side_register = |reg: u8| machine.side_registers().get(usize::from(reg)).copied();
+	match *instruction {
+		Instruction::Store8(ptr) => Some((ptr, 1)),
+		Instruction::Store16(ptr) => Some((ptr, 2)),
+		Instruction::Store32(ptr) => Some((ptr, 4)),
+		Instruction::Write8(reg) => Some((side_register(reg)?, 1)),
+		Instruction::Write16(reg) => Some((side_register(reg)?, 2)),
+		Instruction::Write32(reg) => Some((side_register(reg)?, 4)),
+		Instruction::CopyCodeMemory(_, size) => Some((machine.main_register(), size)),
+		Instruction::Call(_) | Instruction::Push | Instruction::PushRegister(_) => {
+			Some((machine.stack_pointer().checked_sub(word)?, word))
+		}
+		_ => None,
+	}
+}
+
+/// Encode the text as JSON string.
+fn json_string(text: &str) -> String {
+	let mut json = String::from("\"");
+	for c in text.chars() {
+		match c {
+			'"' => json.push_str("\\\""),
+			'\\' => json.push_str("\\\\"),
+			c if c.is_control() => json.push_str(&format!("\\u{:04x}", u32::from(c))),
+			c => json.push(c),
+		}
+	}
+	json.push('"');
+	json
+}
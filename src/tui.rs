@@ -0,0 +1,169 @@
+//! Full-screen terminal frontend for the [`Debugger`].
+
+use ratatui::{
+	crossterm::event::{self, Event, KeyCode, KeyEventKind},
+	layout::{Constraint, Layout, Rect},
+	style::{Style, Stylize},
+	text::Line,
+	widgets::{Block, Paragraph},
+	DefaultTerminal, Frame,
+};
+
+use crate::{Debugger, VmPtr};
+
+/// Key bindings shown in the status line.
+const KEYS: &str =
+	"s step | c continue | b toggle breakpoint | PgUp/PgDn memory | : command | q quit";
+
+/// Number of bytes the memory pane scrolls per key press.
+const MEMORY_SCROLL: VmPtr = 0x40;
+
+/// State of the terminal user interface.
+struct App<'a, const SIDE_REGS: usize> {
+	/// Debugger driving the machine.
+	debugger: &'a mut Debugger<SIDE_REGS>,
+	/// Start address of the memory pane.
+	memory_addr: VmPtr,
+	/// Debugger command being typed, if in command mode.
+	command: Option<String>,
+	/// Output of the last debugger command.
+	output: String,
+	/// Output the program printed so far.
+	program_output: String,
+}
+
+/// Run the full-screen terminal debugger until the user quits. Shows the
+/// disassembly around the instruction pointer, registers and flags, the call
+/// stack and a memory hexdump, updated after every step. The machine should
+/// capture its output (see [`Machine::with_captured_output`]), so that it is
+/// shown in a pane instead of messing up the screen.
+///
+/// [`Machine::with_captured_output`]: crate::Machine::with_captured_output
+pub fn run<const SIDE_REGS: usize>(debugger: &mut Debugger<SIDE_REGS>) -> anyhow::Result<()> {
+	let mut terminal = ratatui::init();
+	let mut app = App {
+		debugger,
+		memory_addr: 0,
+		command: None,
+		output: String::new(),
+		program_output: String::new(),
+	};
+	let result = app.run(&mut terminal);
+	ratatui::restore();
+	result
+}
+
+impl<const SIDE_REGS: usize> App<'_, SIDE_REGS> {
+	/// Draw and handle key presses until the user quits.
+	fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+		loop {
+			terminal.draw(|frame| self.draw(frame))?;
+			let Event::Key(key) = event::read()? else {
+				continue;
+			};
+			if key.kind != KeyEventKind::Press {
+				continue;
+			}
+			if let Some(command) = &mut self.command {
+				match key.code {
+					KeyCode::Enter => {
+						let command = self.command.take().unwrap_or_default();
+						self.execute(&command);
+					}
+					KeyCode::Esc => self.command = None,
+					KeyCode::Backspace => {
+						command.pop();
+					}
+					KeyCode::Char(c) => command.push(c),
+					_ => {}
+				}
+				continue;
+			}
+			match key.code {
+				KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+				KeyCode::Char('s') | KeyCode::Char('n') => self.execute("step"),
+				KeyCode::Char('c') => self.execute("continue"),
+				KeyCode::Char('b') => self.toggle_breakpoint(),
+				KeyCode::Char(':') => self.command = Some(String::new()),
+				KeyCode::PageUp => {
+					self.memory_addr = self.memory_addr.saturating_sub(MEMORY_SCROLL);
+				}
+				KeyCode::PageDown => {
+					let last = self.debugger.machine().memory_size().saturating_sub(1) & !0xf;
+					self.memory_addr = (self.memory_addr + MEMORY_SCROLL).min(last);
+				}
+				_ => {}
+			}
+		}
+	}
+
+	/// Execute a debugger command and show its output or error.
+	fn execute(&mut self, command: &str) {
+		self.output = match self.debugger.execute(command) {
+			Ok(output) => output,
+			Err(err) => format!("Error: {err:#}"),
+		};
+		self.program_output.push_str(&self.debugger.machine_mut().take_output());
+	}
+
+	/// Set or remove a breakpoint at the instruction pointer.
+	fn toggle_breakpoint(&mut self) {
+		let ip = self.debugger.machine().instruction_pointer();
+		if self.debugger.machine().breakpoints().any(|addr| addr == ip) {
+			self.execute(&format!("delete {ip}"));
+		} else {
+			self.execute(&format!("break {ip}"));
+		}
+	}
+
+	/// Draw all panes.
+	fn draw(&self, frame: &mut Frame) {
+		let [main, memory, output, status] = Layout::vertical([
+			Constraint::Min(10),
+			Constraint::Length(10),
+			Constraint::Length(6),
+			Constraint::Length(1),
+		])
+		.areas(frame.area());
+		let [code, side] =
+			Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+				.areas(main);
+		let [registers, stack] =
+			Layout::vertical([Constraint::Length(SIDE_REGS as u16 + 3), Constraint::Min(3)])
+				.areas(side);
+
+		let context = usize::from(code.height.saturating_sub(2) / 2);
+		pane(frame, code, "Disassembly", self.debugger.disassembly(context));
+		pane(frame, registers, "Registers", self.debugger.registers());
+		pane(frame, stack, "Call stack", self.debugger.backtrace());
+		pane(frame, memory, "Memory", self.hexdump(memory));
+
+		let [debugger_output, program_output] =
+			Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+				.areas(output);
+		let title = if self.debugger.is_halted() { "Debugger (halted)" } else { "Debugger" };
+		pane(frame, debugger_output, title, self.output.clone());
+		let lines = self.program_output.lines().collect::<Vec<_>>();
+		let visible =
+			lines.len().saturating_sub(usize::from(program_output.height.saturating_sub(2)));
+		pane(frame, program_output, "Program output", lines[visible..].join("\n"));
+		let status_line = match &self.command {
+			Some(command) => Line::from(format!(":{command}")),
+			None => Line::from(KEYS).style(Style::new().reversed()),
+		};
+		frame.render_widget(status_line, status);
+	}
+
+	/// Hexdump of the memory that fits into the pane.
+	fn hexdump(&self, area: Rect) -> String {
+		let rows = VmPtr::from(area.height.saturating_sub(2));
+		let len =
+			(rows * 16).min(self.debugger.machine().memory_size().saturating_sub(self.memory_addr));
+		self.debugger.hexdump(self.memory_addr, len).unwrap_or_else(|err| format!("Error: {err:#}"))
+	}
+}
+
+/// Render text in a bordered pane with a title.
+fn pane(frame: &mut Frame, area: Rect, title: &str, text: String) {
+	frame.render_widget(Paragraph::new(text).block(Block::bordered().title(title)), area);
+}
@@ -1,4 +1,4 @@
-use std::ffi::CStr;
+use std::{borrow::Cow, ffi::CStr};
 
 use anyhow::Context;
 
@@ -14,6 +14,16 @@ pub fn vm_ptr(ptr: usize) -> VmPtr {
 	ptr.try_into().expect("usize cannot be VmPtr")
 }
 
+/// Parse a numeric literal. Negative literals are encoded as two's complement.
+pub fn parse_value(operand: &str) -> anyhow::Result<VmPtr> {
+	let value = if operand.starts_with('-') {
+		operand.parse::<i32>().map(|value| value as VmPtr)
+	} else {
+		operand.parse()
+	};
+	value.with_context(|| format!("Invalid number: {operand}"))
+}
+
 /// Read the first bytes from a buffer and convert it to a u8.
 pub fn read_u8(bytes: &[u8]) -> anyhow::Result<u8> {
 	bytes.first().context("Out of memory access occurred at the border").copied()
@@ -78,7 +88,75 @@ pub fn read_bytes(buffer: &[u8], len: usize) -> anyhow::Result<&[u8]> {
 	buffer.get(0..len).context("Out of memory access occurred at the border")
 }
 
-/// Read a CStr from a buffer.
-pub fn read_cstr(buffer: &[u8]) -> anyhow::Result<&CStr> {
-	CStr::from_bytes_until_nul(buffer).context("Out of memory access occurred at the border")
+/// Read a UTF-8 string of the given length in bytes from a buffer.
+pub fn read_str(buffer: &[u8], len: usize) -> anyhow::Result<&str> {
+	std::str::from_utf8(read_bytes(buffer, len)?).context("String is not valid UTF-8")
+}
+
+/// Read a string of the given length in bytes from a buffer, replacing invalid
+/// UTF-8 sequences with U+FFFD.
+pub fn read_str_lossy(buffer: &[u8], len: usize) -> anyhow::Result<Cow<'_, str>> {
+	Ok(String::from_utf8_lossy(read_bytes(buffer, len)?))
+}
+
+/// Read a CStr of at most `max_len` bytes without the terminator from a
+/// buffer.
+pub fn read_cstr(buffer: &[u8], max_len: usize) -> anyhow::Result<&CStr> {
+	let bounded = &buffer[..buffer.len().min(max_len.saturating_add(1))];
+	match CStr::from_bytes_until_nul(bounded) {
+		Ok(cstr) => Ok(cstr),
+		Err(_) if bounded.len() < buffer.len() => {
+			anyhow::bail!("String is not terminated within {max_len} bytes")
+		}
+		Err(_) => anyhow::bail!("Out of memory access occurred at the border"),
+	}
+}
+
+/// Format up to 16 bytes at the address as hexdump line with the printable
+/// ASCII characters.
+pub fn hexdump_line(addr: VmPtr, bytes: &[u8]) -> String {
+	let hex = bytes.iter().map(|byte| format!("{byte:02x}")).collect::<Vec<_>>();
+	let text = bytes
+		.iter()
+		.map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+		.collect::<String>();
+	format!("{addr:#010x}: {:<47}  {text}", hex.join(" "))
+}
+
+/// Compute the optimal string alignment distance between two strings, i.e. the
+/// Levenshtein edit distance where swapping two adjacent characters counts as
+/// a single edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+	let a = a.chars().collect::<Vec<_>>();
+	let b = b.chars().collect::<Vec<_>>();
+	let mut before_previous = vec![0; b.len() + 1];
+	let mut previous = (0..=b.len()).collect::<Vec<_>>();
+	let mut current = vec![0; b.len() + 1];
+	for (i, ca) in a.iter().enumerate() {
+		current[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let substitution = previous[j] + usize::from(ca != cb);
+			current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+			if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+				current[j + 1] = current[j + 1].min(before_previous[j - 1] + 1);
+			}
+		}
+		std::mem::swap(&mut before_previous, &mut previous);
+		std::mem::swap(&mut previous, &mut current);
+	}
+	previous[b.len()]
+}
+
+/// Find the candidate closest to the given name, if any is reasonably close.
+pub fn closest_match<'a>(
+	name: &str,
+	candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+	let max_distance = (name.chars().count() / 3).max(1);
+	candidates
+		.into_iter()
+		.map(|candidate| (edit_distance(name, candidate), candidate))
+		.filter(|(distance, _)| *distance <= max_distance)
+		.min()
+		.map(|(_, candidate)| candidate)
 }
@@ -0,0 +1,120 @@
+//! Smoke tests running the `my-vm` binary.
+
+use std::{
+	fs,
+	io::Write,
+	path::PathBuf,
+	process::{Command, Output, Stdio},
+};
+
+/// Prints 3 and halts.
+const PROGRAM: &str = "set 3\nsyscall 1\nhalt\n";
+
+/// Create an empty directory for the files of one test.
+fn temp_dir(test: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("my-vm-cli-{}-{test}", std::process::id()));
+	_ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+/// Run the binary with the arguments and the given input on stdin.
+fn my_vm(args: &[&str], input: &str) -> Output {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_my-vm"))
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+	child.wait_with_output().unwrap()
+}
+
+/// Run the binary and return its output, asserting that it succeeded.
+fn stdout(args: &[&str], input: &str) -> String {
+	let output = my_vm(args, input);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn run_build_disasm() {
+	let dir = temp_dir("run_build_disasm");
+	let asm = dir.join("print.asm");
+	fs::write(&asm, PROGRAM).unwrap();
+	let asm = asm.to_str().unwrap();
+	let bin = dir.join("print.bin");
+	let bin = bin.to_str().unwrap();
+
+	assert_eq!(stdout(&["run", asm], ""), "3");
+	assert!(stdout(&["build", asm, "--output", bin], "").starts_with("Wrote "));
+	assert_eq!(stdout(&["run", bin], ""), "3");
+
+	let disassembly = stdout(&["disasm", bin], "");
+	assert!(disassembly.ends_with("set 3\nsyscall 1\nhalt\n"), "{disassembly}");
+	let listing = stdout(&["disasm", "--listing", asm], "");
+	assert_eq!(
+		listing.lines().collect::<Vec<_>>(),
+		[
+			"00000000  08 00 00 00 03               1: set 3",
+			"00000005  0c 01                        2: syscall 1",
+			"00000007  01                           3: halt",
+		]
+	);
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn debug() {
+	let dir = temp_dir("debug");
+	let asm = dir.join("print.asm");
+	fs::write(&asm, PROGRAM).unwrap();
+
+	let output = stdout(&["debug", asm.to_str().unwrap()], "continue\nquit\n");
+	assert!(output.contains("print.asm:1: set 3"), "{output}");
+	assert!(output.contains("3Halted"), "{output}");
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn default_side_registers() {
+	let dir = temp_dir("default_side_registers");
+	let frame = dir.join("frame.asm");
+	fs::write(&frame, "setRegister 4 1\nsetRegister 7 1\nhalt\n").unwrap();
+	let beyond = dir.join("beyond.asm");
+	fs::write(&beyond, "setRegister 8 1\nhalt\n").unwrap();
+
+	// Programs using the frame pointer and further registers run by default.
+	assert_eq!(stdout(&["run", frame.to_str().unwrap()], ""), "");
+	let output = my_vm(&["run", beyond.to_str().unwrap()], "");
+	assert!(!output.status.success());
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(stderr.contains("requires 9 side registers, but the machine only has 8"), "{stderr}");
+	assert_eq!(stdout(&["run", "--registers", "16", beyond.to_str().unwrap()], ""), "");
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn serial() {
+	let dir = temp_dir("serial");
+	let echo = dir.join("echo.asm");
+	fs::write(&echo, "syscall 15\nsyscall 14\nhalt\n").unwrap();
+	let echo = echo.to_str().unwrap();
+
+	// The serial device is only attached on request.
+	assert_eq!(stdout(&["run", "--serial", "stdio", echo], "x"), "x");
+	let output = my_vm(&["run", echo], "x");
+	assert!(!output.status.success());
+	assert!(String::from_utf8_lossy(&output.stderr).contains("No serial device attached"));
+
+	// The keyboard and the serial device would race for stdin.
+	let output = my_vm(&["run", "--serial", "stdio", "--keyboard", echo], "x");
+	assert!(!output.status.success());
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(
+		stderr.contains("The keyboard and the serial device cannot both read stdin"),
+		"{stderr}"
+	);
+	fs::remove_dir_all(dir).unwrap();
+}